@@ -0,0 +1,68 @@
+// Cost of running frames through `Breakpoints::run_frame` instead of
+// `VM::run_frame`, with none set and with one the program never reaches:
+//
+//     cargo bench --bench breakpoints
+
+use chip8_emu_rs::{Breakpoints, VM, VmConfig, assemble};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// a tight arithmetic loop that never waits, draws or halts
+const PROGRAM: &str = "
+loop:
+    ADD V0, 1
+    ADD V1, 3
+    XOR V2, V0
+    SE V3, 0xFF
+    JP loop
+";
+
+// large enough that the clock check between frames doesn't matter
+const IPF: u32 = 10_000;
+const DURATION: Duration = Duration::from_secs(2);
+
+fn vm() -> VM {
+    // no RND in the program
+    let mut vm = VmConfig::new(|| 0).build().expect("default config");
+    vm.load_rom(&assemble(PROGRAM).expect("benchmark program"))
+        .expect("benchmark ROM fits");
+    vm
+}
+
+// instructions per second
+fn measure(mut frame: impl FnMut(&mut VM) -> u32) -> f64 {
+    let mut vm = vm();
+    let mut instructions: u64 = 0;
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        instructions += frame(&mut vm) as u64;
+    }
+    instructions as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let none = Breakpoints::new();
+    let unreached: Breakpoints = [0xFFE].into_iter().collect();
+
+    let baseline = measure(|vm| vm.run_frame(black_box(IPF)).instructions);
+    let runs = [
+        (
+            "no breakpoints",
+            measure(|vm| none.run_frame(vm, IPF).0.instructions),
+        ),
+        (
+            "one breakpoint",
+            measure(|vm| unreached.run_frame(vm, IPF).0.instructions),
+        ),
+    ];
+
+    println!("{:<28} {:>12.0} instructions/s", "VM::run_frame", baseline);
+    for (name, per_second) in runs {
+        println!(
+            "Breakpoints, {:<15} {:>12.0} instructions/s ({:+.1}%)",
+            name,
+            per_second,
+            (per_second / baseline - 1.0) * 100.0
+        );
+    }
+}
@@ -0,0 +1,67 @@
+// DXYN throughput for 15-row sprites, aligned to a byte and not, with the
+// sprite wrapping around the right edge or clipped at it:
+//
+//     cargo bench --bench draw
+
+use chip8_emu_rs::{Quirk, VM, VmConfig, assemble};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// a sprite per pass, moving right by `step` columns and down a row each time
+fn program(step: u8) -> String {
+    format!(
+        "
+        LD I, sprite
+    loop:
+        DRW V0, V1, 15
+        ADD V0, {step}
+        ADD V1, 1
+        JP loop
+    sprite:
+        DB 0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF
+        DB 0x18, 0x3C, 0x7E, 0xFF, 0x7E, 0x3C, 0x18
+        "
+    )
+}
+
+// instructions per pass through the loop
+const PER_DRAW: u64 = 4;
+// steps between clock checks
+const BATCH: u32 = 10_000;
+const DURATION: Duration = Duration::from_secs(2);
+
+// V0 wraps at 256, so steps that divide it keep the sprite on byte
+// boundaries; 7 never does and crosses the right edge every few passes
+fn vm(step: u8, clip: bool) -> VM {
+    let mut vm = VmConfig::new(|| 0)
+        .quirk(Quirk::Clip, clip)
+        .build()
+        .expect("benchmark config");
+    vm.load_rom(&assemble(&program(step)).expect("benchmark program"))
+        .expect("benchmark ROM fits");
+    vm
+}
+
+// sprites drawn per second
+fn measure(mut vm: VM) -> f64 {
+    let mut instructions: u64 = 0;
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        for _ in 0..BATCH {
+            black_box(vm.step().is_ok());
+        }
+        instructions += BATCH as u64;
+    }
+    black_box(vm.framebuffer_hash());
+    instructions as f64 / PER_DRAW as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    for (name, step, clip) in [
+        ("aligned", 8, false),
+        ("unaligned, wrapping", 7, false),
+        ("unaligned, clipped", 7, true),
+    ] {
+        println!("{:<22} {:>12.0} draws/s", name, measure(vm(step, clip)));
+    }
+}
@@ -0,0 +1,74 @@
+// Instructions per second through `VM::step`, with its StepResult thrown away
+// and with all of it kept:
+//
+//     cargo bench --bench step
+//
+// Only VM::new, load_rom and step are used, so the same file runs against
+// revisions from before StepResult for a before/after comparison; there
+// `step` returns `()` and both loops measure the same thing.
+
+use chip8_emu_rs::VM;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// a mixed arithmetic, skip and draw loop that never waits or halts:
+//
+//     LD V0, 0
+//     LD I, sprite
+// loop:
+//     ADD V0, 1
+//     ADD V1, V0
+//     SE V1, 0
+//     XOR V2, V1
+//     DRW V0, V1, 5
+//     JP loop
+// sprite:
+//     DB 0xF0, 0x90, 0x90, 0x90, 0xF0
+const PROGRAM: [u8; 21] = [
+    0x60, 0x00, 0xA2, 0x10, 0x70, 0x01, 0x81, 0x04, 0x31, 0x00, 0x82, 0x13, 0xD0, 0x15, 0x12, 0x04,
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+];
+
+// steps between clock checks
+const BATCH: u32 = 10_000;
+const DURATION: Duration = Duration::from_secs(2);
+
+fn vm() -> VM {
+    let mut vm = VM::new(|| 0);
+    vm.load_rom(&PROGRAM).expect("benchmark ROM fits");
+    vm
+}
+
+// instructions per second
+fn measure(mut step: impl FnMut(&mut VM)) -> f64 {
+    let mut vm = vm();
+    let mut instructions: u64 = 0;
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        for _ in 0..BATCH {
+            step(&mut vm);
+        }
+        instructions += BATCH as u64;
+    }
+    instructions as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let discarded = measure(|vm| {
+        black_box(vm.step().is_ok());
+    });
+    let kept = measure(|vm| {
+        let _ = black_box(vm.step());
+    });
+
+    println!(
+        "{:<22} {:>12.0} instructions/s",
+        "result discarded", discarded
+    );
+    println!(
+        "{:<22} {:>12.0} instructions/s ({:+.1}%)",
+        "whole result kept",
+        kept,
+        (kept / discarded - 1.0) * 100.0
+    );
+}
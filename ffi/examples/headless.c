@@ -0,0 +1,64 @@
+/*
+ * Runs a ROM headless through the C API and prints the screen as ASCII.
+ *
+ * From the ffi/ directory:
+ *
+ *   cargo build --release
+ *   cc examples/headless.c -Iinclude -Ltarget/release -lchip8 -o headless
+ *   LD_LIBRARY_PATH=target/release ./headless rom.ch8 [frames]
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8.h"
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "Usage: %s <rom> [frames]\n", argv[0]);
+        return 1;
+    }
+    long frames = argc > 2 ? strtol(argv[2], NULL, 10) : 600;
+
+    FILE *file = fopen(argv[1], "rb");
+    if (!file) {
+        perror(argv[1]);
+        return 1;
+    }
+    static uint8_t rom[65536];
+    size_t len = fread(rom, 1, sizeof rom, file);
+    fclose(file);
+
+    Chip8 *chip8 = chip8_new(NULL);
+    int err = chip8_load_rom(chip8, rom, len);
+    if (err != CHIP8_OK) {
+        fprintf(stderr, "Error: cannot load ROM (%d)\n", err);
+        chip8_free(chip8);
+        return 1;
+    }
+
+    /* 600Hz is 10 instructions per 60Hz frame */
+    Chip8FrameOutcome outcome;
+    for (long frame = 0; frame < frames; frame++) {
+        err = chip8_run_frame(chip8, 10, &outcome);
+        if (err != CHIP8_OK) {
+            fprintf(stderr, "Error: VM fault (%d) in frame %ld\n", err, frame);
+            break;
+        }
+        if (outcome.halted) {
+            break;
+        }
+    }
+
+    const uint8_t *pixels;
+    size_t count;
+    if (chip8_framebuffer(chip8, &pixels, &count) == CHIP8_OK) {
+        for (size_t i = 0; i < count; i++) {
+            putchar(pixels[i] ? '#' : '.');
+            if ((i + 1) % CHIP8_FB_WIDTH == 0) {
+                putchar('\n');
+            }
+        }
+    }
+    chip8_free(chip8);
+    return err == CHIP8_OK ? 0 : 1;
+}
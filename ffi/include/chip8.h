@@ -0,0 +1,94 @@
+/*
+ * C API of chip8-emu-rs, built by the crate in ffi/ as libchip8 (shared and
+ * static). Functions returning int use 0 for success and a negative
+ * CHIP8_ERR_* code for failure; panics inside the emulator are caught and
+ * reported as CHIP8_ERR_PANIC.
+ *
+ * Every Chip8 pointer must be NULL (CHIP8_ERR_NULL) or a handle returned by
+ * chip8_new that hasn't been passed to chip8_free yet. A handle must not be
+ * used from two threads at once.
+ */
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CHIP8_FB_WIDTH 64
+#define CHIP8_FB_HEIGHT 32
+
+#define CHIP8_OK 0
+#define CHIP8_ERR_NULL (-1)
+#define CHIP8_ERR_INVALID_ARGUMENT (-2)
+#define CHIP8_ERR_ROM_TOO_LARGE (-3)
+#define CHIP8_ERR_STACK_UNDERFLOW (-4)
+#define CHIP8_ERR_STACK_OVERFLOW (-5)
+#define CHIP8_ERR_MEMORY_OUT_OF_BOUNDS (-6)
+#define CHIP8_ERR_PC_OUT_OF_BOUNDS (-7)
+/* any other fault of the VM */
+#define CHIP8_ERR_FAULT (-8)
+#define CHIP8_ERR_PANIC (-9)
+
+typedef struct Chip8 Chip8;
+
+typedef struct Chip8Config {
+    uint32_t memory_size;   /* 4096..=65536 */
+    uint16_t start_address; /* where ROMs are loaded and run from */
+    double cpu_hz;
+    uint64_t seed;          /* CXNN uses a generator seeded with this */
+    bool shift_uses_vy;
+    bool jump_uses_vx;
+    bool clip_sprites;
+    bool load_store_increments_i; /* FX55/FX65 advance I past the registers */
+    bool vf_reset_on_logic;       /* 8XY1/8XY2/8XY3 clear VF */
+    bool display_wait;            /* DXYN ends chip8_run_frame's frame */
+} Chip8Config;
+
+typedef struct Chip8FrameOutcome {
+    uint32_t instructions; /* actually executed, a faulting one included */
+    bool drew;
+    bool waiting_for_key;  /* FX0A found no key down */
+    bool halted;           /* the program jumped to itself */
+} Chip8FrameOutcome;
+
+/* 4K of memory, programs at 0x200, 600Hz, seed 0, no quirks */
+Chip8Config chip8_default_config(void);
+
+/* config may be NULL for the defaults; returns NULL for an invalid config */
+Chip8 *chip8_new(const Chip8Config *config);
+
+/* copies len bytes from rom (which must be readable) and resets the machine;
+ * a ROM shorter than one instruction is CHIP8_ERR_INVALID_ARGUMENT */
+int chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+/* one instruction, timers untouched */
+int chip8_step(Chip8 *chip8);
+
+/* up to ipf instructions, then one 60Hz timer tick; outcome may be NULL */
+int chip8_run_frame(Chip8 *chip8, uint32_t ipf, Chip8FrameOutcome *outcome);
+
+/* key 0x0..=0xF */
+int chip8_set_key(Chip8 *chip8, uint8_t key, bool down);
+
+/*
+ * Points *pixels at CHIP8_FB_WIDTH * CHIP8_FB_HEIGHT bytes, row by row, 1 for
+ * a lit pixel. The buffer is owned by the handle and valid until the next
+ * call on it.
+ */
+int chip8_framebuffer(Chip8 *chip8, const uint8_t **pixels, size_t *len);
+
+/* 1 while the sound timer runs, 0 otherwise */
+int chip8_sound_active(Chip8 *chip8);
+
+void chip8_free(Chip8 *chip8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CHIP8_H */
@@ -0,0 +1,259 @@
+// Flat C API over the emulator core, declared in include/chip8.h.
+//
+// Every entry point catches panics so they never unwind into C, and reports
+// failures as negative CHIP8_ERR_* codes. A `Chip8` handle owns its VM and is
+// only valid between chip8_new and chip8_free.
+
+// the pointer requirements of each function are spelled out in chip8.h
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use chip8_emu_rs::{Chip8Error, DEFAULT_CPU_HZ, FB_HEIGHT, FB_WIDTH, Quirks, VM, VmConfig};
+
+pub const CHIP8_OK: i32 = 0;
+pub const CHIP8_ERR_NULL: i32 = -1;
+pub const CHIP8_ERR_INVALID_ARGUMENT: i32 = -2;
+pub const CHIP8_ERR_ROM_TOO_LARGE: i32 = -3;
+pub const CHIP8_ERR_STACK_UNDERFLOW: i32 = -4;
+pub const CHIP8_ERR_STACK_OVERFLOW: i32 = -5;
+pub const CHIP8_ERR_MEMORY_OUT_OF_BOUNDS: i32 = -6;
+pub const CHIP8_ERR_PC_OUT_OF_BOUNDS: i32 = -7;
+// any other fault of the VM
+pub const CHIP8_ERR_FAULT: i32 = -8;
+pub const CHIP8_ERR_PANIC: i32 = -9;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Chip8Config {
+    pub memory_size: u32,
+    pub start_address: u16,
+    pub cpu_hz: f64,
+    // CXNN always uses the seeded generator, so runs are reproducible
+    pub seed: u64,
+    pub shift_uses_vy: bool,
+    pub jump_uses_vx: bool,
+    pub clip_sprites: bool,
+    pub load_store_increments_i: bool,
+    pub vf_reset_on_logic: bool,
+    pub display_wait: bool,
+}
+
+#[repr(C)]
+pub struct Chip8FrameOutcome {
+    pub instructions: u32,
+    pub drew: bool,
+    pub waiting_for_key: bool,
+    pub halted: bool,
+}
+
+pub struct Chip8 {
+    vm: VM,
+    // one byte per pixel, 0 or 1, refreshed by chip8_framebuffer
+    pixels: [u8; (FB_WIDTH * FB_HEIGHT) as usize],
+}
+
+fn error_code(e: &Chip8Error) -> i32 {
+    match e {
+        Chip8Error::RomTooLarge { .. } => CHIP8_ERR_ROM_TOO_LARGE,
+        Chip8Error::StackUnderflow { .. } => CHIP8_ERR_STACK_UNDERFLOW,
+        Chip8Error::StackOverflow { .. } => CHIP8_ERR_STACK_OVERFLOW,
+        Chip8Error::MemoryOutOfBounds { .. } => CHIP8_ERR_MEMORY_OUT_OF_BOUNDS,
+        Chip8Error::PcOutOfBounds { .. } | Chip8Error::JumpOutOfBounds { .. } => {
+            CHIP8_ERR_PC_OUT_OF_BOUNDS
+        }
+        Chip8Error::InvalidConfig(_) | Chip8Error::RomTooSmall { .. } => CHIP8_ERR_INVALID_ARGUMENT,
+        _ => CHIP8_ERR_FAULT,
+    }
+}
+
+// Runs `f`, turning a panic into an error code instead of unwinding into C
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(CHIP8_ERR_PANIC)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_default_config() -> Chip8Config {
+    let quirks = Quirks::default();
+    Chip8Config {
+        memory_size: chip8_emu_rs::MEMORY_SIZE as u32,
+        start_address: chip8_emu_rs::ROM_START as u16,
+        cpu_hz: DEFAULT_CPU_HZ,
+        seed: 0,
+        shift_uses_vy: quirks.shift_uses_vy,
+        jump_uses_vx: quirks.jump_uses_vx,
+        clip_sprites: quirks.clip_sprites,
+        load_store_increments_i: quirks.load_store_increments_i,
+        vf_reset_on_logic: quirks.vf_reset_on_logic,
+        display_wait: quirks.display_wait,
+    }
+}
+
+// NULL for the defaults. Returns NULL if the configuration is invalid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_new(config: *const Chip8Config) -> *mut Chip8 {
+    panic::catch_unwind(|| {
+        // SAFETY: the caller passes NULL or a valid Chip8Config
+        let config = unsafe { config.as_ref() }
+            .copied()
+            .unwrap_or_else(|| chip8_default_config());
+        let vm = VmConfig::new(|| 0)
+            .rng_seed(config.seed)
+            .memory_size(config.memory_size as usize)
+            .start_address(config.start_address)
+            .cpu_hz(config.cpu_hz)
+            .quirks(Quirks {
+                shift_uses_vy: config.shift_uses_vy,
+                jump_uses_vx: config.jump_uses_vx,
+                clip_sprites: config.clip_sprites,
+                load_store_increments_i: config.load_store_increments_i,
+                vf_reset_on_logic: config.vf_reset_on_logic,
+                display_wait: config.display_wait,
+            })
+            .build();
+        match vm {
+            Ok(mut vm) => {
+                vm.load_font();
+                Box::into_raw(Box::new(Chip8 {
+                    vm,
+                    pixels: [0; (FB_WIDTH * FB_HEIGHT) as usize],
+                }))
+            }
+            Err(_) => ptr::null_mut(),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+// Loads `len` bytes at the start address and resets the machine
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| {
+        if rom.is_null() {
+            return CHIP8_ERR_NULL;
+        }
+        // SAFETY: the caller passes `len` readable bytes at `rom`
+        let rom = unsafe { slice::from_raw_parts(rom, len) };
+        match chip8.vm.load_new_rom(rom) {
+            Ok(()) => CHIP8_OK,
+            Err(e) => error_code(&e),
+        }
+    })
+}
+
+// One instruction, without ticking the timers
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| match chip8.vm.step() {
+        Ok(_) => CHIP8_OK,
+        Err(e) => error_code(&e),
+    })
+}
+
+// One 60Hz frame of up to `ipf` instructions followed by a timer tick.
+// `outcome` may be NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_run_frame(
+    chip8: *mut Chip8,
+    ipf: u32,
+    outcome: *mut Chip8FrameOutcome,
+) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| {
+        let frame = chip8.vm.run_frame(ipf);
+        // SAFETY: the caller passes NULL or a writable Chip8FrameOutcome
+        if let Some(outcome) = unsafe { outcome.as_mut() } {
+            *outcome = Chip8FrameOutcome {
+                instructions: frame.instructions,
+                drew: frame.drew,
+                waiting_for_key: frame.waiting_for_key,
+                halted: frame.halted,
+            };
+        }
+        match frame.fault {
+            Some(e) => error_code(&e),
+            None => CHIP8_OK,
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, down: bool) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| {
+        if key > 0xF {
+            return CHIP8_ERR_INVALID_ARGUMENT;
+        }
+        chip8.vm.set_key(key as usize, down);
+        CHIP8_OK
+    })
+}
+
+// Points `pixels` at FB_WIDTH * FB_HEIGHT bytes, row by row, 1 for a lit
+// pixel. The buffer belongs to the handle and stays valid until the next call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_framebuffer(
+    chip8: *mut Chip8,
+    pixels: *mut *const u8,
+    len: *mut usize,
+) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| {
+        if pixels.is_null() || len.is_null() {
+            return CHIP8_ERR_NULL;
+        }
+        for (idx, pixel) in chip8.pixels.iter_mut().enumerate() {
+            let idx = idx as u32;
+            *pixel = chip8.vm.pixel(idx % FB_WIDTH, idx / FB_WIDTH) as u8;
+        }
+        // SAFETY: both out pointers were checked for NULL above
+        unsafe {
+            *pixels = chip8.pixels.as_ptr();
+            *len = chip8.pixels.len();
+        }
+        CHIP8_OK
+    })
+}
+
+// 1 while the sound timer runs, 0 otherwise
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *mut Chip8) -> i32 {
+    // SAFETY: the caller passes NULL or a handle from chip8_new that hasn't
+    // been freed
+    let Some(chip8) = (unsafe { chip8.as_mut() }) else {
+        return CHIP8_ERR_NULL;
+    };
+    guard(|| (chip8.vm.sound_timer > 0) as i32)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        // SAFETY: the handle came from chip8_new and is freed only once
+        let chip8 = unsafe { Box::from_raw(chip8) };
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(chip8)));
+    }
+}
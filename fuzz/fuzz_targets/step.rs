@@ -0,0 +1,38 @@
+#![no_main]
+
+use chip8_emu_rs::{ROM_START, VM};
+use libfuzzer_sys::fuzz_target;
+
+// instructions executed per input
+const STEPS: usize = 10_000;
+
+// Input layout: PC (2 bytes), pressed keys as a bitmask (2 bytes), then memory
+// contents written from ROM_START on, wrapping around the whole RAM. Any fault
+// must come back as an error from `step`, never as a panic.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+
+    let mut vm = VM::new(|| 0xA5);
+    vm.load_font();
+    vm.pc = u16::from_be_bytes([data[0], data[1]]);
+    let keys = u16::from_be_bytes([data[2], data[3]]);
+    for key in 0..16 {
+        vm.set_key(key, keys & (1 << key) != 0);
+    }
+    let memory_size = vm.memory.len();
+    for (offset, &byte) in data[4..].iter().enumerate() {
+        vm.memory[(ROM_START + offset) % memory_size] = byte;
+    }
+
+    for step in 0..STEPS {
+        if vm.step().is_err() {
+            return;
+        }
+        // timers at their usual rate relative to a 600Hz CPU
+        if step % 10 == 9 {
+            vm.step_timers(1);
+        }
+    }
+});
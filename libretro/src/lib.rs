@@ -0,0 +1,534 @@
+// libretro core over the emulator, so frontends such as RetroArch can run
+// CHIP-8 ROMs with their own save states, rewind and shaders.
+//
+// libretro drives a single core instance through global entry points, so the
+// machine and the frontend's callbacks live in statics. Each retro_run is one
+// 60Hz `run_frame`. Save states are the core's serde snapshot encoded with
+// postcard.
+
+// the pointer requirements are the ones documented in libretro.h
+#![allow(clippy::missing_safety_doc)]
+
+mod retro;
+
+use std::ffi::{CStr, CString, c_char, c_uint, c_void};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{ptr, slice};
+
+use chip8_emu_rs::{FB_HEIGHT, FB_WIDTH, Profile, Quirks, VM, VmConfig};
+use retro::*;
+
+const SAMPLE_RATE: u32 = 44100;
+const FPS: u32 = 60;
+const BEEP_HZ: u32 = 440;
+const BEEP_VOLUME: i16 = 0x1000;
+
+// the SDL frontend's default colors, as XRGB8888
+const BG: u32 = 0xFFB000;
+const FG: u32 = 0x3C393C;
+
+// room for everything in a snapshot besides memory; serialize_size has to
+// stay fixed while a game runs, so it can't be the exact encoded length
+const STATE_OVERHEAD: usize = 1024;
+
+// RetroPad button for each CHIP-8 key: the d-pad on 2/4/6/8, the usual
+// movement keys, and A on 5, the usual action key
+const BUTTONS: [(c_uint, usize, &CStr); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, c"2 (up)"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, c"8 (down)"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, c"4 (left)"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, c"6 (right)"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, c"5"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, c"0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0xA, c"A"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0xB, c"B"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x1, c"1"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x3, c"3"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0x7, c"7"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0x9, c"9"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, c"C"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, c"D"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, c"E"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, c"F"),
+];
+
+// core options: key, then "description; default|other values"
+const PROFILE_OPTION: &CStr = c"chip8_profile";
+const IPF_OPTION: &CStr = c"chip8_ipf";
+const OPTIONS: [(&CStr, &CStr); 2] = [
+    (
+        PROFILE_OPTION,
+        c"Quirk profile; default|cosmac-vip|chip-48|schip-modern|xo-chip",
+    ),
+    (
+        IPF_OPTION,
+        c"Instructions per frame; 10|5|8|12|15|20|30|50|100|200|500|1000",
+    ),
+];
+
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+struct Core {
+    vm: VM,
+    ipf: u32,
+    // set once the VM faults; the screen then stays as it was
+    faulted: bool,
+    // position in the beep's square wave, in samples
+    phase: u32,
+    video: Vec<u32>,
+    audio: Vec<i16>,
+}
+
+// SAFETY: a VM is only not Send because of its observer, and the core never
+// installs one
+unsafe impl Send for Core {}
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn lock_callbacks() -> std::sync::MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn lock_core() -> std::sync::MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match lock_callbacks().environment {
+        // SAFETY: `data` points to what the frontend expects for `cmd`
+        Some(environment) => unsafe { environment(cmd, data) },
+        None => false,
+    }
+}
+
+// current value of a core option, None if the frontend has none
+fn option(key: &CStr) -> Option<String> {
+    let mut var = Variable {
+        key: key.as_ptr(),
+        value: ptr::null(),
+    };
+    if !environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE,
+        &mut var as *mut _ as *mut c_void,
+    ) || var.value.is_null()
+    {
+        return None;
+    }
+    // SAFETY: the frontend answered with a NUL-terminated string
+    Some(
+        unsafe { CStr::from_ptr(var.value) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+fn profile_quirks(name: &str) -> Quirks {
+    match name {
+        "cosmac-vip" => Quirks::for_profile(Profile::CosmacVip),
+        "chip-48" => Quirks::for_profile(Profile::Chip48),
+        "schip-modern" => Quirks::for_profile(Profile::SchipModern),
+        "xo-chip" => Quirks::for_profile(Profile::XoChip),
+        _ => Quirks::default(),
+    }
+}
+
+// shown on screen by the frontend for a few seconds
+fn show_message(msg: &str) {
+    let Ok(msg) = CString::new(msg) else {
+        return;
+    };
+    let mut message = Message {
+        msg: msg.as_ptr(),
+        frames: 3 * FPS,
+    };
+    environment(
+        RETRO_ENVIRONMENT_SET_MESSAGE,
+        &mut message as *mut _ as *mut c_void,
+    );
+}
+
+impl Core {
+    // re-reads the core options; a changed profile applies to the running game
+    fn apply_options(&mut self) {
+        if let Some(profile) = option(PROFILE_OPTION) {
+            self.vm.quirks = profile_quirks(&profile);
+        }
+        if let Some(ipf) = option(IPF_OPTION).and_then(|ipf| ipf.parse().ok()) {
+            self.ipf = ipf;
+        }
+    }
+
+    fn poll_input(&mut self) {
+        let callbacks = lock_callbacks();
+        if let Some(input_poll) = callbacks.input_poll {
+            // SAFETY: callback installed by the frontend
+            unsafe { input_poll() };
+        }
+        let Some(input_state) = callbacks.input_state else {
+            return;
+        };
+        for (button, key, _) in BUTTONS {
+            // SAFETY: callback installed by the frontend
+            let down = unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, button) } != 0;
+            self.vm.set_key(key, down);
+        }
+    }
+
+    fn render(&mut self) {
+        for (idx, pixel) in self.video.iter_mut().enumerate() {
+            let idx = idx as u32;
+            *pixel = if self.vm.pixel(idx % FB_WIDTH, idx / FB_WIDTH) {
+                FG
+            } else {
+                BG
+            };
+        }
+        if let Some(video_refresh) = lock_callbacks().video_refresh {
+            // SAFETY: `video` holds FB_WIDTH * FB_HEIGHT pixels
+            unsafe {
+                video_refresh(
+                    self.video.as_ptr() as *const c_void,
+                    FB_WIDTH,
+                    FB_HEIGHT,
+                    FB_WIDTH as usize * 4,
+                )
+            };
+        }
+    }
+
+    // one frame of stereo samples, a square wave while the sound timer runs
+    fn play_audio(&mut self) {
+        let beeping = self.vm.sound_timer > 0 && !self.faulted;
+        let half_period = SAMPLE_RATE / BEEP_HZ / 2;
+        for frame in self.audio.chunks_exact_mut(2) {
+            let sample = match (beeping, (self.phase / half_period) % 2) {
+                (false, _) => 0,
+                (true, 0) => BEEP_VOLUME,
+                (true, _) => -BEEP_VOLUME,
+            };
+            frame.fill(sample);
+            self.phase = self.phase.wrapping_add(1);
+        }
+        if let Some(audio_batch) = lock_callbacks().audio_batch {
+            // SAFETY: `audio` holds `len / 2` interleaved stereo frames
+            unsafe { audio_batch(self.audio.as_ptr(), self.audio.len() / 2) };
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_environment(cb: EnvironmentFn) {
+    lock_callbacks().environment = Some(cb);
+    let mut variables: Vec<Variable> = OPTIONS
+        .iter()
+        .map(|(key, value)| Variable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect();
+    variables.push(Variable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(cb: VideoRefreshFn) {
+    lock_callbacks().video_refresh = Some(cb);
+}
+
+// audio goes through the batch callback only
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_cb: AudioSampleFn) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(cb: AudioSampleBatchFn) {
+    lock_callbacks().audio_batch = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(cb: InputPollFn) {
+    lock_callbacks().input_poll = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(cb: InputStateFn) {
+    lock_callbacks().input_state = Some(cb);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    *lock_core() = None;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    // SAFETY: the frontend passes a writable retro_system_info
+    let Some(info) = (unsafe { info.as_mut() }) else {
+        return;
+    };
+    *info = SystemInfo {
+        library_name: c"chip8-emu-rs".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    // SAFETY: the frontend passes a writable retro_system_av_info
+    let Some(info) = (unsafe { info.as_mut() }) else {
+        return;
+    };
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: FB_WIDTH,
+            base_height: FB_HEIGHT,
+            max_width: FB_WIDTH,
+            max_height: FB_HEIGHT,
+            aspect_ratio: FB_WIDTH as f32 / FB_HEIGHT as f32,
+        },
+        timing: SystemTiming {
+            fps: FPS as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    // SAFETY: the frontend passes NULL or a valid retro_game_info
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    // SAFETY: `data` holds `size` bytes, need_fullpath being false
+    let rom = unsafe { slice::from_raw_parts(game.data as *const u8, game.size) };
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut _ as *mut c_void,
+    ) {
+        return false;
+    }
+    let mut descriptors: Vec<InputDescriptor> = BUTTONS
+        .iter()
+        .map(|&(button, _, description)| InputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: button,
+            description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(InputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    // XO-CHIP needs its 64K of memory from the start, so the profile picks
+    // the memory size here as well as the quirks
+    let mut config = VmConfig::new(|| 0);
+    if option(PROFILE_OPTION).as_deref() == Some("xo-chip") {
+        config = config.profile(Profile::XoChip);
+    }
+    // the generator's state is part of save states, so rewind and netplay see
+    // the same CXNN results
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut vm = match config.rng_seed(seed).build() {
+        Ok(vm) => vm,
+        Err(_) => return false,
+    };
+    if let Err(e) = vm.load_rom(rom) {
+        show_message(&format!("chip8-emu-rs: {}", e));
+        return false;
+    }
+
+    let mut core = Core {
+        vm,
+        ipf: 10,
+        faulted: false,
+        phase: 0,
+        video: vec![0; (FB_WIDTH * FB_HEIGHT) as usize],
+        audio: vec![0; 2 * (SAMPLE_RATE / FPS) as usize],
+    };
+    core.apply_options();
+    *lock_core() = Some(core);
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    *lock_core() = None;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = lock_core().as_mut() {
+        core.vm.reset();
+        core.faulted = false;
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let mut core = lock_core();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+    let mut updated = false;
+    if environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut _ as *mut c_void,
+    ) && updated
+    {
+        core.apply_options();
+    }
+
+    core.poll_input();
+    if !core.faulted {
+        let outcome = core.vm.run_frame(core.ipf);
+        if let Some(e) = outcome.fault {
+            core.faulted = true;
+            show_message(&format!("chip8-emu-rs: {}", e));
+        }
+    }
+    core.render();
+    core.vm.draw_flag = false;
+    core.play_audio();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    lock_core()
+        .as_ref()
+        .map_or(0, |core| core.vm.memory.len() + STATE_OVERHEAD)
+}
+
+// a little-endian u32 length, the postcard snapshot, then zero padding up to
+// retro_serialize_size
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = lock_core();
+    let Some(core) = core.as_ref() else {
+        return false;
+    };
+    let Ok(state) = postcard::to_allocvec(&core.vm) else {
+        return false;
+    };
+    if data.is_null() || 4 + state.len() > size {
+        return false;
+    }
+    // SAFETY: the frontend passes `size` writable bytes
+    let out = unsafe { slice::from_raw_parts_mut(data as *mut u8, size) };
+    out[..4].copy_from_slice(&(state.len() as u32).to_le_bytes());
+    out[4..4 + state.len()].copy_from_slice(&state);
+    out[4 + state.len()..].fill(0);
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = lock_core();
+    let Some(core) = core.as_mut() else {
+        return false;
+    };
+    if data.is_null() || size < 4 {
+        return false;
+    }
+    // SAFETY: the frontend passes `size` readable bytes
+    let input = unsafe { slice::from_raw_parts(data as *const u8, size) };
+    let len = u32::from_le_bytes([input[0], input[1], input[2], input[3]]) as usize;
+    let Some(state) = input[4..].get(..len) else {
+        return false;
+    };
+    match postcard::from_bytes::<VM>(state) {
+        Ok(vm) => {
+            core.vm = vm;
+            core.faulted = false;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+// RAM for cheat searches and achievements
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match lock_core().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.vm.memory.as_mut_ptr() as *mut c_void,
+        _ => ptr::null_mut(),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match lock_core().as_ref() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.vm.memory.len(),
+        _ => 0,
+    }
+}
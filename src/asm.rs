@@ -0,0 +1,304 @@
+// Assembler for the mnemonic syntax used by the opcode table (Cowgod's
+// notation), one instruction or directive per line:
+//
+//     ; comments run to the end of the line
+//     start:  LD VA, 0x02      ; labels end with a colon
+//             DRW V0, V1, 5
+//             JP start
+//     sprite: DB 0xF0, 0x90    ; raw bytes
+//             DW start         ; big-endian words, labels allowed
+//             .org 0x300       ; zero padding up to an address
+//
+// `.byte` and `.word` are aliases of DB and DW. This is also the syntax the
+// disassembler emits.
+// Mnemonics and register names are case-insensitive, labels are not. Numbers
+// are decimal, 0x/$ hex or 0b binary. The program is assembled to run from
+// ROM_START.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use crate::instruction::Instruction;
+use crate::vm::ROM_START;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+enum Item<'a> {
+    Instruction {
+        mnemonic: &'a str,
+        operands: Vec<&'a str>,
+    },
+    Bytes(Vec<&'a str>),
+    Words(Vec<&'a str>),
+    // zero bytes up to a `.org` address
+    Padding(usize),
+}
+
+impl Item<'_> {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction { .. } => 2,
+            Item::Bytes(values) => values.len(),
+            Item::Words(values) => 2 * values.len(),
+            Item::Padding(len) => *len,
+        }
+    }
+}
+
+// Two passes: the first collects label addresses and item sizes, the second
+// encodes everything with all labels known.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut labels: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut items = Vec::new();
+    let mut addr = ROM_START;
+
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let error = |message: String| AsmError { line, message };
+
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("invalid label '{}'", label)));
+            }
+            if labels.insert(label, addr).is_some() {
+                return Err(error(format!("duplicate label '{}'", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+            None => (text, ""),
+        };
+        let operands: Vec<&str> = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+        let item = match mnemonic.to_ascii_uppercase().as_str() {
+            "DB" | ".BYTE" => Item::Bytes(operands),
+            "DW" | ".WORD" => Item::Words(operands),
+            // resolved right away, since it moves every label after it
+            ".ORG" => {
+                let [target] = operands[..] else {
+                    return Err(error(String::from(".org expects one address")));
+                };
+                let target = resolve(target, &labels, 0xFFFF).map_err(error)? as usize;
+                if target < addr {
+                    return Err(error(format!(
+                        ".org {:#X} is behind the current address {:#X}",
+                        target, addr
+                    )));
+                }
+                Item::Padding(target - addr)
+            }
+            _ => Item::Instruction { mnemonic, operands },
+        };
+        addr += item.size();
+        items.push((line, item));
+    }
+
+    let mut rom = Vec::new();
+    for (line, item) in &items {
+        let error = |message: String| AsmError {
+            line: *line,
+            message,
+        };
+        match item {
+            Item::Bytes(values) => {
+                for value in values {
+                    rom.push(resolve(value, &labels, 0xFF).map_err(error)? as u8);
+                }
+            }
+            Item::Words(values) => {
+                for value in values {
+                    let word = resolve(value, &labels, 0xFFFF).map_err(error)?;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            Item::Padding(len) => rom.resize(rom.len() + len, 0),
+            Item::Instruction { mnemonic, operands } => {
+                let instruction = parse_instruction(mnemonic, operands, &labels).map_err(error)?;
+                rom.extend_from_slice(&instruction.encode().to_be_bytes());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+#[derive(Clone, Copy)]
+enum Operand<'a> {
+    V(u8),
+    I,
+    // [I]
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    // a number or a label, resolved against the expected range
+    Value(&'a str),
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    match text.to_ascii_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        upper => match upper.strip_prefix('V') {
+            Some(reg) if reg.len() == 1 => match u8::from_str_radix(reg, 16) {
+                Ok(x) => Operand::V(x),
+                Err(_) => Operand::Value(text),
+            },
+            _ => Operand::Value(text),
+        },
+    }
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    let starts_well = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    starts_well
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && matches!(operand(text), Operand::Value(_))
+}
+
+pub(crate) fn parse_number(text: &str) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('$')) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn resolve(text: &str, labels: &BTreeMap<&str, usize>, max: u32) -> Result<u16, String> {
+    let value = match parse_number(text) {
+        Some(value) => value,
+        None if is_label(text) => match labels.get(text) {
+            Some(&addr) => addr as u32,
+            None => return Err(format!("undefined label '{}'", text)),
+        },
+        None => return Err(format!("invalid value '{}'", text)),
+    };
+    if value > max {
+        return Err(format!("value {} out of range (max {:#X})", text, max));
+    }
+    Ok(value as u16)
+}
+
+fn parse_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+    labels: &BTreeMap<&str, usize>,
+) -> Result<Instruction, String> {
+    use Instruction::*;
+    use Operand::{B, Dt, F, I, IndirectI, K, St, V, Value};
+
+    let addr = |text| resolve(text, labels, 0xFFF);
+    let byte = |text| resolve(text, labels, 0xFF).map(|v| v as u8);
+    let nibble = |text| resolve(text, labels, 0xF).map(|v| v as u8);
+
+    let upper = mnemonic.to_ascii_uppercase();
+    let ops: Vec<Operand> = operands.iter().map(|text| operand(text)).collect();
+    let instruction = match (upper.as_str(), ops.as_slice()) {
+        ("CLS", []) => Cls,
+        ("RET", []) => Ret,
+        ("SYS", [Value(a)]) => Sys(addr(a)?),
+        ("JP", [Value(a)]) => Jp(addr(a)?),
+        ("JP", [V(0), Value(a)]) => JpV0(addr(a)?),
+        ("CALL", [Value(a)]) => Call(addr(a)?),
+        ("SE", [V(x), V(y)]) => SeVxVy { x: *x, y: *y },
+        ("SE", [V(x), Value(nn)]) => SeVxByte {
+            x: *x,
+            nn: byte(nn)?,
+        },
+        ("SNE", [V(x), V(y)]) => SneVxVy { x: *x, y: *y },
+        ("SNE", [V(x), Value(nn)]) => SneVxByte {
+            x: *x,
+            nn: byte(nn)?,
+        },
+        ("LD", [V(x), V(y)]) => LdVxVy { x: *x, y: *y },
+        ("LD", [V(x), Value(nn)]) => LdVxByte {
+            x: *x,
+            nn: byte(nn)?,
+        },
+        ("LD", [I, Value(a)]) => LdI(addr(a)?),
+        ("LD", [V(x), Dt]) => LdVxDt { x: *x },
+        ("LD", [V(x), K]) => LdVxK { x: *x },
+        ("LD", [Dt, V(x)]) => LdDtVx { x: *x },
+        ("LD", [St, V(x)]) => LdStVx { x: *x },
+        ("LD", [F, V(x)]) => LdFVx { x: *x },
+        ("LD", [B, V(x)]) => LdBVx { x: *x },
+        ("LD", [IndirectI, V(x)]) => LdIVx { x: *x },
+        ("LD", [V(x), IndirectI]) => LdVxI { x: *x },
+        ("ADD", [V(x), V(y)]) => AddVxVy { x: *x, y: *y },
+        ("ADD", [V(x), Value(nn)]) => AddVxByte {
+            x: *x,
+            nn: byte(nn)?,
+        },
+        ("ADD", [I, V(x)]) => AddIVx { x: *x },
+        ("OR", [V(x), V(y)]) => Or { x: *x, y: *y },
+        ("AND", [V(x), V(y)]) => And { x: *x, y: *y },
+        ("XOR", [V(x), V(y)]) => Xor { x: *x, y: *y },
+        ("SUB", [V(x), V(y)]) => Sub { x: *x, y: *y },
+        ("SUBN", [V(x), V(y)]) => Subn { x: *x, y: *y },
+        // without Vy the source is Vx itself, which behaves the same under
+        // either shift quirk
+        ("SHR", [V(x)]) => Shr { x: *x, y: *x },
+        ("SHR", [V(x), V(y)]) => Shr { x: *x, y: *y },
+        ("SHL", [V(x)]) => Shl { x: *x, y: *x },
+        ("SHL", [V(x), V(y)]) => Shl { x: *x, y: *y },
+        ("RND", [V(x), Value(nn)]) => Rnd {
+            x: *x,
+            nn: byte(nn)?,
+        },
+        ("DRW", [V(x), V(y), Value(n)]) => Drw {
+            x: *x,
+            y: *y,
+            n: nibble(n)?,
+        },
+        ("SKP", [V(x)]) => Skp { x: *x },
+        ("SKNP", [V(x)]) => Sknp { x: *x },
+        (
+            "CLS" | "RET" | "SYS" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND"
+            | "XOR" | "SUB" | "SUBN" | "SHR" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP",
+            _,
+        ) => {
+            return Err(format!(
+                "invalid operands for {}: '{}'",
+                upper,
+                operands.join(", ")
+            ));
+        }
+        _ => return Err(format!("unknown mnemonic '{}'", mnemonic)),
+    };
+    Ok(instruction)
+}
@@ -0,0 +1,65 @@
+// Square wave for the buzzer, shared by frontends that make their own samples.
+// Turning the beep on and off ramps the amplitude up and down over a few
+// milliseconds instead of gating it outright, which would click.
+pub struct Tone {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    envelope: Envelope,
+}
+
+impl Tone {
+    // `hz` at `sample_rate`, peaking at `volume`, with `ramp_ms` of linear
+    // attack and release
+    pub fn new(sample_rate: u32, hz: f32, volume: f32, ramp_ms: f32) -> Self {
+        Tone {
+            phase_inc: hz / sample_rate as f32,
+            phase: 0.0,
+            volume,
+            envelope: Envelope::new(sample_rate, ramp_ms),
+        }
+    }
+
+    // the next sample, ramping towards full volume while `gate` is on and
+    // towards silence while it's off
+    pub fn next_sample(&mut self, gate: bool) -> f32 {
+        let sample = if self.phase <= 0.5 {
+            self.volume
+        } else {
+            -self.volume
+        };
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+        self.envelope.apply(gate, sample)
+    }
+
+    pub fn fill(&mut self, gate: bool, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.next_sample(gate);
+        }
+    }
+}
+
+// Linear attack/release gain applied on top of the waveform
+struct Envelope {
+    gain: f32,
+    step: f32,
+}
+
+impl Envelope {
+    fn new(sample_rate: u32, ramp_ms: f32) -> Self {
+        let ramp_samples = (sample_rate as f32 * ramp_ms / 1000.0).max(1.0);
+        Envelope {
+            gain: 0.0,
+            step: 1.0 / ramp_samples,
+        }
+    }
+
+    fn apply(&mut self, gate: bool, sample: f32) -> f32 {
+        if gate {
+            self.gain = (self.gain + self.step).min(1.0);
+        } else {
+            self.gain = (self.gain - self.step).max(0.0);
+        }
+        sample * self.gain
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::condition::Condition;
+use crate::instruction::Instruction;
+use crate::vm::{FrameOutcome, VM};
+
+// Addresses to stop at before the instruction there runs, optionally only
+// when a condition holds, plus conditions and opcode patterns checked before
+// every instruction, for debuggers
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    addrs: HashMap<u16, Option<Condition>>,
+    conditions: Vec<Condition>,
+    opcodes: Vec<OpcodePattern>,
+}
+
+// Opcodes whose bits under `mask` equal `pattern`'s, e.g. F00A/F0FF for
+// every FX0A
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpcodePattern {
+    pub pattern: u16,
+    pub mask: u16,
+}
+
+impl OpcodePattern {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.pattern & self.mask
+    }
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // false if it was already set; replaces any condition it had
+    pub fn insert(&mut self, addr: u16) -> bool {
+        self.addrs.insert(addr, None).is_none()
+    }
+
+    // a breakpoint at `addr` that only stops when `condition` holds
+    pub fn insert_if(&mut self, addr: u16, condition: Condition) {
+        self.addrs.insert(addr, Some(condition));
+    }
+
+    // false if it wasn't set
+    pub fn remove(&mut self, addr: u16) -> bool {
+        self.addrs.remove(&addr).is_some()
+    }
+
+    // sets or clears `addr`, returning whether it's set now
+    pub fn toggle(&mut self, addr: u16) -> bool {
+        if self.remove(addr) {
+            false
+        } else {
+            self.insert(addr);
+            true
+        }
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        self.addrs.contains_key(&addr)
+    }
+
+    // checked before every instruction, wherever it is
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push(condition);
+    }
+
+    // false if there was no such condition
+    pub fn remove_condition(&mut self, condition: &Condition) -> bool {
+        let before = self.conditions.len();
+        self.conditions.retain(|c| c != condition);
+        self.conditions.len() != before
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    // false if it was already set
+    pub fn add_opcode(&mut self, pattern: OpcodePattern) -> bool {
+        if self.opcodes.contains(&pattern) {
+            return false;
+        }
+        self.opcodes.push(pattern);
+        true
+    }
+
+    // false if it wasn't set
+    pub fn remove_opcode(&mut self, pattern: OpcodePattern) -> bool {
+        let before = self.opcodes.len();
+        self.opcodes.retain(|&p| p != pattern);
+        self.opcodes.len() != before
+    }
+
+    pub fn opcodes(&self) -> &[OpcodePattern] {
+        &self.opcodes
+    }
+
+    // the first pattern the opcode at the PC matches
+    pub fn opcode_hit(&self, vm: &VM) -> Option<OpcodePattern> {
+        if self.opcodes.is_empty() {
+            return None;
+        }
+        let pc = vm.pc as usize;
+        let opcode = u16::from_be_bytes([*vm.memory.get(pc)?, *vm.memory.get(pc + 1)?]);
+        self.opcodes
+            .iter()
+            .copied()
+            .find(|pattern| pattern.matches(opcode))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty() && self.conditions.is_empty() && self.opcodes.is_empty()
+    }
+
+    // in address order
+    pub fn sorted(&self) -> Vec<(u16, Option<Condition>)> {
+        let mut addrs: Vec<(u16, Option<Condition>)> =
+            self.addrs.iter().map(|(&addr, &c)| (addr, c)).collect();
+        addrs.sort_unstable_by_key(|&(addr, _)| addr);
+        addrs
+    }
+
+    // whether to stop before the instruction at the PC
+    pub fn hit(&self, vm: &VM) -> bool {
+        let at_addr = match self.addrs.get(&vm.pc) {
+            Some(condition) => condition.is_none_or(|condition| condition.holds(vm)),
+            None => false,
+        };
+        at_addr
+            || self.conditions.iter().any(|condition| condition.holds(vm))
+            || self.opcode_hit(vm).is_some()
+    }
+
+    // `VM::run_frame`, except that it also stops before any instruction one
+    // of the breakpoints stops at and returns its address. A frame cut short
+    // that way doesn't tick the timers, the rest of it hasn't run yet. The PC
+    // is checked before the first instruction too, so to resume from a
+    // breakpoint step over it first. With no breakpoints set this is
+    // `run_frame` itself, so runs that don't use them pay nothing per
+    // instruction.
+    pub fn run_frame(&self, vm: &mut VM, ipf: u32) -> (FrameOutcome, Option<u16>) {
+        if self.is_empty() {
+            return (vm.run_frame(ipf), None);
+        }
+        let mut outcome = FrameOutcome {
+            instructions: 0,
+            drew: false,
+            waiting_for_key: false,
+            halted: false,
+            watch_hit: None,
+            fault: None,
+        };
+        while outcome.instructions < ipf {
+            if self.hit(vm) {
+                return (outcome, Some(vm.pc));
+            }
+            outcome.instructions += 1;
+            match vm.step() {
+                Ok(step) => {
+                    outcome.drew |= step.drew;
+                    if let Some(hit) = vm.watch_hit.take() {
+                        outcome.watch_hit = Some(hit);
+                        break;
+                    }
+                    if step.waiting_for_key {
+                        outcome.waiting_for_key = true;
+                        break;
+                    }
+                    if step.instruction == Instruction::Jp(step.pc) {
+                        outcome.halted = true;
+                        break;
+                    }
+                    if vm.quirks.display_wait && matches!(step.instruction, Instruction::Drw { .. })
+                    {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    outcome.fault = Some(e);
+                    return (outcome, None);
+                }
+            }
+        }
+        vm.step_timers(1);
+        (outcome, None)
+    }
+}
+
+impl FromIterator<u16> for Breakpoints {
+    fn from_iter<I: IntoIterator<Item = u16>>(addrs: I) -> Self {
+        Breakpoints {
+            addrs: addrs.into_iter().map(|addr| (addr, None)).collect(),
+            conditions: Vec::new(),
+            opcodes: Vec::new(),
+        }
+    }
+}
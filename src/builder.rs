@@ -0,0 +1,142 @@
+// ROM images built in Rust, for tests: each method appends one big-endian
+// opcode, so a program reads like its listing without going through the
+// assembler's text.
+//
+//     let rom = RomBuilder::new().ld(0xA, 0x02).add(0xA, 1).jp(0x202).build();
+
+use alloc::vec::Vec;
+
+use crate::instruction::Instruction;
+use crate::vm::ROM_START;
+
+#[derive(Clone, Debug)]
+pub struct RomBuilder {
+    start: u16,
+    bytes: Vec<u8>,
+}
+
+impl Default for RomBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RomBuilder {
+    // for a ROM loaded at 0x200
+    pub fn new() -> Self {
+        Self::at(ROM_START as u16)
+    }
+
+    // for a ROM loaded at `start`, which only `here` depends on
+    pub fn at(start: u16) -> Self {
+        RomBuilder {
+            start,
+            bytes: Vec::new(),
+        }
+    }
+
+    // the address the next opcode lands at, for jumps back to it
+    pub fn here(&self) -> u16 {
+        self.start.wrapping_add(self.bytes.len() as u16)
+    }
+
+    // any word, e.g. `.op(0x6A02)`
+    pub fn op(mut self, opcode: u16) -> Self {
+        self.bytes.extend_from_slice(&opcode.to_be_bytes());
+        self
+    }
+
+    pub fn instruction(self, instruction: Instruction) -> Self {
+        self.op(instruction.encode())
+    }
+
+    // raw data such as sprites, not padded to a word
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    // 00E0
+    pub fn cls(self) -> Self {
+        self.instruction(Instruction::Cls)
+    }
+
+    // 00EE
+    pub fn ret(self) -> Self {
+        self.instruction(Instruction::Ret)
+    }
+
+    // 1NNN
+    pub fn jp(self, nnn: u16) -> Self {
+        self.instruction(Instruction::Jp(nnn))
+    }
+
+    // 2NNN
+    pub fn call(self, nnn: u16) -> Self {
+        self.instruction(Instruction::Call(nnn))
+    }
+
+    // 3XNN
+    pub fn se(self, x: u8, nn: u8) -> Self {
+        self.instruction(Instruction::SeVxByte { x, nn })
+    }
+
+    // 4XNN
+    pub fn sne(self, x: u8, nn: u8) -> Self {
+        self.instruction(Instruction::SneVxByte { x, nn })
+    }
+
+    // 6XNN
+    pub fn ld(self, x: u8, nn: u8) -> Self {
+        self.instruction(Instruction::LdVxByte { x, nn })
+    }
+
+    // 7XNN
+    pub fn add(self, x: u8, nn: u8) -> Self {
+        self.instruction(Instruction::AddVxByte { x, nn })
+    }
+
+    // 8XY0
+    pub fn ld_v(self, x: u8, y: u8) -> Self {
+        self.instruction(Instruction::LdVxVy { x, y })
+    }
+
+    // ANNN
+    pub fn ld_i(self, nnn: u16) -> Self {
+        self.instruction(Instruction::LdI(nnn))
+    }
+
+    // CXNN
+    pub fn rnd(self, x: u8, nn: u8) -> Self {
+        self.instruction(Instruction::Rnd { x, nn })
+    }
+
+    // DXYN
+    pub fn drw(self, x: u8, y: u8, n: u8) -> Self {
+        self.instruction(Instruction::Drw { x, y, n })
+    }
+
+    // FX0A
+    pub fn wait_key(self, x: u8) -> Self {
+        self.instruction(Instruction::LdVxK { x })
+    }
+
+    // FX33
+    pub fn bcd(self, x: u8) -> Self {
+        self.instruction(Instruction::LdBVx { x })
+    }
+
+    // FX55
+    pub fn store(self, x: u8) -> Self {
+        self.instruction(Instruction::LdIVx { x })
+    }
+
+    // FX65
+    pub fn load(self, x: u8) -> Self {
+        self.instruction(Instruction::LdVxI { x })
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.bytes
+    }
+}
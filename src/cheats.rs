@@ -0,0 +1,182 @@
+use chip8_emu_rs::{
+    AudioSink, Control, DisplaySink, FONT, FONT_START, Frontend, InputSource, ROM_START, VM,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::sha1::sha1;
+
+// Cheats for a ROM live next to it, pong.ch8's in pong.cheats, one per line:
+//
+//     NAME = ADDR VALUE
+//
+// e.g. `infinite lives = 0x6a2 9`. Blank lines and lines starting with '#'
+// are skipped. A cheat is on while its address is frozen to its value, see
+// VM::freeze, so save states carry it along.
+#[derive(Clone)]
+pub struct Cheat {
+    pub name: String,
+    pub addr: u16,
+    pub value: u8,
+}
+
+impl Cheat {
+    pub fn is_on(&self, vm: &VM) -> bool {
+        vm.freezes().contains(&(self.addr, self.value))
+    }
+}
+
+pub fn sidecar(rom: &Path) -> PathBuf {
+    rom.with_extension("cheats")
+}
+
+fn parse_cheat(line: &str) -> Result<Cheat, String> {
+    let (name, pin) = line
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME = ADDR VALUE, got '{}'", line))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("the cheat needs a name before '='".to_string());
+    }
+    match pin.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [addr, value] => Ok(Cheat {
+            name: name.to_string(),
+            addr: crate::parse_addr(addr)
+                .ok_or_else(|| format!("expected an address, got '{}'", addr))?,
+            value: crate::parse_byte(value)
+                .ok_or_else(|| format!("expected a byte, got '{}'", value))?,
+        }),
+        _ => Err(format!(
+            "expected an address and a value after '=', got '{}'",
+            pin.trim()
+        )),
+    }
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Cheat>> {
+    let mut cheats = Vec::new();
+    for (idx, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let cheat = parse_cheat(line).map_err(|msg| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", idx + 1, msg),
+            )
+        })?;
+        if let Some(warning) = area_warning(cheat.addr) {
+            eprintln!(
+                "Warning: {}: cheat '{}': {}",
+                path.display(),
+                cheat.name,
+                warning
+            );
+        }
+        cheats.push(cheat);
+    }
+    Ok(cheats)
+}
+
+// Why freezing `addr` is most likely a mistake: programs don't keep their
+// variables in the font or below it
+pub fn area_warning(addr: u16) -> Option<String> {
+    let font = FONT_START..FONT_START + FONT.len();
+    if font.contains(&(addr as usize)) {
+        Some(format!("{:#06X} is in the font", addr))
+    } else if (addr as usize) < ROM_START {
+        Some(format!(
+            "{:#06X} is in the interpreter's area below {:#06X}",
+            addr, ROM_START
+        ))
+    } else {
+        None
+    }
+}
+
+// The cheats of every ROM on the command line that has a file of them, by
+// the ROM's hash, to find the running ROM's after a playlist switch
+#[derive(Clone, Default)]
+pub struct CheatBook(Vec<([u8; 20], Vec<Cheat>)>);
+
+impl CheatBook {
+    pub fn add(&mut self, rom: &[u8], cheats: Vec<Cheat>) {
+        self.0.push((sha1(rom), cheats));
+    }
+
+    pub fn for_rom(&self, vm: &VM) -> &[Cheat] {
+        let sha1 = sha1(vm.rom());
+        self.0
+            .iter()
+            .find(|(hash, _)| *hash == sha1)
+            .map_or(&[], |(_, cheats)| cheats)
+    }
+
+    // Turns the cheat after the first one that's on, and every other one
+    // off; after the last, all of them off. Says what it did.
+    pub fn cycle(&self, vm: &mut VM) -> String {
+        let cheats = self.for_rom(vm);
+        if cheats.is_empty() {
+            return "No cheats for this ROM".to_string();
+        }
+        let next = cheats
+            .iter()
+            .position(|cheat| cheat.is_on(vm))
+            .map_or(0, |idx| idx + 1);
+        for cheat in cheats {
+            if cheat.is_on(vm) {
+                vm.unfreeze(cheat.addr);
+            }
+        }
+        let Some(cheat) = cheats.get(next) else {
+            return "Cheats off".to_string();
+        };
+        match vm.freeze(cheat.addr, cheat.value) {
+            Ok(()) => format!("Cheat: {}", cheat.name),
+            Err(e) => format!("Cheat {}: {}", cheat.name, e),
+        }
+    }
+}
+
+// Wraps a frontend and answers its cheat hotkey (F10) with the next cheat
+pub struct CheatKey<'a> {
+    inner: &'a mut dyn Frontend,
+    book: &'a CheatBook,
+}
+
+impl<'a> CheatKey<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, book: &'a CheatBook) -> Self {
+        CheatKey { inner, book }
+    }
+}
+
+impl DisplaySink for CheatKey<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for CheatKey<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for CheatKey<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        match self.inner.poll(vm) {
+            Control::NextCheat => {
+                let message = self.book.cycle(vm);
+                self.inner.show_message(&message);
+                Control::Continue
+            }
+            control => control,
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// Breakpoint conditions such as `v3 == 0x1f` or `i >= 0xE00 && dt != 0`:
+// one or two comparisons of a register against a literal. Parsed once, then
+// evaluated without allocating, so checking them on every instruction
+// doesn't change the timing being debugged.
+
+use core::{error, fmt};
+
+use crate::vm::VM;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    V(u8),
+    I,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    pub operand: Operand,
+    pub cmp: Cmp,
+    pub value: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub first: Comparison,
+    // `&& second`
+    pub second: Option<Comparison>,
+}
+
+// What was wrong and where, as a byte range of the parsed text, so callers
+// can underline the offending token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError {
+    pub start: usize,
+    pub len: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.start + 1)
+    }
+}
+
+impl error::Error for ConditionError {}
+
+impl Operand {
+    fn read(self, vm: &VM) -> u16 {
+        match self {
+            Operand::V(x) => vm.v[x as usize] as u16,
+            Operand::I => vm.i,
+            Operand::Dt => vm.delay_timer as u16,
+            Operand::St => vm.sound_timer as u16,
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(&self, vm: &VM) -> bool {
+        let left = self.operand.read(vm);
+        match self.cmp {
+            Cmp::Eq => left == self.value,
+            Cmp::Ne => left != self.value,
+            Cmp::Lt => left < self.value,
+            Cmp::Le => left <= self.value,
+            Cmp::Gt => left > self.value,
+            Cmp::Ge => left >= self.value,
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, ConditionError> {
+        let mut tokens = Tokens { text, pos: 0 };
+        let first = parse_comparison(&mut tokens)?;
+        let second = match tokens.next() {
+            None => None,
+            Some(("&&", _)) => Some(parse_comparison(&mut tokens)?),
+            Some((token, start)) => {
+                return Err(error_at(
+                    token,
+                    start,
+                    "expected && or the end of the condition",
+                ));
+            }
+        };
+        if let Some((token, start)) = tokens.next() {
+            return Err(error_at(token, start, "expected the end of the condition"));
+        }
+        Ok(Condition { first, second })
+    }
+
+    pub fn holds(&self, vm: &VM) -> bool {
+        self.first.holds(vm) && self.second.is_none_or(|second| second.holds(vm))
+    }
+}
+
+fn parse_comparison(tokens: &mut Tokens) -> Result<Comparison, ConditionError> {
+    let (token, start) = tokens.expect("expected a register, I, DT or ST")?;
+    let operand = if token.eq_ignore_ascii_case("i") {
+        Operand::I
+    } else if token.eq_ignore_ascii_case("dt") {
+        Operand::Dt
+    } else if token.eq_ignore_ascii_case("st") {
+        Operand::St
+    } else {
+        match token.split_at_checked(1) {
+            Some(("v" | "V", x)) if x.len() == 1 => Operand::V(
+                u8::from_str_radix(x, 16)
+                    .map_err(|_| error_at(token, start, "expected a register, I, DT or ST"))?,
+            ),
+            _ => return Err(error_at(token, start, "expected a register, I, DT or ST")),
+        }
+    };
+    let (token, start) = tokens.expect("expected ==, !=, <, <=, > or >=")?;
+    let cmp = match token {
+        "==" => Cmp::Eq,
+        "!=" => Cmp::Ne,
+        "<" => Cmp::Lt,
+        "<=" => Cmp::Le,
+        ">" => Cmp::Gt,
+        ">=" => Cmp::Ge,
+        _ => return Err(error_at(token, start, "expected ==, !=, <, <=, > or >=")),
+    };
+    let (token, start) = tokens.expect("expected a number")?;
+    let value = match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => token.parse(),
+    }
+    .map_err(|_| error_at(token, start, "expected a number"))?;
+    Ok(Comparison {
+        operand,
+        cmp,
+        value,
+    })
+}
+
+// Words and operators with their byte offsets; `v3==1` splits like `v3 == 1`
+struct Tokens<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn next(&mut self) -> Option<(&'a str, usize)> {
+        let rest = &self.text[self.pos..];
+        let start = self.pos + (rest.len() - rest.trim_start().len());
+        let rest = &self.text[start..];
+        let first = rest.chars().next()?;
+        let is_operator = |c: char| "=!<>&".contains(c);
+        let len = if is_operator(first) {
+            rest.find(|c| !is_operator(c))
+        } else if first.is_ascii_alphanumeric() {
+            rest.find(|c: char| !c.is_ascii_alphanumeric())
+        } else {
+            Some(first.len_utf8())
+        }
+        .unwrap_or(rest.len());
+        self.pos = start + len;
+        Some((&rest[..len], start))
+    }
+
+    // the next token, or `message` pointing just past the end
+    fn expect(&mut self, message: &'static str) -> Result<(&'a str, usize), ConditionError> {
+        self.next().ok_or(ConditionError {
+            start: self.text.len(),
+            len: 1,
+            message,
+        })
+    }
+}
+
+fn error_at(token: &str, start: usize, message: &'static str) -> ConditionError {
+    ConditionError {
+        start,
+        len: token.len(),
+        message,
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::V(x) => write!(f, "v{:x}", x),
+            Operand::I => write!(f, "i"),
+            Operand::Dt => write!(f, "dt"),
+            Operand::St => write!(f, "st"),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cmp = match self.cmp {
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        };
+        write!(f, "{} {} {:#X}", self.operand, cmp, self.value)
+    }
+}
+
+// in the syntax `parse` reads
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        if let Some(second) = &self.second {
+            write!(f, " && {}", second)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use alloc::format;
+
+use crate::error::Chip8Error;
+use crate::frontend::DEFAULT_CPU_HZ;
+use crate::rng::Rng;
+use crate::vm::{FONT_BYTES, FONT_START, MEMORY_SIZE, ROM_START, VM};
+
+// largest address space 16-bit I and PC registers can reach
+pub(crate) const MAX_MEMORY_SIZE: usize = 0x10000;
+
+// Interpreter families whose behaviour differs in the quirks below
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    CosmacVip,
+    Chip48,
+    SchipModern,
+    XoChip,
+}
+
+// A single behaviour switch, for setting quirks one at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quirk {
+    // 8XY6/8XYE shift Vy into Vx instead of shifting Vx in place
+    Shift,
+    // BNNN jumps to XNN + Vx instead of NNN + V0
+    Jump,
+    // sprites are cut off at the screen edges instead of wrapping around
+    Clip,
+    // FX55/FX65 leave I at I + X + 1 instead of unchanged
+    LoadStore,
+    // 8XY1/8XY2/8XY3 clear VF after the OR/AND/XOR
+    VfReset,
+    // DXYN ends the frame: the VIP waited for the display interrupt to draw
+    DisplayWait,
+}
+
+// What FX33 and FX55 writing below ROM_START does. Nothing a program needs
+// lives there but the font, so such a write is usually a stray I.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LowMemory {
+    // write as usual
+    #[default]
+    Off,
+    // write, and tell the observer (`VmObserver::on_low_memory_write`)
+    Warn,
+    // fault with Chip8Error::ProtectedWrite instead of writing
+    Trap,
+}
+
+// Behaviour that differs between interpreters, consulted by `step`. The
+// defaults are this emulator's historical behaviour rather than any profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    pub shift_uses_vy: bool,
+    pub jump_uses_vx: bool,
+    pub clip_sprites: bool,
+    pub load_store_increments_i: bool,
+    pub vf_reset_on_logic: bool,
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub fn for_profile(profile: Profile) -> Self {
+        match profile {
+            Profile::CosmacVip => Quirks {
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: true,
+                load_store_increments_i: true,
+                vf_reset_on_logic: true,
+                display_wait: true,
+            },
+            // CHIP-48 actually left I at I + X, which neither setting matches;
+            // the ROMs written for it work with I unchanged
+            Profile::Chip48 | Profile::SchipModern => Quirks {
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                clip_sprites: true,
+                load_store_increments_i: false,
+                vf_reset_on_logic: false,
+                display_wait: false,
+            },
+            Profile::XoChip => Quirks {
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: false,
+                load_store_increments_i: true,
+                vf_reset_on_logic: false,
+                display_wait: false,
+            },
+        }
+    }
+
+    pub fn get(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::Shift => self.shift_uses_vy,
+            Quirk::Jump => self.jump_uses_vx,
+            Quirk::Clip => self.clip_sprites,
+            Quirk::LoadStore => self.load_store_increments_i,
+            Quirk::VfReset => self.vf_reset_on_logic,
+            Quirk::DisplayWait => self.display_wait,
+        }
+    }
+
+    pub fn set(&mut self, quirk: Quirk, on: bool) {
+        match quirk {
+            Quirk::Shift => self.shift_uses_vy = on,
+            Quirk::Jump => self.jump_uses_vx = on,
+            Quirk::Clip => self.clip_sprites = on,
+            Quirk::LoadStore => self.load_store_increments_i = on,
+            Quirk::VfReset => self.vf_reset_on_logic = on,
+            Quirk::DisplayWait => self.display_wait = on,
+        }
+    }
+}
+
+// Everything a VM is constructed from. Settings are applied in call order, so
+// `.profile()` followed by `.quirk()` overrides a single quirk of the profile.
+#[derive(Clone, Debug)]
+pub struct VmConfig {
+    pub(crate) quirks: Quirks,
+    pub(crate) memory_size: usize,
+    pub(crate) start_address: u16,
+    pub(crate) cpu_hz: f64,
+    pub(crate) rng: Rng,
+    pub(crate) strict_sys: bool,
+    pub(crate) strict_jumps: bool,
+    pub(crate) halt_on_spin: bool,
+    pub(crate) low_memory: LowMemory,
+    pub(crate) track_hot_spots: bool,
+    pub(crate) track_executed: bool,
+    pub(crate) strict_smc: bool,
+    pub(crate) log_collisions: usize,
+}
+
+impl VmConfig {
+    pub fn new(rng: fn() -> u8) -> Self {
+        VmConfig {
+            quirks: Quirks::default(),
+            memory_size: MEMORY_SIZE,
+            start_address: ROM_START as u16,
+            cpu_hz: DEFAULT_CPU_HZ,
+            rng: Rng::External(rng),
+            strict_sys: false,
+            strict_jumps: false,
+            halt_on_spin: false,
+            low_memory: LowMemory::Off,
+            track_hot_spots: false,
+            track_executed: false,
+            strict_smc: false,
+            log_collisions: 0,
+        }
+    }
+
+    // quirks of `profile`; XO-CHIP also gets its 64K address space
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.quirks = Quirks::for_profile(profile);
+        if profile == Profile::XoChip {
+            self.memory_size = MAX_MEMORY_SIZE;
+        }
+        self
+    }
+
+    pub fn quirk(mut self, quirk: Quirk, on: bool) -> Self {
+        self.quirks.set(quirk, on);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    pub fn start_address(mut self, addr: u16) -> Self {
+        self.start_address = addr;
+        self
+    }
+
+    pub fn cpu_hz(mut self, hz: f64) -> Self {
+        self.cpu_hz = hz;
+        self
+    }
+
+    pub fn rng(mut self, rng: fn() -> u8) -> Self {
+        self.rng = Rng::External(rng);
+        self
+    }
+
+    // deterministic CXNN: same seed and inputs give bit-identical runs
+    pub fn rng_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::seeded(seed);
+        self
+    }
+
+    // CXNN always yields `byte & NN`, taking randomness out entirely for
+    // golden-image tests of ROMs like maze generators; not for playing
+    pub fn rng_fixed(mut self, byte: u8) -> Self {
+        self.rng = Rng::Fixed(byte);
+        self
+    }
+
+    // 0NNN faults with InvalidOpcode instead of being ignored, to catch ROMs
+    // written for a machine with real SYS routines; 0000, cleared memory,
+    // stays a no-op
+    pub fn strict_sys(mut self, on: bool) -> Self {
+        self.strict_sys = on;
+        self
+    }
+
+    // BNNN past the end of memory faults with JumpOutOfBounds instead of
+    // wrapping around to the start, for ROMs whose offset table went wrong
+    pub fn strict_jumps(mut self, on: bool) -> Self {
+        self.strict_jumps = on;
+        self
+    }
+
+    // a 1NNN jumping to itself stops the VM with Chip8Error::Halted, for
+    // test ROMs that end that way; off by default since games spin like that
+    // too, waiting for the timers
+    pub fn halt_on_spin(mut self, on: bool) -> Self {
+        self.halt_on_spin = on;
+        self
+    }
+
+    // guard the font and the interpreter's area below ROM_START against
+    // FX33 and FX55
+    pub fn protect_low_memory(mut self, mode: LowMemory) -> Self {
+        self.low_memory = mode;
+        self
+    }
+
+    // count how often each address is executed, for `VM::hot_spots`
+    pub fn track_hot_spots(mut self, on: bool) -> Self {
+        self.track_hot_spots = on;
+        self
+    }
+
+    // remember which addresses instructions were fetched from, for
+    // `VM::was_executed`, and tell the observer whenever FX33 or FX55
+    // writes over one (`VmObserver::on_code_write`)
+    pub fn track_executed(mut self, on: bool) -> Self {
+        self.track_executed = on;
+        self
+    }
+
+    // stop on self-modifying code as on a write watchpoint, see
+    // `VM::take_watch_hit`; implies track_executed
+    pub fn strict_smc(mut self, on: bool) -> Self {
+        self.strict_smc = on;
+        self
+    }
+
+    // keep the last `len` draw collisions for `VM::collision_log`; 0, the
+    // default, keeps none and costs nothing
+    pub fn log_collisions(mut self, len: usize) -> Self {
+        self.log_collisions = len;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), Chip8Error> {
+        if !(MEMORY_SIZE..=MAX_MEMORY_SIZE).contains(&self.memory_size) {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "memory size {} is outside {}..={}",
+                self.memory_size, MEMORY_SIZE, MAX_MEMORY_SIZE
+            )));
+        }
+        if self.start_address as usize >= self.memory_size {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "start address {:04X} is beyond memory size {}",
+                self.start_address, self.memory_size
+            )));
+        }
+        // load_rom and reset copy the font in first, so a ROM over it would
+        // be half overwritten
+        if (self.start_address as usize) < FONT_START + FONT_BYTES {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "start address {:04X} would put the ROM over the font at {:04X}-{:04X}",
+                self.start_address,
+                FONT_START,
+                FONT_START + FONT_BYTES - 1
+            )));
+        }
+        if !(self.cpu_hz.is_finite() && self.cpu_hz > 0.0) {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "clock rate {} Hz is not a positive number",
+                self.cpu_hz
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<VM, Chip8Error> {
+        VM::with_config(self)
+    }
+}
@@ -0,0 +1,41 @@
+use chip8_emu_rs::{Chip8Error, VM, instructions_per_frame};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// --conformance: the ROM runs headless for a fixed number of instructions
+// with no keys pressed, and before each one the state goes to the file as
+//
+//     <pc> <i> <v0> <v1> ... <vf>
+//
+// in fixed-width uppercase hex, so a trace from another emulator written in
+// the same columns can be compared with a plain diff. The timers tick once
+// per frame's worth of instructions, as when running.
+pub const DEFAULT_CYCLES: u64 = 10_000;
+
+// Returns the VM's fault, if it stopped on one, after writing the lines up to
+// the instruction that faulted
+pub fn run(vm: &mut VM, path: &Path, cycles: u64) -> io::Result<Option<Chip8Error>> {
+    let mut out = BufWriter::with_capacity(1 << 16, File::create(path)?);
+    let ipf = instructions_per_frame(vm) as u64;
+    for _ in 0..cycles {
+        write_line(&mut out, vm)?;
+        if let Err(e) = vm.step() {
+            out.flush()?;
+            return Ok(Some(e));
+        }
+        if vm.cycles().is_multiple_of(ipf) {
+            vm.step_timers(1);
+        }
+    }
+    out.flush()?;
+    Ok(None)
+}
+
+fn write_line(out: &mut impl Write, vm: &VM) -> io::Result<()> {
+    write!(out, "{:04X} {:04X}", vm.pc, vm.i)?;
+    for v in vm.v {
+        write!(out, " {:02X}", v)?;
+    }
+    writeln!(out)
+}
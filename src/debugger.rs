@@ -0,0 +1,1231 @@
+use chip8_emu_rs::{
+    Access, Breakpoints, Chip8Error, Condition, Control, FB_HEIGHT, FB_WIDTH, Frontend,
+    OpcodePattern, Symbols, TIMER_HZ, VM, WatchHit, Watchpoint, instructions_per_frame,
+};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cheats::{self, CheatBook};
+use crate::hotloop::{self, Detector};
+use crate::parse_addr;
+use crate::postmortem::describe_frame;
+use crate::rewind::Rewind;
+use crate::search::{Filter, Search};
+
+const HELP: &str = "\
+Commands:
+  s [n]            step n instructions (default 1), printing each
+  rs [n]           step n instructions back (default 1), as far as about
+                   64000 instructions or the last set, poke or undo
+  n [COUNT]        run COUNT frames (default 1), a frame's worth of
+                   instructions and a timer tick each; `.` in the window
+                   does one, and repeats while held
+  c                continue until a breakpoint or Ctrl+C
+  regs             show registers, stack pointer and timers
+  mem ADDR [LEN]   hex and ASCII dump of LEN bytes (default 16) from ADDR;
+                   > marks the byte at I, * bytes changed since the last
+                   mem, ! both
+  mem follow i [LEN]
+                   the same around I, 48 bytes by default
+  find BYTES       list the addresses holding BYTES, e.g. find A2 F0
+  search eq VALUE  find a variable: the first keeps the addresses holding
+                   VALUE (decimal, or hex with 0x), later ones only those
+                   of them that still do
+  search changed   keep the addresses that changed since the last search
+  search unchanged keep the addresses that didn't
+  search list      show the addresses left and their values
+  search reset     start the next search eq over from all of memory
+  fb               print the screen as text
+  sprite [N]       draw the N bytes at I as a sprite (default: the height of
+                   the DXYN at PC, or 15)
+  sprites ADDR COUNT [HEIGHT]
+                   draw COUNT sprites of HEIGHT bytes (default 5) side by
+                   side from ADDR, to find graphics in a ROM
+  bt               show the call stack, innermost call first
+  b [ADDR]         set a breakpoint, or list them
+  b ADDR if COND   break at ADDR only when COND holds, e.g. v3 == 0x1f
+  b if COND        break wherever COND holds, e.g. i >= 0xe00 && dt != 0
+  d ADDR           delete the breakpoint at ADDR
+  d if COND        delete a condition set with `b if`
+  bop [PATTERN [MASK]]
+                   break on opcodes matching PATTERN under MASK (default
+                   FFFF), e.g. F00A F0FF, or on draw, keywait, rand, cls,
+                   call, ret, bcd, store or load; lists them without one
+  dop PATTERN [MASK]
+                   delete an opcode breakpoint
+  w [SPEC]         watch memory, or list the watchpoints; SPEC is ADDR or
+                   ADDR-END, then :r, :w (the default) or :rw
+  dw ADDR[-END]    delete the watchpoints covering ADDR or the range
+  set REG VALUE    set V0-VF, I, PC, DT or ST, e.g. set v3 0x1f
+  poke ADDR BYTE...
+                   write bytes to memory from ADDR, e.g. poke 0x6a2 0xff 1
+  undo             take back the last set or poke
+  freeze ADDR VALUE
+                   keep writing VALUE to ADDR after every instruction
+  freezes          list the frozen addresses
+  unfreeze ADDR|all
+                   stop freezing ADDR, or every address
+  cheats           list the cheats from the ROM's .cheats file
+  cheat N          turn the Nth cheat on or off
+  q                quit
+b, d, bop, dop, w, dw, regs, mem, find, search list, search reset, freeze,
+freezes, unfreeze, cheats, cheat, fb, sprite, sprites and bt also work while
+running.";
+
+const PROMPT: &str = "(chip8) ";
+
+enum Action {
+    Prompt,
+    Continue,
+    Quit,
+}
+
+// Runs the ROM under a command prompt on stdin. The frontend keeps being
+// polled and presented at 60Hz while paused, so the window stays responsive.
+// Ctrl+C interrupts `c` instead of killing the process.
+pub struct Debugger {
+    breakpoints: Breakpoints,
+    // lines typed at the prompt, read on their own thread so waiting for one
+    // never blocks the frontend
+    commands: Receiver<String>,
+    interrupted: Arc<AtomicBool>,
+    paused: bool,
+    ipf: u32,
+    // hot loops are announced as they're found
+    hot_loops: Option<Rc<RefCell<Detector>>>,
+    // memory as the last `mem` showed it, to mark what changed since
+    mem_seen: Option<Vec<u8>>,
+    // what `set` and `poke` overwrote, most recent last, for `undo`
+    edits: Vec<Edit>,
+    // snapshots and events for `rs`
+    rewind: Rewind,
+    // the addresses `search` has narrowed down so far
+    search: Option<Search>,
+    // for `cheats` and `cheat`
+    cheats: CheatBook,
+    // labels from --symbols, shown with addresses and accepted for them
+    symbols: Symbols,
+}
+
+// A register `set` can change
+#[derive(Clone, Copy)]
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+}
+
+impl Register {
+    fn parse(name: &str) -> Option<Register> {
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "i" => Some(Register::I),
+            "pc" => Some(Register::Pc),
+            "dt" => Some(Register::Dt),
+            "st" => Some(Register::St),
+            _ => {
+                let x = u8::from_str_radix(name.strip_prefix('v')?, 16).ok()?;
+                (x < 16).then_some(Register::V(x as usize))
+            }
+        }
+    }
+
+    fn get(self, vm: &VM) -> u16 {
+        match self {
+            Register::V(x) => vm.v[x] as u16,
+            Register::I => vm.i,
+            Register::Pc => vm.pc,
+            Register::Dt => vm.delay_timer as u16,
+            Register::St => vm.sound_timer as u16,
+        }
+    }
+
+    // why `value` doesn't fit, if it doesn't
+    fn set(self, vm: &mut VM, value: u16) -> Result<(), String> {
+        let byte = || u8::try_from(value).map_err(|_| format!("{} holds a byte.", self));
+        match self {
+            Register::V(x) => vm.v[x] = byte()?,
+            Register::I => vm.i = value,
+            Register::Pc if value as usize + 1 >= vm.memory.len() => {
+                return Err(format!(
+                    "PC must be below {:#06X}, the end of memory.",
+                    vm.memory.len() - 1
+                ));
+            }
+            Register::Pc => vm.pc = value,
+            Register::Dt => vm.delay_timer = byte()?,
+            Register::St => vm.set_sound_timer(byte()?),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+        }
+    }
+}
+
+// What a `set` or `poke` replaced
+enum Edit {
+    Register(Register, u16),
+    Memory(usize, Vec<u8>),
+}
+
+impl Debugger {
+    // `paused` is --debug, without it the ROM runs until a --break address
+    pub fn new(
+        vm: &VM,
+        breakpoints: Breakpoints,
+        paused: bool,
+        hot_loops: Option<Rc<RefCell<Detector>>>,
+        cheats: CheatBook,
+        symbols: Symbols,
+    ) -> io::Result<Self> {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))?;
+        let (tx, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if tx.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Debugger {
+            breakpoints,
+            commands,
+            interrupted,
+            paused,
+            ipf: instructions_per_frame(vm),
+            hot_loops,
+            mem_seen: None,
+            edits: Vec::new(),
+            rewind: Rewind::new(vm),
+            search: None,
+            cheats,
+            symbols,
+        })
+    }
+
+    pub fn run(&mut self, vm: &mut VM, frontend: &mut dyn Frontend) -> Result<(), Chip8Error> {
+        let frame_dt = Duration::from_secs_f64(1.0 / TIMER_HZ);
+        if self.paused {
+            println!("Paused. Type 'h' for help.");
+            print_context(vm, &self.symbols);
+            prompt();
+        } else {
+            println!("Running. Ctrl+C pauses, type 'h' for help.");
+        }
+
+        loop {
+            let frame_start = Instant::now();
+            let control = frontend.poll(vm);
+            self.rewind.checkpoint(vm);
+            match control {
+                Control::Quit => return Ok(()),
+                Control::StepFrame if self.paused => {
+                    self.step_frames(vm, 1);
+                    prompt();
+                }
+                _ => {}
+            }
+            loop {
+                let line = match self.commands.try_recv() {
+                    Ok(line) => line,
+                    Err(TryRecvError::Empty) => break,
+                    // stdin closed
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+                match self.command(line.trim(), vm) {
+                    Action::Prompt if self.paused => prompt(),
+                    Action::Prompt => {}
+                    Action::Continue => {
+                        self.resume(vm);
+                        break;
+                    }
+                    Action::Quit => return Ok(()),
+                }
+            }
+            if self.paused {
+                // Ctrl+C only means something while running
+                self.interrupted.store(false, Ordering::Relaxed);
+            } else if self.interrupted.swap(false, Ordering::Relaxed) {
+                println!();
+                self.pause(vm, "Interrupted");
+            } else {
+                self.run_frame(vm);
+            }
+
+            frontend.present(vm);
+            vm.draw_flag = false;
+            frontend.set_beep(!self.paused && vm.sound_timer > 0);
+            if let Some(rest) = frame_dt.checked_sub(frame_start.elapsed()) {
+                thread::sleep(rest);
+            }
+        }
+    }
+
+    fn pause(&mut self, vm: &VM, reason: &str) {
+        self.paused = true;
+        println!("{} at {}", reason, describe_addr(&self.symbols, vm.pc));
+        print_context(vm, &self.symbols);
+        prompt();
+    }
+
+    fn resume(&mut self, vm: &mut VM) {
+        self.paused = false;
+        // leave the breakpoint we're stopped at instead of hitting it again
+        if self.breakpoints.hit(vm) {
+            match self.step(vm) {
+                Ok(_) => {
+                    if let Some(hit) = vm.take_watch_hit() {
+                        print_watch_hit(&hit, &self.symbols);
+                        self.pause(vm, "Stopped");
+                    }
+                }
+                Err(e) => self.pause(vm, &format!("Error: {}", e)),
+            }
+        }
+    }
+
+    fn run_frame(&mut self, vm: &mut VM) {
+        if let Some(reason) = self.advance_frame(vm, self.ipf) {
+            self.pause(vm, &reason);
+        }
+    }
+
+    // `count` frames as `c` runs them, stopping early where `c` would pause
+    fn step_frames(&mut self, vm: &mut VM, count: u64) {
+        for _ in 0..count {
+            let mut ipf = self.ipf;
+            // leave the breakpoint we're stopped at, as `c` does
+            if self.breakpoints.hit(vm) {
+                if let Err(e) = vm.step() {
+                    println!("Error: {}", e);
+                    break;
+                }
+                ipf -= 1;
+            }
+            if let Some(reason) = self.advance_frame(vm, ipf) {
+                println!("{} at {}", reason, describe_addr(&self.symbols, vm.pc));
+                break;
+            }
+            self.rewind.checkpoint(vm);
+        }
+        println!("Frame {}", vm.frames());
+        print_context(vm, &self.symbols);
+    }
+
+    // Up to `ipf` instructions and a timer tick, printing what stopped them
+    // early and returning why if the debugger should pause
+    fn advance_frame(&mut self, vm: &mut VM, ipf: u32) -> Option<String> {
+        let frames = vm.frames();
+        let (outcome, hit) = self.breakpoints.run_frame(vm, ipf);
+        if vm.frames() != frames {
+            self.rewind.record_tick(vm);
+        }
+        self.report_hot_loops(vm);
+        if let Some(e) = outcome.fault {
+            Some(format!("Error: {}", e))
+        } else if hit.is_some() {
+            if let Some(pattern) = self.breakpoints.opcode_hit(vm) {
+                let pc = vm.pc as usize;
+                let opcode = u16::from_be_bytes([vm.memory[pc], vm.memory[pc + 1]]);
+                println!(
+                    "Opcode breakpoint {}: {}",
+                    describe_pattern(pattern),
+                    self.symbols.disassemble_at(vm.pc, opcode)
+                );
+            }
+            Some("Breakpoint".to_string())
+        } else if let Some(hit) = outcome.watch_hit {
+            print_watch_hit(&hit, &self.symbols);
+            Some("Stopped".to_string())
+        } else {
+            None
+        }
+    }
+
+    fn report_hot_loops(&self, vm: &VM) {
+        let Some(detector) = &self.hot_loops else {
+            return;
+        };
+        for (start, end) in detector.borrow_mut().take_found() {
+            println!(
+                "Hot loop at {:#06X}-{:#06X}, nothing drawn or read from the keypad since entering it:",
+                start, end
+            );
+            hotloop::print_disassembly(vm, start, end, "  ");
+        }
+    }
+
+    // One instruction, with the timers ticking once per frame's worth of
+    // them as in --slowmo, so stepping sees about the same timing as running
+    fn step(&mut self, vm: &mut VM) -> Result<(u16, u16), Chip8Error> {
+        let step = vm.step()?;
+        if vm.cycles().is_multiple_of(self.ipf as u64) {
+            vm.step_timers(1);
+            self.rewind.record_tick(vm);
+        }
+        Ok((step.pc, step.opcode))
+    }
+
+    // the whole of memory is remembered for the next call's change marks
+    fn print_memory(&mut self, vm: &VM, addr: usize, len: usize) {
+        let seen = self.mem_seen.as_deref();
+        for line in memory_lines(&vm.memory, addr, len, vm.i as usize, seen) {
+            println!("{}", line);
+        }
+        self.mem_seen = Some(vm.memory.clone());
+    }
+
+    // says so if it can't, or if it's likely a mistake
+    fn freeze(&mut self, vm: &mut VM, addr: u16, value: u8) -> bool {
+        if let Err(e) = vm.freeze(addr, value) {
+            println!("Error: {}", e);
+            return false;
+        }
+        if let Some(warning) = cheats::area_warning(addr) {
+            println!("Warning: {}.", warning);
+        }
+        // memory changed outside the program
+        self.rewind.restart(vm);
+        println!("Froze {:#06X} = {:#04X}", addr, value);
+        true
+    }
+
+    fn report_search(&self) {
+        match self
+            .search
+            .as_ref()
+            .map_or(0, |search| search.candidates().len())
+        {
+            0 => println!("No addresses left; search reset starts over."),
+            count => println!("{} address(es) left", count),
+        }
+    }
+
+    fn command(&mut self, line: &str, vm: &mut VM) -> Action {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Action::Prompt;
+        };
+        let args: Vec<&str> = words.collect();
+        match (name, args.as_slice()) {
+            ("s" | "rs" | "n" | "c" | "set" | "poke" | "undo", _) if !self.paused => {
+                println!("Running, Ctrl+C pauses first.")
+            }
+            ("s", [] | [_]) => {
+                let Some(count) = args.first().map_or(Some(1), |n| n.parse::<u64>().ok()) else {
+                    println!("s expects a number of instructions.");
+                    return Action::Prompt;
+                };
+                for _ in 0..count {
+                    match self.step(vm) {
+                        Ok((pc, opcode)) => {
+                            println!("{}", self.symbols.disassemble_at(pc, opcode));
+                            self.rewind.checkpoint(vm);
+                        }
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            break;
+                        }
+                    }
+                    if let Some(hit) = vm.take_watch_hit() {
+                        print_watch_hit(&hit, &self.symbols);
+                        break;
+                    }
+                    // stepping on would only repeat the FX0A
+                    if vm.is_waiting_for_key() {
+                        println!("Waiting for a key.");
+                        break;
+                    }
+                }
+                self.report_hot_loops(vm);
+                print_context(vm, &self.symbols);
+            }
+            ("rs", [] | [_]) => {
+                let Some(count) = args.first().map_or(Some(1), |n| n.parse::<u64>().ok()) else {
+                    println!("rs expects a number of instructions.");
+                    return Action::Prompt;
+                };
+                match self.rewind.step_back(vm, count) {
+                    Ok(()) => println!("Back at cycle {}", vm.cycles()),
+                    Err(msg) => println!("{}", msg),
+                }
+                print_context(vm, &self.symbols);
+            }
+            ("n", [] | [_]) => match args.first().map_or(Some(1), |n| n.parse::<u64>().ok()) {
+                Some(count) => self.step_frames(vm, count),
+                None => println!("n expects a number of frames."),
+            },
+            ("c", []) => return Action::Continue,
+            ("regs", []) => print_registers(vm),
+            ("mem", ["follow", "i"] | ["follow", "i", _]) => {
+                let len = match args.get(2) {
+                    Some(len) => parse_addr(len),
+                    None => Some(48),
+                };
+                match len {
+                    // from the row before the one I is in
+                    Some(len) => {
+                        let start = (vm.i as usize & !0xF).saturating_sub(16);
+                        self.print_memory(vm, start, len as usize);
+                    }
+                    None => println!("mem follow i expects an optional length."),
+                }
+            }
+            ("mem", [addr] | [addr, _]) => {
+                let len = match args.get(1) {
+                    Some(len) => parse_addr(len),
+                    None => Some(16),
+                };
+                match (parse_addr(addr), len) {
+                    (Some(addr), Some(len)) => self.print_memory(vm, addr as usize, len as usize),
+                    _ => println!("mem expects an address and an optional length, or follow i."),
+                }
+            }
+            ("find", [_, ..]) => match parse_bytes(&args) {
+                Some(pattern) => print_matches(vm, &pattern),
+                None => println!("find expects bytes in hex, e.g. find A2 F0 or find A2F0."),
+            },
+            // memory changes under a running ROM as it's read
+            ("search", ["eq", _] | ["changed" | "unchanged"]) if !self.paused => {
+                println!("Running, Ctrl+C pauses first.")
+            }
+            ("search", ["eq", value]) => match crate::parse_byte(value) {
+                Some(value) => {
+                    match &mut self.search {
+                        Some(search) => search.refine(vm, Filter::Eq(value)),
+                        None => self.search = Some(Search::start(vm, value)),
+                    }
+                    self.report_search();
+                }
+                None => println!("search eq expects a byte, e.g. search eq 3."),
+            },
+            ("search", [filter @ ("changed" | "unchanged")]) => match &mut self.search {
+                Some(search) => {
+                    let filter = match *filter {
+                        "changed" => Filter::Changed,
+                        _ => Filter::Unchanged,
+                    };
+                    search.refine(vm, filter);
+                    self.report_search();
+                }
+                None => println!("Start a search with search eq VALUE."),
+            },
+            ("search", ["list"]) => match &self.search {
+                Some(search) => print_candidates(vm, search.candidates()),
+                None => println!("No search in progress."),
+            },
+            ("search", ["reset"]) => {
+                self.search = None;
+                println!("Search reset.");
+            }
+            ("fb", []) => print_framebuffer(vm),
+            ("sprite", [] | [_]) => {
+                let rows = match args.first() {
+                    Some(n) => parse_addr(n).filter(|n| (1..=15).contains(n)),
+                    None => Some(sprite_rows(vm) as u16),
+                };
+                match rows {
+                    Some(rows) => print_sprites(vm, vm.i as usize, 1, rows as usize),
+                    None => println!("sprite expects a height from 1 to 15."),
+                }
+            }
+            ("sprites", [addr, count] | [addr, count, _]) => {
+                let height = match args.get(2) {
+                    Some(height) => parse_addr(height).filter(|h| (1..=15).contains(h)),
+                    None => Some(5),
+                };
+                match (parse_addr(addr), parse_addr(count), height) {
+                    (Some(addr), Some(count), Some(height)) => {
+                        print_sprites(vm, addr as usize, count as usize, height as usize)
+                    }
+                    _ => println!("sprites expects an address, a count and a height from 1 to 15."),
+                }
+            }
+            ("bt", []) => print_backtrace(vm, &self.symbols),
+            ("b", []) if self.breakpoints.is_empty() => println!("No breakpoints."),
+            ("b", []) => {
+                for (addr, condition) in self.breakpoints.sorted() {
+                    let addr = describe_addr(&self.symbols, addr);
+                    match condition {
+                        Some(condition) => println!("Break at {} if {}", addr, condition),
+                        None => println!("Break at {}", addr),
+                    }
+                }
+                for condition in self.breakpoints.conditions() {
+                    println!("Break if {}", condition);
+                }
+            }
+            ("b", _) => match parse_break(line[1..].trim(), &self.symbols) {
+                Ok(BreakSpec::At(addr, None)) => {
+                    self.breakpoints.insert(addr);
+                    println!("Breakpoint set at {}", describe_addr(&self.symbols, addr));
+                }
+                Ok(BreakSpec::At(addr, Some(condition))) => {
+                    self.breakpoints.insert_if(addr, condition);
+                    println!(
+                        "Breakpoint set at {} if {}",
+                        describe_addr(&self.symbols, addr),
+                        condition
+                    );
+                }
+                Ok(BreakSpec::Anywhere(condition)) => {
+                    self.breakpoints.add_condition(condition);
+                    println!("Breaking if {}", condition);
+                }
+                Err(msg) => println!("{}", msg),
+            },
+            ("d", _) => match parse_break(line[1..].trim(), &self.symbols) {
+                Ok(BreakSpec::At(addr, _)) if self.breakpoints.remove(addr) => {
+                    println!("Breakpoint deleted at {:#06X}", addr);
+                }
+                Ok(BreakSpec::At(addr, _)) => println!("No breakpoint at {:#06X}", addr),
+                Ok(BreakSpec::Anywhere(condition))
+                    if self.breakpoints.remove_condition(&condition) =>
+                {
+                    println!("Deleted the condition {}", condition);
+                }
+                Ok(BreakSpec::Anywhere(condition)) => println!("No condition {}", condition),
+                Err(msg) => println!("{}", msg),
+            },
+            ("bop", []) if self.breakpoints.opcodes().is_empty() => {
+                println!("No opcode breakpoints.")
+            }
+            ("bop", []) => {
+                for &pattern in self.breakpoints.opcodes() {
+                    println!("Break on {}", describe_pattern(pattern));
+                }
+            }
+            ("bop", spec) => match parse_opcode_pattern(spec) {
+                Some(pattern) => {
+                    self.breakpoints.add_opcode(pattern);
+                    println!("Breaking on {}", describe_pattern(pattern));
+                }
+                None => println!("bop expects PATTERN [MASK] in hex, or a name such as keywait."),
+            },
+            ("dop", spec) => match parse_opcode_pattern(spec) {
+                Some(pattern) if self.breakpoints.remove_opcode(pattern) => {
+                    println!(
+                        "Deleted the opcode breakpoint {}",
+                        describe_pattern(pattern)
+                    );
+                }
+                Some(pattern) => println!("No opcode breakpoint {}", describe_pattern(pattern)),
+                None => println!("dop expects PATTERN [MASK] in hex, or a name such as keywait."),
+            },
+            ("w", []) if vm.watchpoints().is_empty() => println!("No watchpoints."),
+            ("w", []) => {
+                for watchpoint in vm.watchpoints() {
+                    println!("Watching {}", watchpoint);
+                }
+            }
+            ("w", [spec]) => match parse_watch(spec) {
+                Some(watchpoint) => {
+                    vm.add_watchpoint(watchpoint);
+                    println!("Watching {}", watchpoint);
+                }
+                None => println!("w expects ADDR or ADDR-END, then :r, :w or :rw."),
+            },
+            ("dw", [range]) => match parse_range(range) {
+                Some((start, end)) => {
+                    let removed = vm.remove_watchpoints(start, end);
+                    println!("Deleted {} watchpoint(s)", removed);
+                }
+                None => println!("dw expects ADDR or ADDR-END."),
+            },
+            ("set", [name, value]) => {
+                let (Some(register), Some(value)) = (Register::parse(name), parse_addr(value))
+                else {
+                    println!("set expects V0-VF, I, PC, DT or ST and a value.");
+                    return Action::Prompt;
+                };
+                let old = register.get(vm);
+                match register.set(vm, value) {
+                    Ok(()) => {
+                        self.edits.push(Edit::Register(register, old));
+                        self.rewind.restart(vm);
+                        println!("{} = {:#X} (was {:#X})", register, value, old);
+                    }
+                    Err(msg) => println!("{}", msg),
+                }
+            }
+            ("poke", [addr, bytes @ ..]) if !bytes.is_empty() => {
+                let bytes: Option<Vec<u8>> = bytes
+                    .iter()
+                    .map(|byte| parse_addr(byte)?.try_into().ok())
+                    .collect();
+                let (Some(addr), Some(bytes)) = (parse_addr(addr), bytes) else {
+                    println!("poke expects an address and bytes, e.g. poke 0x6a2 0xff 1.");
+                    return Action::Prompt;
+                };
+                let (addr, end) = (addr as usize, addr as usize + bytes.len());
+                if end > vm.memory.len() {
+                    println!(
+                        "{} byte(s) from {:#06X} run past the end of memory at {:#06X}.",
+                        bytes.len(),
+                        addr,
+                        vm.memory.len()
+                    );
+                    return Action::Prompt;
+                }
+                let old = vm.memory[addr..end].to_vec();
+                vm.memory[addr..end].copy_from_slice(&bytes);
+                self.edits.push(Edit::Memory(addr, old));
+                self.rewind.restart(vm);
+                println!("Wrote {} byte(s) at {:#06X}", bytes.len(), addr);
+                if (addr..end).contains(&(vm.pc as usize))
+                    || (addr..end).contains(&(vm.pc as usize + 1))
+                {
+                    print_context(vm, &self.symbols);
+                }
+            }
+            ("undo", []) => match self.edits.pop() {
+                Some(Edit::Register(register, old)) => {
+                    // it fit before, so it fits again
+                    let _ = register.set(vm, old);
+                    self.rewind.restart(vm);
+                    println!("{} = {:#X} again", register, old);
+                }
+                Some(Edit::Memory(addr, old)) => {
+                    vm.memory[addr..addr + old.len()].copy_from_slice(&old);
+                    self.rewind.restart(vm);
+                    println!("Restored {} byte(s) at {:#06X}", old.len(), addr);
+                }
+                None => println!("Nothing to undo."),
+            },
+            ("freeze", [addr, value]) => {
+                let (Some(addr), Some(value)) = (parse_addr(addr), crate::parse_byte(value)) else {
+                    println!("freeze expects an address and a byte, e.g. freeze 0x6a2 9.");
+                    return Action::Prompt;
+                };
+                self.freeze(vm, addr, value);
+            }
+            ("freezes", []) if vm.freezes().is_empty() => println!("No frozen addresses."),
+            ("freezes", []) => {
+                let cheats = self.cheats.for_rom(vm);
+                for &(addr, value) in vm.freezes() {
+                    let cheat = cheats
+                        .iter()
+                        .find(|cheat| (cheat.addr, cheat.value) == (addr, value));
+                    match cheat {
+                        Some(cheat) => println!("{:#06X} = {:#04X} ({})", addr, value, cheat.name),
+                        None => println!("{:#06X} = {:#04X}", addr, value),
+                    }
+                }
+            }
+            ("unfreeze", ["all"]) => {
+                let addrs: Vec<u16> = vm.freezes().iter().map(|&(addr, _)| addr).collect();
+                for &addr in &addrs {
+                    vm.unfreeze(addr);
+                }
+                println!("Unfroze {} address(es)", addrs.len());
+            }
+            ("unfreeze", [addr]) => match parse_addr(addr) {
+                Some(addr) if vm.unfreeze(addr) => println!("Unfroze {:#06X}", addr),
+                Some(addr) => println!("{:#06X} isn't frozen.", addr),
+                None => println!("unfreeze expects an address or all."),
+            },
+            ("cheats", []) => {
+                let cheats = self.cheats.for_rom(vm);
+                if cheats.is_empty() {
+                    println!("No cheats for this ROM; they go in a .cheats file next to it.");
+                }
+                for (idx, cheat) in cheats.iter().enumerate() {
+                    let state = if cheat.is_on(vm) { "on" } else { "off" };
+                    println!(
+                        "{:>2}  {:<3}  {:#06X} = {:#04X}  {}",
+                        idx + 1,
+                        state,
+                        cheat.addr,
+                        cheat.value,
+                        cheat.name
+                    );
+                }
+            }
+            ("cheat", [n]) => {
+                let cheats = self.cheats.for_rom(vm);
+                let Some(cheat) = n
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| cheats.get(n.checked_sub(1)?))
+                    .cloned()
+                else {
+                    println!("cheat expects a number from `cheats`.");
+                    return Action::Prompt;
+                };
+                if cheat.is_on(vm) {
+                    vm.unfreeze(cheat.addr);
+                    self.rewind.restart(vm);
+                    println!("Cheat off: {}", cheat.name);
+                } else if self.freeze(vm, cheat.addr, cheat.value) {
+                    println!("Cheat on: {}", cheat.name);
+                }
+            }
+            ("q", []) => return Action::Quit,
+            ("h" | "help", []) => println!("{}", HELP),
+            _ => println!("Unknown command '{}'. Type 'h' for help.", line),
+        }
+        Action::Prompt
+    }
+}
+
+// named opcode classes for `bop` and --break-op
+const OPCODE_NAMES: [(&str, u16, u16); 9] = [
+    ("draw", 0xD000, 0xF000),
+    ("keywait", 0xF00A, 0xF0FF),
+    ("rand", 0xC000, 0xF000),
+    ("cls", 0x00E0, 0xFFFF),
+    ("call", 0x2000, 0xF000),
+    ("ret", 0x00EE, 0xFFFF),
+    ("bcd", 0xF033, 0xF0FF),
+    ("store", 0xF055, 0xF0FF),
+    ("load", 0xF065, 0xF0FF),
+];
+
+// a name from OPCODE_NAMES, or PATTERN [MASK] in hex with an optional 0x
+pub fn parse_opcode_pattern(words: &[&str]) -> Option<OpcodePattern> {
+    let hex = |word: &str| {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        u16::from_str_radix(digits, 16).ok()
+    };
+    let (pattern, mask) = match words {
+        [word] => match OPCODE_NAMES
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(word))
+        {
+            Some(&(_, pattern, mask)) => (pattern, mask),
+            None => (hex(word)?, 0xFFFF),
+        },
+        [pattern, mask] => (hex(pattern)?, hex(mask)?),
+        _ => return None,
+    };
+    Some(OpcodePattern { pattern, mask })
+}
+
+// F00A/F0FF (keywait)
+fn describe_pattern(pattern: OpcodePattern) -> String {
+    let name = OPCODE_NAMES.iter().find(|&&(_, p, m)| {
+        OpcodePattern {
+            pattern: p,
+            mask: m,
+        } == pattern
+    });
+    match name {
+        Some((name, _, _)) => format!("{:04X}/{:04X} ({})", pattern.pattern, pattern.mask, name),
+        None => format!("{:04X}/{:04X}", pattern.pattern, pattern.mask),
+    }
+}
+
+pub enum BreakSpec {
+    At(u16, Option<Condition>),
+    Anywhere(Condition),
+}
+
+// `ADDR`, `ADDR if COND` or `[if] COND`, for `b`, `d` and --break-if; ADDR
+// may be a label from `symbols`. Errors repeat the text with the offending
+// token underlined.
+pub fn parse_break(spec: &str, symbols: &Symbols) -> Result<BreakSpec, String> {
+    let (first, rest) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+    let addr = parse_addr(first).or_else(|| symbols.addr(first));
+    if addr.is_none() && first.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(underline(spec, 0, first.len(), "expected an address"));
+    }
+    // a word that can't start a condition, alone or before `if`, was meant
+    // as a label
+    let rest_trimmed = rest.trim_start();
+    if addr.is_none()
+        && first != "if"
+        && Register::parse(first).is_none()
+        && first.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && (rest_trimmed.is_empty() || rest_trimmed.starts_with("if "))
+    {
+        return Err(unknown_label(first, symbols));
+    }
+    let condition = match (addr, rest.trim_start()) {
+        (Some(addr), "") => return Ok(BreakSpec::At(addr, None)),
+        (Some(_), rest) => rest
+            .strip_prefix("if")
+            .filter(|cond| cond.starts_with(char::is_whitespace)),
+        (None, _) => Some(spec.strip_prefix("if ").unwrap_or(spec)),
+    };
+    let Some(condition) = condition else {
+        return Err(underline(
+            spec,
+            spec.len() - rest.trim_start().len(),
+            rest.trim_start().len(),
+            "expected `if` after the address",
+        ));
+    };
+    let offset = spec.len() - condition.len();
+    let condition = Condition::parse(condition)
+        .map_err(|e| underline(spec, offset + e.start, e.len, e.message))?;
+    Ok(match addr {
+        Some(addr) => BreakSpec::At(addr, Some(condition)),
+        None => BreakSpec::Anywhere(condition),
+    })
+}
+
+// says which labels come close, if any do
+pub fn unknown_label(name: &str, symbols: &Symbols) -> String {
+    if symbols.is_empty() {
+        return format!(
+            "Unknown label '{}': no symbols loaded, see --symbols.",
+            name
+        );
+    }
+    match symbols.close_matches(name).as_slice() {
+        [] => format!("Unknown label '{}'.", name),
+        matches => format!(
+            "Unknown label '{}'. Did you mean {}?",
+            name,
+            matches.join(", ")
+        ),
+    }
+}
+
+// `0x0312`, or `0x0312 (draw_score+0x4)` with a label at or before it
+fn describe_addr(symbols: &Symbols, addr: u16) -> String {
+    match symbols.describe(addr) {
+        Some(label) => format!("{:#06X} ({})", addr, label),
+        None => format!("{:#06X}", addr),
+    }
+}
+
+// `message`, then `text` with `len` bytes from `start` marked underneath
+fn underline(text: &str, start: usize, len: usize, message: &str) -> String {
+    format!(
+        "{}:\n  {}\n  {}{}",
+        message,
+        text,
+        " ".repeat(start),
+        "^".repeat(len.max(1))
+    )
+}
+
+// ADDR or ADDR-END, then an optional :r, :w or :rw, for --watch and `w`.
+// Writes only by default, that's what a corrupted variable needs.
+pub fn parse_watch(spec: &str) -> Option<Watchpoint> {
+    let (range, mode) = spec.split_once(':').unwrap_or((spec, "w"));
+    let (start, end) = parse_range(range)?;
+    let (read, write) = match mode {
+        "r" => (true, false),
+        "w" => (false, true),
+        "rw" => (true, true),
+        _ => return None,
+    };
+    Some(Watchpoint {
+        start,
+        end,
+        read,
+        write,
+    })
+}
+
+// ADDR or ADDR-END, inclusive
+fn parse_range(range: &str) -> Option<(u16, u16)> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+        None => (parse_addr(range)?, parse_addr(range)?),
+    };
+    (start <= end).then_some((start, end))
+}
+
+// 16 bytes per line with their ASCII, cut off at the end of memory. Bytes
+// that differ from `seen` get a '*', the one at I a '>', or '!' for both.
+fn memory_lines(
+    memory: &[u8],
+    addr: usize,
+    len: usize,
+    i: usize,
+    seen: Option<&[u8]>,
+) -> Vec<String> {
+    let end = addr.saturating_add(len).min(memory.len());
+    let start = addr.min(end);
+    let mut lines = Vec::new();
+    for row in (start..end).step_by(16) {
+        let bytes = &memory[row..(row + 16).min(end)];
+        let mut line = format!("{:#06X} ", row);
+        for (offset, &byte) in bytes.iter().enumerate() {
+            let at = row + offset;
+            let changed = seen.is_some_and(|seen| seen.get(at) != Some(&byte));
+            let marker = match (at == i, changed) {
+                (true, true) => '!',
+                (true, false) => '>',
+                (false, true) => '*',
+                (false, false) => ' ',
+            };
+            line.push(marker);
+            line.push_str(&format!("{:02X}", byte));
+        }
+        // keeps the ASCII column lined up on a short last row
+        line.push_str(&"   ".repeat(16 - bytes.len()));
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| {
+                if (0x20..0x7F).contains(&b) {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(format!("{}  |{}|", line, ascii));
+    }
+    lines
+}
+
+fn print_watch_hit(hit: &WatchHit, symbols: &Symbols) {
+    let instruction = symbols.disassemble_at(hit.pc, hit.opcode);
+    match hit.access {
+        Access::Read => println!(
+            "Watchpoint: {:#06X} read ({:02X}) by {}",
+            hit.addr, hit.old, instruction
+        ),
+        Access::Write => println!(
+            "Watchpoint: {:#06X} written {:02X} -> {:02X} by {}",
+            hit.addr, hit.old, hit.new, instruction
+        ),
+    }
+}
+
+fn prompt() {
+    print!("{}", PROMPT);
+    let _ = io::stdout().flush();
+}
+
+// the instruction about to run, marked, with the two before and after it
+// and the labels among them
+fn print_context(vm: &VM, symbols: &Symbols) {
+    let start = vm.pc.saturating_sub(4);
+    for addr in (start..=vm.pc.saturating_add(4)).step_by(2) {
+        let at = addr as usize;
+        if let (Some(&hi), Some(&lo)) = (vm.memory.get(at), vm.memory.get(at + 1)) {
+            if let Some(name) = symbols.name(addr) {
+                println!("   {}:", name);
+            }
+            let marker = if addr == vm.pc { "->" } else { "  " };
+            println!(
+                "{} {}",
+                marker,
+                symbols.disassemble_at(addr, u16::from_be_bytes([hi, lo]))
+            );
+        }
+    }
+}
+
+fn print_registers(vm: &VM) {
+    let regs: Vec<String> = (0..16)
+        .map(|x| format!("V{:X}={:02X}", x, vm.v[x]))
+        .collect();
+    println!("{}", regs[..8].join(" "));
+    println!("{}", regs[8..].join(" "));
+    println!(
+        "PC={:04X} I={:04X} SP={} DT={:02X} ST={:02X}",
+        vm.pc,
+        vm.i,
+        vm.stack().len(),
+        vm.delay_timer,
+        vm.sound_timer
+    );
+}
+
+fn print_backtrace(vm: &VM, symbols: &Symbols) {
+    if vm.stack().is_empty() {
+        println!("No calls in progress.");
+    }
+    for (depth, &ret) in vm.stack().iter().rev().enumerate() {
+        println!("#{:<2} {}", depth, describe_frame(vm, ret, symbols));
+    }
+}
+
+// hex bytes for `find`, separately or run together, with optional 0x
+fn parse_bytes(words: &[&str]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for word in words {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return None;
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            bytes.push(u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?);
+        }
+    }
+    Some(bytes)
+}
+
+// the first FIND_LIMIT addresses, eight to a line, and how many there are
+const FIND_LIMIT: usize = 64;
+
+fn print_matches(vm: &VM, pattern: &[u8]) {
+    let found: Vec<usize> = vm
+        .memory
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .map(|(addr, _)| addr)
+        .collect();
+    if found.is_empty() {
+        println!("Not found.");
+        return;
+    }
+    for chunk in found[..found.len().min(FIND_LIMIT)].chunks(8) {
+        let addrs: Vec<String> = chunk.iter().map(|addr| format!("{:#06X}", addr)).collect();
+        println!("{}", addrs.join(" "));
+    }
+    if found.len() > FIND_LIMIT {
+        println!("... {} matches in all", found.len());
+    } else {
+        println!("{} match(es)", found.len());
+    }
+}
+
+// `search list`: the first FIND_LIMIT candidates with what they hold now,
+// four to a line
+fn print_candidates(vm: &VM, candidates: &[usize]) {
+    if candidates.is_empty() {
+        println!("No addresses left.");
+        return;
+    }
+    for chunk in candidates[..candidates.len().min(FIND_LIMIT)].chunks(4) {
+        let entries: Vec<String> = chunk
+            .iter()
+            .map(|&addr| match vm.memory.get(addr) {
+                Some(value) => format!("{:#06X} = {:3}", addr, value),
+                None => format!("{:#06X} = ---", addr),
+            })
+            .collect();
+        println!("{}", entries.join("   "));
+    }
+    if candidates.len() > FIND_LIMIT {
+        println!("... {} addresses in all", candidates.len());
+    }
+}
+
+// The rows DXYN would draw from I: the N of the DXYN at PC if that's what's
+// next, otherwise the 15 a sprite can be at most
+pub fn sprite_rows(vm: &VM) -> usize {
+    let pc = vm.pc as usize;
+    match (vm.memory.get(pc), vm.memory.get(pc + 1)) {
+        (Some(&hi), Some(&lo)) if hi >> 4 == 0xD && lo & 0xF != 0 => (lo & 0xF) as usize,
+        _ => 15,
+    }
+}
+
+// sprites to a line of output, each 8 pixels wide plus a gap
+const SPRITES_PER_LINE: usize = 8;
+
+// `count` sprites of `height` bytes from `addr`, side by side under their
+// addresses; a single one also gets its bytes in hex. Stops at the end of
+// memory.
+fn print_sprites(vm: &VM, addr: usize, count: usize, height: usize) {
+    let starts: Vec<usize> = (0..count)
+        .map(|idx| addr + idx * height)
+        .take_while(|&start| start < vm.memory.len())
+        .collect();
+    if starts.is_empty() {
+        println!("{:#06X} is past the end of memory.", addr);
+    }
+    for line in starts.chunks(SPRITES_PER_LINE) {
+        let header: Vec<String> = line
+            .iter()
+            .map(|start| format!("{:<8}", format!("{:#06X}", start)))
+            .collect();
+        println!("{}", header.join("  ").trim_end());
+        for row in 0..height {
+            let cells: Vec<String> = line
+                .iter()
+                .map(|&start| match vm.memory.get(start + row) {
+                    Some(&byte) => (0..8)
+                        .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                        .collect(),
+                    None => " ".repeat(8),
+                })
+                .collect();
+            match (count, vm.memory.get(line[0] + row)) {
+                (1, Some(byte)) => println!("{}  {:02X}", cells[0], byte),
+                _ => println!("{}", cells.join("  ").trim_end()),
+            }
+        }
+    }
+}
+
+fn print_framebuffer(vm: &VM) {
+    for y in 0..FB_HEIGHT {
+        let row: String = (0..FB_WIDTH)
+            .map(|x| if vm.pixel(x, y) { '#' } else { '.' })
+            .collect();
+        println!("{}", row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory_lines;
+
+    const MEMORY_SIZE: usize = 0x1000;
+
+    fn memory() -> Vec<u8> {
+        (0..MEMORY_SIZE).map(|addr| addr as u8).collect()
+    }
+
+    #[test]
+    fn first_row_marks_i_and_changes() {
+        let memory = memory();
+        let mut seen = memory.clone();
+        seen[0x000] = 0xFF;
+        seen[0x003] = 0xFF;
+        let lines = memory_lines(&memory, 0x000, 16, 0x000, Some(&seen));
+        assert_eq!(
+            lines,
+            ["0x0000 !00 01 02*03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F  |................|"]
+        );
+    }
+
+    #[test]
+    fn last_row_stops_at_the_end_of_memory() {
+        let memory = memory();
+        let lines = memory_lines(&memory, 0xFF0, 64, 0xFFF, None);
+        assert_eq!(
+            lines,
+            ["0x0FF0  F0 F1 F2 F3 F4 F5 F6 F7 F8 F9 FA FB FC FD FE>FF  |................|"]
+        );
+
+        // a short last row keeps the ASCII column where it was
+        let full = lines[0].find('|');
+        let lines = memory_lines(&memory, 0xFF8, 16, 0, None);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].find('|'), full);
+        assert!(lines[0].ends_with("|........|"));
+
+        assert!(memory_lines(&memory, 0x1000, 16, 0, None).is_empty());
+        assert!(memory_lines(&memory, usize::MAX, 16, 0, None).is_empty());
+    }
+}
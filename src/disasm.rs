@@ -0,0 +1,34 @@
+// Disassembler emitting the assembler's own syntax, so the text of a listing
+// assembles back to the same bytes. Words that aren't instructions come out as
+// DW and a trailing odd byte as DB, which keeps sprite data visible.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::instruction::Instruction;
+
+// `LD I, 0x22A`
+pub fn disassemble(word: u16) -> String {
+    Instruction::decode(word).to_string()
+}
+
+// `0x0200  A22A  LD I, 0x22A`
+pub fn disassemble_at(addr: u16, word: u16) -> String {
+    format!("0x{:04X}  {:04X}  {}", addr, word, disassemble(word))
+}
+
+// One line per word of `rom` as loaded at `start`
+pub fn disassemble_rom(rom: &[u8], start: u16) -> Vec<String> {
+    let mut lines = Vec::with_capacity(rom.len().div_ceil(2));
+    let mut chunks = rom.chunks_exact(2);
+    let mut addr = start;
+    for word in chunks.by_ref() {
+        lines.push(disassemble_at(addr, u16::from_be_bytes([word[0], word[1]])));
+        addr = addr.wrapping_add(2);
+    }
+    if let [byte] = chunks.remainder() {
+        lines.push(format!("0x{:04X}  {:02X}    DB 0x{:02X}", addr, byte, byte));
+    }
+    lines
+}
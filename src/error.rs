@@ -0,0 +1,157 @@
+use alloc::string::String;
+#[cfg(feature = "zip")]
+use alloc::vec::Vec;
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    RomTooLarge {
+        size: usize,
+        max: usize,
+    },
+    // an empty or 1-byte ROM, without a single instruction in it
+    RomTooSmall {
+        size: usize,
+    },
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    InvalidOpcode {
+        opcode: u16,
+        pc: u16,
+    },
+    StackUnderflow {
+        pc: u16,
+    },
+    // CALL with all STACK_SIZE levels in use
+    StackOverflow {
+        pc: u16,
+    },
+    PcOutOfBounds {
+        pc: u16,
+    },
+    // BNNN at `pc` computed `target`, past the end of memory, with
+    // VmConfig::strict_jumps
+    JumpOutOfBounds {
+        target: usize,
+        pc: u16,
+    },
+    MemoryOutOfBounds {
+        addr: usize,
+    },
+    InvalidConfig(String),
+    // a 1NNN jumped to itself with VmConfig::halt_on_spin set: the program
+    // finished rather than failed
+    Halted {
+        pc: u16,
+    },
+    // FX33 or FX55 at `pc` wrote below ROM_START, with
+    // VmConfig::protect_low_memory(LowMemory::Trap)
+    ProtectedWrite {
+        addr: usize,
+        pc: u16,
+    },
+    // a .hex ROM with something other than hex bytes at `line`:`column`,
+    // both counted from 1
+    #[cfg(feature = "std")]
+    HexRom {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    // a ROM archive that's unreadable or has no usable entry
+    #[cfg(feature = "zip")]
+    Archive(String),
+    // a ROM archive with more than one ROM in it and none picked
+    #[cfg(feature = "zip")]
+    AmbiguousArchive {
+        entries: Vec<String>,
+    },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => write!(
+                f,
+                "ROM too large ({} bytes). Max allowed is {} bytes.",
+                size, max
+            ),
+            Chip8Error::RomTooSmall { size } => write!(
+                f,
+                "ROM too small ({} bytes). It needs at least one 2-byte instruction.",
+                size
+            ),
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => write!(f, "{}", e),
+            Chip8Error::InvalidOpcode { opcode, pc } => {
+                write!(f, "invalid opcode {:04X} at {:04X}", opcode, pc)
+            }
+            Chip8Error::StackUnderflow { pc } => {
+                write!(f, "RET with an empty stack at {:04X}", pc)
+            }
+            Chip8Error::StackOverflow { pc } => {
+                write!(f, "CALL with a full stack at {:04X}", pc)
+            }
+            Chip8Error::PcOutOfBounds { pc } => {
+                write!(f, "program counter out of bounds at {:04X}", pc)
+            }
+            Chip8Error::JumpOutOfBounds { target, pc } => {
+                write!(
+                    f,
+                    "jump to {:04X} past the end of memory at {:04X}",
+                    target, pc
+                )
+            }
+            Chip8Error::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:04X}", addr)
+            }
+            Chip8Error::InvalidConfig(reason) => write!(f, "invalid configuration: {}", reason),
+            Chip8Error::Halted { pc } => {
+                write!(f, "program finished, jumping to itself at {:04X}", pc)
+            }
+            Chip8Error::ProtectedWrite { addr, pc } => write!(
+                f,
+                "write to protected memory at {:04X} by the instruction at {:04X}",
+                addr, pc
+            ),
+            #[cfg(feature = "std")]
+            Chip8Error::HexRom {
+                line,
+                column,
+                reason,
+            } => write!(
+                f,
+                "bad hex ROM at line {}, column {}: {}",
+                line, column, reason
+            ),
+            #[cfg(feature = "zip")]
+            Chip8Error::Archive(reason) => write!(f, "bad ROM archive: {}", reason),
+            #[cfg(feature = "zip")]
+            Chip8Error::AmbiguousArchive { entries } => write!(
+                f,
+                "the archive holds {} ROMs: {}",
+                entries.len(),
+                entries.join(", ")
+            ),
+        }
+    }
+}
+
+impl error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
@@ -0,0 +1,231 @@
+use chip8_emu_rs::{Chip8Error, FrameInfo, StepInfo, VM, VmObserver};
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::sha1::{hex, sha1};
+
+// --event-log: what happened as the ROM ran, one JSON object per line,
+//
+//     {"frame": 12, "cycle": 3456, "event": "draw", "pc": 528, ...}
+//
+// where `frame` is `VM::frames` and `cycle` the instruction the event came
+// from, or for key presses and releases the instructions run before them.
+// Events by category:
+//
+//     rom    rom: the ROM was loaded, with its size and SHA-1
+//     draw   draw: a DXYN, with its coordinates, sprite and whether it
+//            turned any pixel off
+//     key    key: a key went down or up, as the VM saw it
+//     wait   wait_start, wait_end: FX0A started waiting and got its key
+//     sound  sound: the sound timer started or stopped running
+//     fault  fault: the VM stopped with an error
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Rom,
+    Draw,
+    Key,
+    Wait,
+    Sound,
+    Fault,
+}
+
+// --event-filter names, in the order the help lists them
+const CATEGORIES: [(&str, Category); 6] = [
+    ("rom", Category::Rom),
+    ("draw", Category::Draw),
+    ("key", Category::Key),
+    ("wait", Category::Wait),
+    ("sound", Category::Sound),
+    ("fault", Category::Fault),
+];
+
+impl Category {
+    pub fn from_name(name: &str) -> Option<Category> {
+        CATEGORIES
+            .iter()
+            .find(|(category_name, _)| category_name.eq_ignore_ascii_case(name))
+            .map(|&(_, category)| category)
+    }
+}
+
+pub fn category_names() -> String {
+    let names: Vec<&str> = CATEGORIES.iter().map(|&(name, _)| name).collect();
+    names.join(", ")
+}
+
+pub struct EventLog {
+    out: BufWriter<File>,
+    path: PathBuf,
+    // empty for every category
+    categories: Vec<Category>,
+    frame: u64,
+    // `VM::cycles` as of the last instruction that finished
+    cycle: u64,
+    // FX0A's register while it waits
+    waiting: Option<u8>,
+    // the first write that failed; logging stops there
+    error: Option<io::Error>,
+}
+
+impl EventLog {
+    // Starts the log with the ROM `vm` has loaded
+    pub fn create(path: &Path, categories: Vec<Category>, vm: &VM) -> io::Result<EventLog> {
+        let mut log = EventLog {
+            out: BufWriter::new(File::create(path)?),
+            path: path.to_path_buf(),
+            categories,
+            frame: vm.frames(),
+            cycle: vm.cycles(),
+            waiting: None,
+            error: None,
+        };
+        log.rom_loaded(vm.rom());
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.flush()
+    }
+
+    fn rom_loaded(&mut self, rom: &[u8]) {
+        let fields = format!("\"size\": {}, \"sha1\": \"{}\"", rom.len(), hex(&sha1(rom)));
+        self.write(Category::Rom, "rom", self.cycle, &fields);
+    }
+
+    // One line, unless its category is filtered out or a write failed.
+    // `fields` goes in as is, after the common ones.
+    fn write(&mut self, category: Category, event: &str, cycle: u64, fields: &str) {
+        if self.error.is_some()
+            || !(self.categories.is_empty() || self.categories.contains(&category))
+        {
+            return;
+        }
+        let mut line = format!(
+            "{{\"frame\": {}, \"cycle\": {}, \"event\": \"{}\"",
+            self.frame, cycle, event
+        );
+        if !fields.is_empty() {
+            line.push_str(", ");
+            line.push_str(fields);
+        }
+        line.push('}');
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    // for events raised while an instruction runs, before `on_step` counts it
+    fn running(&self) -> u64 {
+        self.cycle + 1
+    }
+}
+
+// A JSON string with quotes, for text that may hold anything
+fn quoted(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// The observer half of an event log, sharing it with main so it can be
+// flushed after the VM stops
+pub struct EventLogger(pub Rc<RefCell<EventLog>>);
+
+impl VmObserver for EventLogger {
+    fn on_draw(&mut self, frame: &FrameInfo) {
+        let mut log = self.0.borrow_mut();
+        let fields = format!(
+            "\"pc\": {}, \"x\": {}, \"y\": {}, \"height\": {}, \"sprite\": {}, \"collision\": {}",
+            frame.pc,
+            frame.x,
+            frame.y,
+            frame.height,
+            frame.sprite,
+            frame.collisions > 0
+        );
+        let cycle = log.running();
+        log.write(Category::Draw, "draw", cycle, &fields);
+    }
+
+    fn on_sound_changed(&mut self, on: bool) {
+        let mut log = self.0.borrow_mut();
+        // FX18 starts it, the timer running out at a frame boundary stops it
+        let cycle = if on { log.running() } else { log.cycle };
+        log.write(Category::Sound, "sound", cycle, &format!("\"on\": {}", on));
+    }
+
+    fn on_key_wait(&mut self, x: u8) {
+        let mut log = self.0.borrow_mut();
+        if log.waiting.is_none() {
+            log.waiting = Some(x);
+            let cycle = log.running();
+            log.write(
+                Category::Wait,
+                "wait_start",
+                cycle,
+                &format!("\"x\": {}", x),
+            );
+        }
+    }
+
+    fn on_key_taken(&mut self, x: u8, key: u8) {
+        let mut log = self.0.borrow_mut();
+        if log.waiting.take().is_some() {
+            let fields = format!("\"x\": {}, \"key\": {}", x, key);
+            let cycle = log.running();
+            log.write(Category::Wait, "wait_end", cycle, &fields);
+        }
+    }
+
+    fn on_key_changed(&mut self, key: u8, pressed: bool) {
+        let mut log = self.0.borrow_mut();
+        let fields = format!("\"key\": {}, \"down\": {}", key, pressed);
+        let cycle = log.cycle;
+        log.write(Category::Key, "key", cycle, &fields);
+    }
+
+    fn on_step(&mut self, step: &StepInfo) {
+        self.0.borrow_mut().cycle = step.cycle;
+    }
+
+    fn on_fault(&mut self, fault: &Chip8Error) {
+        let mut log = self.0.borrow_mut();
+        let fields = format!("\"message\": {}", quoted(&fault.to_string()));
+        let cycle = log.running();
+        log.write(Category::Fault, "fault", cycle, &fields);
+    }
+
+    fn on_frame(&mut self, frames: u64) {
+        self.0.borrow_mut().frame = frames;
+    }
+
+    fn on_rom_loaded(&mut self, rom: &[u8]) {
+        let mut log = self.0.borrow_mut();
+        log.frame = 0;
+        log.cycle = 0;
+        log.waiting = None;
+        log.rom_loaded(rom);
+    }
+}
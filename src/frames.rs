@@ -0,0 +1,99 @@
+use chip8_emu_rs::{AudioSink, Control, DisplaySink, Frontend, InputSource, VM};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Golden files for --record-frames and --check-frames: the screen's
+// `VM::framebuffer_hash` at every frame shown, one per line in hex. Together
+// with --deterministic and a --play-input log or a --script, the same build
+// shows the same frames every time, so a check that fails pins down the
+// first frame a change made different. Lines starting with '#' are comments.
+const HEADER: &str = "# chip8-emu-rs frame hashes: one per frame, FNV-1a of the screen\n";
+
+#[derive(Clone)]
+pub enum FrameCheck {
+    Record(PathBuf),
+    // the golden file and the hashes read from it
+    Verify(PathBuf, Vec<u64>),
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<u64>> {
+    let mut hashes = Vec::new();
+    for (idx, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let hash = u64::from_str_radix(line, 16).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: expected a hash in hex, got '{}'", idx + 1, line),
+            )
+        })?;
+        hashes.push(hash);
+    }
+    Ok(hashes)
+}
+
+pub fn save(path: &Path, hashes: &[u64]) -> io::Result<()> {
+    let mut text = HEADER.to_string();
+    for hash in hashes {
+        text.push_str(&format!("{:016x}\n", hash));
+    }
+    fs::write(path, text)
+}
+
+// Where a run's frames part from the golden file's, for an error message
+pub fn mismatch(golden: &[u64], shown: &[u64]) -> Option<String> {
+    if let Some(frame) = golden.iter().zip(shown).position(|(a, b)| a != b) {
+        return Some(format!(
+            "frame {} differs: screen hash {:016x}, expected {:016x}",
+            frame + 1,
+            shown[frame],
+            golden[frame]
+        ));
+    }
+    (golden.len() != shown.len()).then(|| {
+        format!(
+            "the run showed {} frames, expected {}",
+            shown.len(),
+            golden.len()
+        )
+    })
+}
+
+// Wraps a frontend and notes the screen's hash at every present
+pub struct HashedFrontend<'a> {
+    inner: &'a mut dyn Frontend,
+    hashes: &'a RefCell<Vec<u64>>,
+}
+
+impl<'a> HashedFrontend<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, hashes: &'a RefCell<Vec<u64>>) -> Self {
+        HashedFrontend { inner, hashes }
+    }
+}
+
+impl DisplaySink for HashedFrontend<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.hashes.borrow_mut().push(vm.framebuffer_hash());
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for HashedFrontend<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for HashedFrontend<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        self.inner.poll(vm)
+    }
+}
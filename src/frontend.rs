@@ -0,0 +1,251 @@
+#[cfg(feature = "std")]
+use crate::error::Chip8Error;
+use crate::vm::{FrameOutcome, VM};
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CPU_HZ: f64 = 600.0;
+pub const TIMER_HZ: f64 = 60.0;
+
+pub enum Control {
+    Continue,
+    Quit,
+    // run one frame and stay paused; only the debugger pauses, everything
+    // else carries on as with Continue
+    StepFrame,
+    // the rewind key is held: the frontend put the VM back to an earlier
+    // frame, which is presented without running, and silently
+    Rewind,
+    // the save and load state hotkeys; the binary's save state wrapper takes
+    // them, everything else carries on as with Continue
+    SaveState,
+    LoadState,
+    // the cheat hotkey, taken by the binary's cheat wrapper likewise
+    NextCheat,
+}
+
+// Receives the screen at 60Hz. `vm.draw_flag` tells whether the framebuffer
+// changed since the previous call.
+pub trait DisplaySink {
+    fn present(&mut self, vm: &VM);
+
+    // A short notice for the player, such as which cheat a hotkey turned
+    // on, shown for a moment over or under the screen. Ignored by default.
+    fn show_message(&mut self, _text: &str) {}
+}
+
+// Sets the VM's 16 keys (`VM::set_key`) to their current state and reports a
+// quit request.
+pub trait InputSource {
+    fn poll(&mut self, vm: &mut VM) -> Control;
+}
+
+pub trait AudioSink {
+    fn set_beep(&mut self, on: bool);
+}
+
+// A complete frontend; implemented for anything providing all three parts, so
+// `run` can take a single object chosen at runtime
+pub trait Frontend: DisplaySink + InputSource + AudioSink {}
+
+impl<T: DisplaySink + InputSource + AudioSink> Frontend for T {}
+
+// Frontend that shows and plays nothing and never quits, for headless runs
+pub struct NullFrontend;
+
+impl DisplaySink for NullFrontend {
+    fn present(&mut self, _vm: &VM) {}
+}
+
+impl InputSource for NullFrontend {
+    fn poll(&mut self, _vm: &mut VM) -> Control {
+        Control::Continue
+    }
+}
+
+impl AudioSink for NullFrontend {
+    fn set_beep(&mut self, _on: bool) {}
+}
+
+// Instructions per 60Hz frame at the VM's clock rate
+pub fn instructions_per_frame(vm: &VM) -> u32 {
+    // f64::round needs std; the rate is positive, so adding a half will do
+    ((vm.cpu_hz() / TIMER_HZ + 0.5) as u32).max(1)
+}
+
+// Adaptive per-frame instruction budget. It follows the number of
+// instructions between draws, so that each presented frame carries about one
+// screen update: ROMs that draw every few frames are sped up, ROMs that spin
+// without drawing run at `max_ipf`, and ROMs that draw every frame drift back
+// to the configured clock rate. Only the VM's own cycle count is measured, so
+// recorded runs still replay exactly.
+#[derive(Clone, Debug)]
+pub struct AutoThrottle {
+    min_ipf: u32,
+    max_ipf: u32,
+    // cycle count at the end of the last frame that drew
+    last_draw: u64,
+}
+
+impl AutoThrottle {
+    pub fn new(min_ipf: u32, max_ipf: u32) -> Self {
+        AutoThrottle {
+            min_ipf: min_ipf.max(1),
+            max_ipf: max_ipf.max(min_ipf).max(1),
+            last_draw: 0,
+        }
+    }
+
+    // Budget for the next frame, given the one just run with `ipf`
+    pub fn next_ipf(&mut self, vm: &VM, frame: &FrameOutcome, ipf: u32) -> u32 {
+        let cycles = vm.cycles();
+        if cycles < self.last_draw {
+            // the VM was reset
+            self.last_draw = cycles;
+        }
+        let since_draw = cycles - self.last_draw;
+        let target = if frame.waiting_for_key || frame.halted {
+            // short frames that say nothing about the ROM's pace
+            ipf as u64
+        } else if frame.drew {
+            self.last_draw = cycles;
+            if since_draw <= frame.instructions as u64 {
+                // drew in the previous frame too
+                instructions_per_frame(vm) as u64
+            } else {
+                since_draw
+            }
+        } else {
+            since_draw
+        };
+        // smoothed, so one odd frame doesn't jolt the speed
+        let next = (3 * ipf as u64 + target) / 4;
+        next.clamp(self.min_ipf as u64, self.max_ipf as u64) as u32
+    }
+}
+
+// Drives the VM in real time, one `run_frame` per 60Hz tick with input polled
+// before and the screen presented after it. Frames only depend on the input,
+// never on the wall clock, so recorded runs replay exactly. Returns when the
+// frontend asks to quit, or with the error that stopped the VM.
+#[cfg(feature = "std")]
+pub fn run(vm: &mut VM, frontend: &mut dyn Frontend) -> Result<(), Chip8Error> {
+    run_paced(vm, frontend, None, false)
+}
+
+// `run` with the instructions per frame chosen by `throttle` instead of fixed
+// by the clock rate
+#[cfg(feature = "std")]
+pub fn run_auto_throttle(
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    throttle: AutoThrottle,
+) -> Result<(), Chip8Error> {
+    run_paced(vm, frontend, Some(throttle), false)
+}
+
+// `run`, or `run_auto_throttle` given a throttle, except that frames missed
+// in a stall aren't counted down on the timers: they tick exactly once per
+// frame, so the run depends on nothing but its input
+#[cfg(feature = "std")]
+pub fn run_lockstep(
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    throttle: Option<AutoThrottle>,
+) -> Result<(), Chip8Error> {
+    run_paced(vm, frontend, throttle, true)
+}
+
+#[cfg(feature = "std")]
+fn run_paced(
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    mut throttle: Option<AutoThrottle>,
+    lockstep: bool,
+) -> Result<(), Chip8Error> {
+    let mut ipf = instructions_per_frame(vm);
+    if let Some(throttle) = &mut throttle {
+        throttle.last_draw = vm.cycles();
+        ipf = ipf.clamp(throttle.min_ipf, throttle.max_ipf);
+    }
+    let frame_dt = Duration::from_secs_f64(1.0 / TIMER_HZ);
+    let mut next_frame = Instant::now();
+
+    loop {
+        let rewinding = match frontend.poll(vm) {
+            Control::Quit => return Ok(()),
+            Control::Rewind => true,
+            _ => false,
+        };
+        if !rewinding {
+            let outcome = vm.run_frame(ipf);
+            if let Some(e) = outcome.fault {
+                return Err(e);
+            }
+            if let Some(throttle) = &mut throttle {
+                ipf = throttle.next_ipf(vm, &outcome, ipf);
+            }
+        }
+        frontend.present(vm);
+        vm.draw_flag = false;
+        frontend.set_beep(!rewinding && vm.sound_timer > 0);
+
+        next_frame += frame_dt;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else if now - next_frame > MAX_FRAME_LAG * frame_dt {
+            // after a stall (dragged window, suspended process) drop the
+            // missed frames instead of racing through them, but still count
+            // them down on the timers
+            if !lockstep {
+                let missed = ((now - next_frame).as_secs_f64() * TIMER_HZ) as u64;
+                vm.step_timers(missed.min(u8::MAX as u64) as u8);
+            }
+            next_frame = now;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+const MAX_FRAME_LAG: u32 = 5;
+
+// Single-cycle mode for watching a ROM execute: one instruction every
+// `interval`, with the screen presented after each of them. Timers tick once
+// per frame's worth of instructions, so ROMs see the same timing as at full
+// speed, just stretched out.
+#[cfg(feature = "std")]
+pub fn run_slowmo(
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    interval: Duration,
+) -> Result<(), Chip8Error> {
+    let ipf = instructions_per_frame(vm) as u64;
+    // input is still polled at ~60Hz while waiting for the next instruction
+    let poll_dt = Duration::from_secs_f64(1.0 / TIMER_HZ);
+
+    loop {
+        vm.step()?;
+        if vm.cycles().is_multiple_of(ipf) {
+            vm.step_timers(1);
+        }
+
+        frontend.present(vm);
+        vm.draw_flag = false;
+        frontend.set_beep(vm.sound_timer > 0);
+
+        let next = Instant::now() + interval;
+        loop {
+            if let Control::Quit = frontend.poll(vm) {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= next {
+                break;
+            }
+            thread::sleep((next - now).min(poll_dt));
+        }
+    }
+}
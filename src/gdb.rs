@@ -0,0 +1,730 @@
+use chip8_emu_rs::{
+    Breakpoints, Chip8Error, Control, Frontend, TIMER_HZ, VM, instructions_per_frame,
+};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// --gdb: a stub for GDB's remote serial protocol, so gdb or an IDE can attach
+// with `target remote :3333`. It covers what a debugger needs to look at and
+// drive a program: the registers and memory, read and written, continue,
+// single step, software breakpoints (Z0) and why the program stopped. The
+// registers, in gdb's numbering:
+//
+//     0-15  v0-vf   8 bits
+//     16    i       16 bits
+//     17    pc      16 bits
+//     18    sp      8 bits, the call depth; read only
+//     19    dt      8 bits
+//     20    st      8 bits
+//
+// described to gdb by a target XML. Multi-byte registers are little-endian
+// on the wire, as for most targets. gdb attaching stops the program, and it
+// runs on once gdb detaches or the connection drops.
+
+// gdb's stop signals
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+const REGISTERS: usize = 21;
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+
+// what gdb is told it may send at once, in hex as the protocol has it
+const PACKET_SIZE: usize = 0x4000;
+
+// The register file as gdb reads it
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n<feature name=\"org.chip8.core\">\n",
+    );
+    for x in 0..16 {
+        xml.push_str(&format!(
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>\n",
+            x
+        ));
+    }
+    xml.push_str(
+        "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>\n\
+         <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\n\
+         <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>\n\
+         <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>\n\
+         <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>\n\
+         </feature>\n</target>\n",
+    );
+    xml
+}
+
+fn register_size(reg: usize) -> usize {
+    match reg {
+        REG_I | REG_PC => 2,
+        _ => 1,
+    }
+}
+
+fn read_register(vm: &VM, reg: usize) -> u16 {
+    match reg {
+        0..=15 => vm.v[reg] as u16,
+        REG_I => vm.i,
+        REG_PC => vm.pc,
+        REG_SP => vm.stack().len() as u16,
+        REG_DT => vm.delay_timer as u16,
+        _ => vm.sound_timer as u16,
+    }
+}
+
+// false for the read-only SP
+fn write_register(vm: &mut VM, reg: usize, value: u16) -> bool {
+    match reg {
+        0..=15 => vm.v[reg] = value as u8,
+        REG_I => vm.i = value,
+        REG_PC => vm.pc = value,
+        REG_DT => vm.delay_timer = value as u8,
+        // through the setter, so the beep and the observer hear of it
+        REG_ST => vm.set_sound_timer(value as u8),
+        _ => return false,
+    }
+    true
+}
+
+// The signal gdb is told a fault stopped the program with
+fn fault_signal(fault: &Chip8Error) -> u8 {
+    match fault {
+        Chip8Error::InvalidOpcode { .. } => SIGILL,
+        _ => SIGSEGV,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(text.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+// `addr,len`
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((parse_number(addr)?, parse_number(len)?))
+}
+
+// What came in from gdb
+enum Incoming {
+    Packet(String),
+    // Ctrl+C in gdb, sent as a bare 0x03
+    Interrupt,
+}
+
+struct Connection {
+    stream: TcpStream,
+    // bytes read that don't make a whole packet yet
+    buffer: Vec<u8>,
+    // after QStartNoAckMode, neither side sends '+'
+    no_ack: bool,
+}
+
+impl Connection {
+    // Reads what gdb has sent, waiting up to `wait` for it. False once gdb
+    // closed the connection.
+    fn fill(&mut self, wait: Duration) -> io::Result<bool> {
+        if wait.is_zero() {
+            self.stream.set_nonblocking(true)?;
+        } else {
+            self.stream.set_nonblocking(false)?;
+            self.stream.set_read_timeout(Some(wait))?;
+        }
+        let mut chunk = [0; 4096];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => Ok(false),
+            Ok(len) => {
+                self.buffer.extend_from_slice(&chunk[..len]);
+                Ok(true)
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The next whole packet or interrupt in the buffer, acknowledging it
+    fn next(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let Some(&first) = self.buffer.first() else {
+                return Ok(None);
+            };
+            match first {
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Incoming::Interrupt));
+                }
+                b'$' => {}
+                // acks, and anything else between packets
+                _ => {
+                    self.buffer.remove(0);
+                    continue;
+                }
+            }
+            let Some(end) = self.buffer.iter().position(|&byte| byte == b'#') else {
+                return Ok(None);
+            };
+            if self.buffer.len() < end + 3 {
+                return Ok(None);
+            }
+            let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+            let data = &packet[1..end];
+            let checksum = std::str::from_utf8(&packet[end + 1..])
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+            let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+            if !self.no_ack {
+                let ack: &[u8] = if checksum == Some(sum) { b"+" } else { b"-" };
+                self.write(ack)?;
+            }
+            if checksum == Some(sum) {
+                return Ok(Some(Incoming::Packet(
+                    String::from_utf8_lossy(data).into_owned(),
+                )));
+            }
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.write_all(bytes)
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        for byte in data.bytes() {
+            if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+                packet.extend_from_slice(&[b'}', byte ^ 0x20]);
+            } else {
+                packet.push(byte);
+            }
+        }
+        let sum = packet[1..]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        self.write(&packet)
+    }
+}
+
+// What a packet asks of the run loop
+enum Action {
+    Reply(String),
+    // c and s: the stop reply comes once the program stops
+    Resume,
+    Step,
+    Detach,
+    Kill,
+}
+
+pub struct GdbStub {
+    listener: TcpListener,
+    connection: Option<Connection>,
+    breakpoints: Breakpoints,
+    // stopped for gdb; only while it's attached
+    halted: bool,
+    // the signal of the last stop, for '?'
+    signal: u8,
+    ipf: u32,
+    target_xml: String,
+}
+
+impl GdbStub {
+    pub fn bind(addr: SocketAddr, vm: &VM) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbStub {
+            listener,
+            connection: None,
+            breakpoints: Breakpoints::new(),
+            halted: false,
+            signal: SIGTRAP,
+            ipf: instructions_per_frame(vm),
+            target_xml: target_xml(),
+        })
+    }
+
+    // For --gdb-wait: blocks until gdb attaches, which stops the program
+    pub fn wait_for_gdb(&mut self) -> io::Result<()> {
+        println!(
+            "Waiting for gdb on {}, attach with 'target remote {}'.",
+            self.listener.local_addr()?,
+            self.listener.local_addr()?
+        );
+        self.listener.set_nonblocking(false)?;
+        let (stream, peer) = self.listener.accept()?;
+        self.listener.set_nonblocking(true)?;
+        self.attach(stream, peer)
+    }
+
+    fn attach(&mut self, stream: TcpStream, peer: SocketAddr) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        println!("gdb attached from {}.", peer);
+        self.connection = Some(Connection {
+            stream,
+            buffer: Vec::new(),
+            no_ack: false,
+        });
+        self.halted = true;
+        self.signal = SIGTRAP;
+        Ok(())
+    }
+
+    fn detach(&mut self) {
+        println!("gdb detached.");
+        self.connection = None;
+        self.breakpoints = Breakpoints::new();
+        self.halted = false;
+    }
+
+    pub fn run(&mut self, vm: &mut VM, frontend: &mut dyn Frontend) -> Result<(), Chip8Error> {
+        let frame_dt = Duration::from_secs_f64(1.0 / TIMER_HZ);
+        loop {
+            let frame_start = Instant::now();
+            if matches!(frontend.poll(vm), Control::Quit) {
+                return Ok(());
+            }
+            if self.connection.is_none() {
+                match self.listener.accept() {
+                    Ok((stream, peer)) => {
+                        if let Err(e) = self.attach(stream, peer) {
+                            eprintln!("Warning: gdb connection failed: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => eprintln!("Warning: gdb connection failed: {}", e),
+                }
+            }
+            // while stopped, packets are answered as they come until the
+            // next frame is due
+            let wait = if self.halted {
+                frame_dt.saturating_sub(frame_start.elapsed())
+            } else {
+                Duration::ZERO
+            };
+            match self.serve(vm, wait) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => {
+                    eprintln!("Warning: gdb connection lost: {}", e);
+                    self.detach();
+                }
+            }
+            if !self.halted {
+                self.run_frame(vm)?;
+            }
+
+            frontend.present(vm);
+            vm.draw_flag = false;
+            frontend.set_beep(!self.halted && vm.sound_timer > 0);
+            if let Some(rest) = frame_dt.checked_sub(frame_start.elapsed()) {
+                thread::sleep(rest);
+            }
+        }
+    }
+
+    // Answers what gdb sent, waiting up to `wait` for more; false once gdb
+    // asked to kill the program
+    fn serve(&mut self, vm: &mut VM, wait: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + wait;
+        let Some(connection) = &mut self.connection else {
+            return Ok(true);
+        };
+        if !connection.fill(Duration::ZERO)? {
+            self.detach();
+            return Ok(true);
+        }
+        loop {
+            let Some(connection) = &mut self.connection else {
+                return Ok(true);
+            };
+            let incoming = match connection.next()? {
+                Some(incoming) => incoming,
+                None => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() || !self.halted {
+                        return Ok(true);
+                    }
+                    if !connection.fill(left)? {
+                        self.detach();
+                        return Ok(true);
+                    }
+                    continue;
+                }
+            };
+            let packet = match incoming {
+                Incoming::Interrupt => {
+                    if !self.halted {
+                        self.stop(SIGINT)?;
+                    }
+                    continue;
+                }
+                Incoming::Packet(packet) => packet,
+            };
+            match self.handle(vm, &packet) {
+                Action::Reply(reply) => {
+                    self.reply(&reply)?;
+                    // from the packet after this one
+                    if packet == "QStartNoAckMode"
+                        && let Some(connection) = &mut self.connection
+                    {
+                        connection.no_ack = true;
+                    }
+                }
+                Action::Resume => {
+                    self.halted = false;
+                    // leave the breakpoint we're stopped at instead of
+                    // hitting it again
+                    if self.breakpoints.hit(vm)
+                        && let Some(signal) = self.step(vm)
+                    {
+                        self.stop(signal)?;
+                    }
+                }
+                Action::Step => {
+                    let signal = self.step(vm).unwrap_or(SIGTRAP);
+                    self.stop(signal)?;
+                }
+                Action::Detach => {
+                    self.reply("OK")?;
+                    self.detach();
+                    return Ok(true);
+                }
+                Action::Kill => return Ok(false),
+            }
+        }
+    }
+
+    fn reply(&mut self, data: &str) -> io::Result<()> {
+        match &mut self.connection {
+            Some(connection) => connection.send(data),
+            None => Ok(()),
+        }
+    }
+
+    // Stops the program and tells gdb why
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.halted = true;
+        self.signal = signal;
+        self.reply(&format!("S{:02x}", signal))
+    }
+
+    // One instruction, ticking the timers every frame's worth as `c` would;
+    // the signal to stop with if it faulted
+    fn step(&mut self, vm: &mut VM) -> Option<u8> {
+        if let Err(e) = vm.step() {
+            return Some(fault_signal(&e));
+        }
+        if vm.cycles().is_multiple_of(self.ipf as u64) {
+            vm.step_timers(1);
+        }
+        vm.take_watch_hit().map(|_| SIGTRAP)
+    }
+
+    fn run_frame(&mut self, vm: &mut VM) -> Result<(), Chip8Error> {
+        let (outcome, hit) = self.breakpoints.run_frame(vm, self.ipf);
+        let stopped = match outcome.fault {
+            // --halt-on-spin: the program finished, which gdb hears as an exit
+            Some(e @ Chip8Error::Halted { .. }) => {
+                let _ = self.reply("W00");
+                return Err(e);
+            }
+            Some(e) if self.connection.is_some() => Some(fault_signal(&e)),
+            Some(e) => return Err(e),
+            None if hit.is_some() || outcome.watch_hit.is_some() => Some(SIGTRAP),
+            None => None,
+        };
+        if let Some(signal) = stopped
+            && self.connection.is_some()
+            && let Err(e) = self.stop(signal)
+        {
+            eprintln!("Warning: gdb connection lost: {}", e);
+            self.detach();
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, vm: &mut VM, packet: &str) -> Action {
+        let reply = |text: &str| Action::Reply(text.to_string());
+        let error = || reply("E01");
+        // the first character, whatever its width; an empty packet has none
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        match command {
+            "?" => Action::Reply(format!("S{:02x}", self.signal)),
+            "g" => {
+                let mut bytes = Vec::new();
+                for reg in 0..REGISTERS {
+                    let value = read_register(vm, reg).to_le_bytes();
+                    bytes.extend_from_slice(&value[..register_size(reg)]);
+                }
+                Action::Reply(hex(&bytes))
+            }
+            "G" => {
+                let Some(bytes) = parse_hex(args) else {
+                    return error();
+                };
+                let mut at = 0;
+                for reg in 0..REGISTERS {
+                    let size = register_size(reg);
+                    let Some(value) = bytes.get(at..at + size) else {
+                        return error();
+                    };
+                    let value = u16::from_le_bytes([value[0], *value.get(1).unwrap_or(&0)]);
+                    // SP can't be set, so gdb writing back what it read is fine
+                    if reg != REG_SP {
+                        write_register(vm, reg, value);
+                    }
+                    at += size;
+                }
+                reply("OK")
+            }
+            "p" => match parse_number(args).filter(|&reg| reg < REGISTERS) {
+                Some(reg) => {
+                    let value = read_register(vm, reg).to_le_bytes();
+                    Action::Reply(hex(&value[..register_size(reg)]))
+                }
+                None => error(),
+            },
+            "P" => {
+                let Some((reg, value)) = args.split_once('=') else {
+                    return error();
+                };
+                let reg = parse_number(reg).filter(|&reg| reg < REGISTERS);
+                match (reg, parse_hex(value)) {
+                    (Some(reg), Some(bytes)) if bytes.len() == register_size(reg) => {
+                        let value = u16::from_le_bytes([bytes[0], *bytes.get(1).unwrap_or(&0)]);
+                        if write_register(vm, reg, value) {
+                            reply("OK")
+                        } else {
+                            error()
+                        }
+                    }
+                    _ => error(),
+                }
+            }
+            "m" => match parse_range(args) {
+                Some((addr, len)) if addr < vm.memory.len() => {
+                    let end = addr.saturating_add(len).min(vm.memory.len());
+                    Action::Reply(hex(&vm.memory[addr..end]))
+                }
+                _ => error(),
+            },
+            "M" => {
+                let Some((range, data)) = args.split_once(':') else {
+                    return error();
+                };
+                match (parse_range(range), parse_hex(data)) {
+                    (Some((addr, len)), Some(bytes))
+                        if bytes.len() == len
+                            && addr
+                                .checked_add(len)
+                                .is_some_and(|end| end <= vm.memory.len()) =>
+                    {
+                        vm.memory[addr..addr + len].copy_from_slice(&bytes);
+                        reply("OK")
+                    }
+                    _ => error(),
+                }
+            }
+            "c" | "s" => {
+                // an address to carry on from
+                if !args.is_empty() {
+                    match parse_number(args) {
+                        Some(addr) => vm.pc = addr as u16,
+                        None => return error(),
+                    }
+                }
+                if command == "c" {
+                    Action::Resume
+                } else {
+                    Action::Step
+                }
+            }
+            // software and hardware breakpoints alike stop before the
+            // instruction runs
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                let addr = fields.next().and_then(parse_number);
+                match (kind, addr) {
+                    (Some("0" | "1"), Some(addr)) => {
+                        if command == "Z" {
+                            self.breakpoints.insert(addr as u16);
+                        } else {
+                            self.breakpoints.remove(addr as u16);
+                        }
+                        reply("OK")
+                    }
+                    // watchpoints aren't supported
+                    _ => reply(""),
+                }
+            }
+            "D" => Action::Detach,
+            "k" => Action::Kill,
+            "H" => reply("OK"),
+            "q" | "Q" => self.query(packet),
+            _ => reply(""),
+        }
+    }
+
+    fn query(&mut self, packet: &str) -> Action {
+        if packet.starts_with("qSupported") {
+            return Action::Reply(format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            ));
+        }
+        if let Some(rest) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, len)) = parse_range(rest) else {
+                return Action::Reply("E01".to_string());
+            };
+            let xml = self.target_xml.as_bytes();
+            let start = offset.min(xml.len());
+            let end = start.saturating_add(len).min(xml.len());
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+            return Action::Reply(format!(
+                "{}{}",
+                marker,
+                String::from_utf8_lossy(&xml[start..end])
+            ));
+        }
+        match packet {
+            "QStartNoAckMode" => Action::Reply("OK".to_string()),
+            "qAttached" => Action::Reply("1".to_string()),
+            "qC" => Action::Reply("QC1".to_string()),
+            "qfThreadInfo" => Action::Reply("m1".to_string()),
+            "qsThreadInfo" => Action::Reply("l".to_string()),
+            _ => Action::Reply(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_emu_rs::{ROM_START, VmConfig, VmObserver};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // what a session sees the beep do
+    struct Beeps(Rc<RefCell<Vec<bool>>>);
+
+    impl VmObserver for Beeps {
+        fn on_sound_changed(&mut self, on: bool) {
+            self.0.borrow_mut().push(on);
+        }
+    }
+
+    // a stub with a client attached as gdb would be, over a real socket
+    fn attach(vm: &VM) -> (GdbStub, TcpStream) {
+        let mut stub = GdbStub::bind("127.0.0.1:0".parse().unwrap(), vm).unwrap();
+        let client = TcpStream::connect(stub.listener.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stub.listener.set_nonblocking(false).unwrap();
+        let (stream, peer) = stub.listener.accept().unwrap();
+        stub.attach(stream, peer).unwrap();
+        (stub, client)
+    }
+
+    fn send(client: &mut TcpStream, data: &[u8]) {
+        let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        client.write_all(&packet).unwrap();
+    }
+
+    // the next packet from the stub, past any acks
+    fn receive(client: &mut TcpStream) -> String {
+        let mut packet = Vec::new();
+        let mut byte = [0];
+        loop {
+            client.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b'+' if packet.is_empty() => {}
+                b'#' => break,
+                _ => packet.push(byte[0]),
+            }
+        }
+        let mut checksum = [0; 2];
+        client.read_exact(&mut checksum).unwrap();
+        String::from_utf8(packet[1..].to_vec()).unwrap()
+    }
+
+    // one packet answered the way the run loop answers it while stopped
+    fn exchange(stub: &mut GdbStub, vm: &mut VM, client: &mut TcpStream, data: &[u8]) -> String {
+        send(client, data);
+        assert!(stub.serve(vm, Duration::from_millis(20)).unwrap());
+        receive(client)
+    }
+
+    #[test]
+    fn a_scripted_session() {
+        // LD V0, 1; LD V1, 2; JP 0x202
+        let mut vm = VmConfig::new(|| 0).build().unwrap();
+        vm.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x02]).unwrap();
+        let beeps = Rc::new(RefCell::new(Vec::new()));
+        vm.set_observer(Box::new(Beeps(beeps.clone())));
+        let (mut stub, mut client) = attach(&vm);
+        let mut session =
+            |vm: &mut VM, data: &str| exchange(&mut stub, vm, &mut client, data.as_bytes());
+
+        assert!(session(&mut vm, "qSupported:multiprocess+").starts_with("PacketSize="));
+        assert_eq!(session(&mut vm, "?"), "S05");
+
+        // nothing to split a command from, or a first character wider
+        // than a byte: unknown, not a crash
+        assert_eq!(session(&mut vm, ""), "");
+        assert_eq!(session(&mut vm, "\u{e9}1"), "");
+
+        // memory, up to the end and not past it, however far
+        assert_eq!(session(&mut vm, "M300,2:abcd"), "OK");
+        assert_eq!(session(&mut vm, "m300,2"), "abcd");
+        assert_eq!(session(&mut vm, "Mffe,2:0102"), "OK");
+        assert_eq!(session(&mut vm, "Mfff,2:0102"), "E01");
+        assert_eq!(session(&mut vm, "Mffffffffffffffff,1:01"), "E01");
+        assert_eq!(&vm.memory[0xFFE..], &[1, 2]);
+
+        // registers; ST turns the beep on and off as LD ST, Vx would
+        assert_eq!(session(&mut vm, "P14=05"), "OK");
+        assert_eq!(session(&mut vm, "P14=00"), "OK");
+        assert_eq!(*beeps.borrow(), [true, false]);
+        assert_eq!(session(&mut vm, "P12=01"), "E01");
+
+        // a step, then running into a breakpoint
+        assert_eq!(session(&mut vm, "s"), "S05");
+        assert_eq!((vm.pc, vm.v[0]), (ROM_START as u16 + 2, 1));
+        assert_eq!(session(&mut vm, "Z0,204,2"), "OK");
+        send(&mut client, b"c");
+        assert!(stub.serve(&mut vm, Duration::ZERO).unwrap());
+        stub.run_frame(&mut vm).unwrap();
+        assert_eq!(receive(&mut client), "S05");
+        assert_eq!((vm.pc, vm.v[1]), (ROM_START as u16 + 4, 2));
+
+        send(&mut client, b"k");
+        assert!(!stub.serve(&mut vm, Duration::from_millis(20)).unwrap());
+    }
+}
@@ -0,0 +1,152 @@
+use chip8_emu_rs::{Instruction, StepInfo, VM, VmObserver, disassemble_at};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A hot loop is the PC staying inside WINDOW bytes for THRESHOLD
+// instructions in a row without drawing or reading the keypad: a delay-timer
+// wait, a busy wait on something that never changes, or a hang
+const WINDOW: u16 = 8;
+const THRESHOLD: u64 = 1000;
+// PCs kept to find the loop's bounds once it's found, so the instructions
+// leading into it aren't counted as part of it
+const RECENT: usize = 8;
+
+#[derive(Default)]
+pub struct Detector {
+    // the current run: its bounds, length and last few PCs
+    start: u16,
+    end: u16,
+    run: u64,
+    recent: [u16; RECENT],
+    // set once the run passes THRESHOLD
+    found: Option<(u16, u16)>,
+    // cycles spent in each loop and how many times it was entered
+    loops: HashMap<(u16, u16), (u64, u64)>,
+    // loops found since the last `take_found`
+    new: Vec<(u16, u16)>,
+}
+
+impl Detector {
+    fn record(&mut self, info: &StepInfo) {
+        let step = &info.step;
+        let pc = step.pc;
+        let (start, end) = (self.start.min(pc), self.end.max(pc));
+        if self.run == 0 || end - start >= WINDOW {
+            self.close();
+            (self.start, self.end) = (pc, pc);
+        } else {
+            (self.start, self.end) = (start, end);
+        }
+        self.recent[self.run as usize % RECENT] = pc;
+        self.run += 1;
+        if reads_input(info) {
+            self.close();
+        } else if self.run == THRESHOLD {
+            let start = *self.recent.iter().min().unwrap();
+            let end = *self.recent.iter().max().unwrap();
+            self.found = Some((start, end));
+            self.new.push((start, end));
+        }
+    }
+
+    // ends the current run, counting it if it was a loop
+    fn close(&mut self) {
+        if let Some(range) = self.found.take() {
+            let (cycles, entered) = self.loops.entry(range).or_default();
+            // the instructions before the loop was found ran in it too
+            *cycles += self.run;
+            *entered += 1;
+        }
+        self.run = 0;
+    }
+
+    // loops found since the last call, first address and last instruction's
+    // address
+    pub fn take_found(&mut self) -> Vec<(u16, u16)> {
+        std::mem::take(&mut self.new)
+    }
+
+    // (first address, last instruction's address, cycles, times entered),
+    // most cycles first, including the loop running now
+    pub fn loops(&mut self) -> Vec<(u16, u16, u64, u64)> {
+        if let Some(range) = self.found {
+            self.close();
+            self.found = Some(range);
+        }
+        let mut loops: Vec<(u16, u16, u64, u64)> = self
+            .loops
+            .iter()
+            .map(|(&(start, end), &(cycles, entered))| (start, end, cycles, entered))
+            .collect();
+        loops.sort_by_key(|&(start, _, cycles, _)| (std::cmp::Reverse(cycles), start));
+        loops
+    }
+}
+
+// drawing, or a key read that saw a key, is the program making progress
+fn reads_input(info: &StepInfo) -> bool {
+    let step = &info.step;
+    step.drew
+        || step.waiting_for_key
+        || match step.instruction {
+            Instruction::Skp { .. } => step.skipped,
+            Instruction::Sknp { .. } => !step.skipped,
+            Instruction::LdVxK { .. } => true,
+            _ => false,
+        }
+}
+
+// The observer, shared with main and the debugger
+pub struct HotLoops(pub Rc<RefCell<Detector>>);
+
+impl HotLoops {
+    pub fn new() -> Self {
+        HotLoops(Rc::new(RefCell::new(Detector::default())))
+    }
+}
+
+impl VmObserver for HotLoops {
+    fn on_step(&mut self, info: &StepInfo) {
+        self.0.borrow_mut().record(info);
+    }
+}
+
+// the loop's instructions, one per line with `indent` in front
+pub fn print_disassembly(vm: &VM, start: u16, end: u16, indent: &str) {
+    for addr in (start..=end).step_by(2) {
+        let at = addr as usize;
+        if let (Some(&hi), Some(&lo)) = (vm.memory.get(at), vm.memory.get(at + 1)) {
+            println!(
+                "{}{}",
+                indent,
+                disassemble_at(addr, u16::from_be_bytes([hi, lo]))
+            );
+        }
+    }
+}
+
+// --report-hot-loops
+pub fn print_report(vm: &VM, detector: &mut Detector) {
+    let loops = detector.loops();
+    if loops.is_empty() {
+        println!(
+            "No hot loops: the PC never stayed within {} bytes for {} instructions.",
+            WINDOW, THRESHOLD
+        );
+        return;
+    }
+    let total = vm.cycles().max(1);
+    println!("Hot loops, by cycles spent in them:");
+    for (start, end, cycles, entered) in loops {
+        println!(
+            "{:04X}-{:04X}  {} cycles ({:.1}%), entered {} time(s)",
+            start,
+            end,
+            cycles,
+            cycles as f64 * 100.0 / total as f64,
+            entered
+        );
+        print_disassembly(vm, start, end, "    ");
+    }
+}
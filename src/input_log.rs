@@ -0,0 +1,322 @@
+use chip8_emu_rs::{AudioSink, Control, DisplaySink, Frontend, InputSource, VM};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Key changes keyed to `VM::cycles`, stored one per line as
+//
+//     <cycle> <key as a hex digit> <down|up>
+//
+// An event at cycle C is in effect from the first poll after C instructions
+// have run; frontends poll at every frame boundary, which makes the replay
+// land on the same instruction. Lines starting with '#' are comments.
+//
+// Before the events, a log written now has what the run depended on:
+//
+//     rom <SHA-1 of the ROM>
+//     quirks <preset, or custom>
+//     ipf <instructions per frame, or MIN:MAX with --auto-throttle>
+//     seed <N> | fixed-rand <BYTE>
+//
+// and after them `end <cycle>`, where the recording stopped. Each is
+// optional, so older logs and hand-written ones still load.
+#[derive(Clone, Copy)]
+struct InputEvent {
+    cycle: u64,
+    key: usize,
+    pressed: bool,
+}
+
+const HEADER: &str = "# chip8-emu-rs input log: cycle key down|up\n";
+
+// What a recording ran with, which a replay has to match
+#[derive(Clone, Default)]
+pub struct LogHeader {
+    pub rom: Option<String>,
+    pub quirks: Option<String>,
+    pub ipf: Option<String>,
+    pub seed: Option<u64>,
+    pub fixed_rand: Option<u8>,
+}
+
+impl LogHeader {
+    // the first thing `current` has different from the recording, for an
+    // error message
+    pub fn mismatch(&self, current: &LogHeader) -> Option<String> {
+        fn differs<T: PartialEq + fmt::Display>(
+            name: &str,
+            logged: &Option<T>,
+            current: &Option<T>,
+        ) -> Option<String> {
+            let logged = logged.as_ref()?;
+            if current.as_ref() == Some(logged) {
+                return None;
+            }
+            let current = current.as_ref().map_or("none".to_string(), T::to_string);
+            Some(format!("{} {}, this run has {}", name, logged, current))
+        }
+        differs("ROM", &self.rom, &current.rom)
+            .or_else(|| differs("quirks", &self.quirks, &current.quirks))
+            .or_else(|| differs("instructions per frame", &self.ipf, &current.ipf))
+            .or_else(|| differs("seed", &self.seed, &current.seed))
+            .or_else(|| differs("fixed-rand", &self.fixed_rand, &current.fixed_rand))
+    }
+
+    fn write(&self, text: &mut String) {
+        if let Some(rom) = &self.rom {
+            text.push_str(&format!("rom {}\n", rom));
+        }
+        if let Some(quirks) = &self.quirks {
+            text.push_str(&format!("quirks {}\n", quirks));
+        }
+        if let Some(ipf) = &self.ipf {
+            text.push_str(&format!("ipf {}\n", ipf));
+        }
+        if let Some(seed) = self.seed {
+            text.push_str(&format!("seed {}\n", seed));
+        }
+        if let Some(byte) = self.fixed_rand {
+            text.push_str(&format!("fixed-rand {}\n", byte));
+        }
+    }
+
+    // false if `line` isn't a header line
+    fn parse_line(&mut self, line: &str) -> bool {
+        let Some((name, value)) = line.split_once(' ') else {
+            return false;
+        };
+        let value = value.trim();
+        match name {
+            "rom" => self.rom = Some(value.to_string()),
+            "quirks" => self.quirks = Some(value.to_string()),
+            "ipf" => self.ipf = Some(value.to_string()),
+            "seed" => match value.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(_) => return false,
+            },
+            "fixed-rand" => match value.parse() {
+                Ok(byte) => self.fixed_rand = Some(byte),
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+// A log read back for --play-input
+#[derive(Clone)]
+pub struct InputLog {
+    pub header: LogHeader,
+    events: Vec<InputEvent>,
+    // the cycle the recording stopped at, or the last event's
+    end: u64,
+}
+
+impl InputLog {
+    pub fn load(path: &Path) -> io::Result<InputLog> {
+        let mut header = LogHeader::default();
+        let mut events = Vec::new();
+        let mut end = None;
+        for (idx, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || header.parse_line(line) {
+                continue;
+            }
+            if let Some(cycle) = line.strip_prefix("end ")
+                && let Ok(cycle) = cycle.trim().parse()
+            {
+                end = Some(cycle);
+                continue;
+            }
+            let event = parse_event(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: expected '<cycle> <key> down|up'", idx + 1),
+                )
+            })?;
+            events.push(event);
+        }
+        // keeps same-cycle events in file order
+        events.sort_by_key(|event| event.cycle);
+        let end = end.unwrap_or_else(|| events.last().map_or(0, |event| event.cycle));
+        Ok(InputLog {
+            header,
+            events,
+            end,
+        })
+    }
+}
+
+fn parse_event(line: &str) -> Option<InputEvent> {
+    let mut fields = line.split_whitespace();
+    let cycle = fields.next()?.parse().ok()?;
+    let key = usize::from_str_radix(fields.next()?, 16).ok()?;
+    let pressed = match fields.next()? {
+        "down" => true,
+        "up" => false,
+        _ => return None,
+    };
+    if key > 0xF || fields.next().is_some() {
+        return None;
+    }
+    Some(InputEvent {
+        cycle,
+        key,
+        pressed,
+    })
+}
+
+// the keypad after every event up to `events`' end
+fn keys_after(events: &[InputEvent]) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for event in events {
+        keys[event.key] = event.pressed;
+    }
+    keys
+}
+
+// Wraps a frontend and logs every change its `poll` makes to the keypad.
+// Rewinding drops what was logged after the point it went back to, so the
+// log follows the run that carried on from there.
+pub struct InputRecorder<'a> {
+    inner: &'a mut dyn Frontend,
+    header: LogHeader,
+    keys: [bool; 16],
+    events: Vec<InputEvent>,
+}
+
+impl<'a> InputRecorder<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, header: LogHeader) -> Self {
+        InputRecorder {
+            inner,
+            header,
+            keys: [false; 16],
+            events: Vec::new(),
+        }
+    }
+
+    // `vm` as the recording stopped
+    pub fn save(&self, path: &Path, vm: &VM) -> io::Result<()> {
+        let mut text = String::from(HEADER);
+        self.header.write(&mut text);
+        for event in &self.events {
+            let state = if event.pressed { "down" } else { "up" };
+            text.push_str(&format!("{} {:X} {}\n", event.cycle, event.key, state));
+        }
+        text.push_str(&format!("end {}\n", vm.cycles()));
+        fs::write(path, text)
+    }
+}
+
+impl DisplaySink for InputRecorder<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for InputRecorder<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for InputRecorder<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let control = self.inner.poll(vm);
+        if self
+            .events
+            .last()
+            .is_some_and(|event| event.cycle > vm.cycles())
+        {
+            self.events.retain(|event| event.cycle <= vm.cycles());
+            self.keys = keys_after(&self.events);
+        }
+        for key in 0..16 {
+            if vm.is_key_down(key) != self.keys[key] {
+                self.keys[key] = vm.is_key_down(key);
+                self.events.push(InputEvent {
+                    cycle: vm.cycles(),
+                    key,
+                    pressed: vm.is_key_down(key),
+                });
+            }
+        }
+        control
+    }
+}
+
+// Wraps a frontend and drives the keypad from a recorded log instead of the
+// frontend's keys; the frontend still presents, beeps and handles quitting.
+// After a rewind the log picks up again from the point gone back to. With
+// `exit_at_end` it quits once the VM reaches the cycle the recording stopped
+// at.
+pub struct InputReplayer<'a> {
+    inner: &'a mut dyn Frontend,
+    keys: [bool; 16],
+    events: Vec<InputEvent>,
+    next: usize,
+    // VM::cycles at the last poll
+    cycle: u64,
+    end: Option<u64>,
+}
+
+impl<'a> InputReplayer<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, log: InputLog, exit_at_end: bool) -> Self {
+        InputReplayer {
+            inner,
+            keys: [false; 16],
+            events: log.events,
+            next: 0,
+            cycle: 0,
+            end: exit_at_end.then_some(log.end),
+        }
+    }
+}
+
+impl DisplaySink for InputReplayer<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for InputReplayer<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for InputReplayer<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let control = self.inner.poll(vm);
+        if vm.cycles() < self.cycle {
+            self.next = self
+                .events
+                .partition_point(|event| event.cycle <= vm.cycles());
+            self.keys = keys_after(&self.events[..self.next]);
+        }
+        self.cycle = vm.cycles();
+        while let Some(event) = self.events.get(self.next) {
+            if event.cycle > vm.cycles() {
+                break;
+            }
+            self.keys[event.key] = event.pressed;
+            self.next += 1;
+        }
+        // the live keys the frontend just read don't count
+        vm.set_keys(self.keys);
+        if self.end.is_some_and(|end| vm.cycles() >= end) {
+            return Control::Quit;
+        }
+        control
+    }
+}
@@ -0,0 +1,286 @@
+use core::fmt;
+
+// One decoded CHIP-8 instruction. Field names follow the opcode patterns in
+// `OPCODES`: x/y register nibbles, nnn an address, nn a byte, n a nibble.
+// Words that aren't a known instruction decode to `Unknown` with the raw word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Sys(u16),
+    Cls,
+    Ret,
+    Jp(u16),
+    Call(u16),
+    SeVxByte { x: u8, nn: u8 },
+    SneVxByte { x: u8, nn: u8 },
+    SeVxVy { x: u8, y: u8 },
+    LdVxByte { x: u8, nn: u8 },
+    AddVxByte { x: u8, nn: u8 },
+    LdVxVy { x: u8, y: u8 },
+    Or { x: u8, y: u8 },
+    And { x: u8, y: u8 },
+    Xor { x: u8, y: u8 },
+    AddVxVy { x: u8, y: u8 },
+    Sub { x: u8, y: u8 },
+    Shr { x: u8, y: u8 },
+    Subn { x: u8, y: u8 },
+    Shl { x: u8, y: u8 },
+    SneVxVy { x: u8, y: u8 },
+    LdI(u16),
+    JpV0(u16),
+    Rnd { x: u8, nn: u8 },
+    Drw { x: u8, y: u8, n: u8 },
+    Skp { x: u8 },
+    Sknp { x: u8 },
+    LdVxDt { x: u8 },
+    LdVxK { x: u8 },
+    LdDtVx { x: u8 },
+    LdStVx { x: u8 },
+    AddIVx { x: u8 },
+    LdFVx { x: u8 },
+    LdBVx { x: u8 },
+    // FX55, store V0..=Vx at I
+    LdIVx { x: u8 },
+    // FX65, load V0..=Vx from I
+    LdVxI { x: u8 },
+    Unknown(u16),
+}
+
+use Instruction::*;
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Instruction {
+        let nnn = opcode & 0x0FFF;
+        let nn = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Cls,
+                0x00EE => Ret,
+                _ => Sys(nnn),
+            },
+            0x1000 => Jp(nnn),
+            0x2000 => Call(nnn),
+            0x3000 => SeVxByte { x, nn },
+            0x4000 => SneVxByte { x, nn },
+            0x5000 if n == 0 => SeVxVy { x, y },
+            0x6000 => LdVxByte { x, nn },
+            0x7000 => AddVxByte { x, nn },
+            0x8000 => match n {
+                0x0 => LdVxVy { x, y },
+                0x1 => Or { x, y },
+                0x2 => And { x, y },
+                0x3 => Xor { x, y },
+                0x4 => AddVxVy { x, y },
+                0x5 => Sub { x, y },
+                0x6 => Shr { x, y },
+                0x7 => Subn { x, y },
+                0xE => Shl { x, y },
+                _ => Unknown(opcode),
+            },
+            0x9000 if n == 0 => SneVxVy { x, y },
+            0xA000 => LdI(nnn),
+            0xB000 => JpV0(nnn),
+            0xC000 => Rnd { x, nn },
+            0xD000 => Drw { x, y, n },
+            0xE000 => match nn {
+                0x9E => Skp { x },
+                0xA1 => Sknp { x },
+                _ => Unknown(opcode),
+            },
+            0xF000 => match nn {
+                0x07 => LdVxDt { x },
+                0x0A => LdVxK { x },
+                0x15 => LdDtVx { x },
+                0x18 => LdStVx { x },
+                0x1E => AddIVx { x },
+                0x29 => LdFVx { x },
+                0x33 => LdBVx { x },
+                0x55 => LdIVx { x },
+                0x65 => LdVxI { x },
+                _ => Unknown(opcode),
+            },
+            _ => Unknown(opcode),
+        }
+    }
+
+    // Inverse of decode; fields wider than their nibble/byte are truncated
+    pub fn encode(&self) -> u16 {
+        match *self {
+            Sys(nnn) => nnn & 0x0FFF,
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            Jp(nnn) => 0x1000 | nnn & 0x0FFF,
+            Call(nnn) => 0x2000 | nnn & 0x0FFF,
+            SeVxByte { x, nn } => xnn(0x3000, x, nn),
+            SneVxByte { x, nn } => xnn(0x4000, x, nn),
+            SeVxVy { x, y } => xyn(0x5000, x, y, 0x0),
+            LdVxByte { x, nn } => xnn(0x6000, x, nn),
+            AddVxByte { x, nn } => xnn(0x7000, x, nn),
+            LdVxVy { x, y } => xyn(0x8000, x, y, 0x0),
+            Or { x, y } => xyn(0x8000, x, y, 0x1),
+            And { x, y } => xyn(0x8000, x, y, 0x2),
+            Xor { x, y } => xyn(0x8000, x, y, 0x3),
+            AddVxVy { x, y } => xyn(0x8000, x, y, 0x4),
+            Sub { x, y } => xyn(0x8000, x, y, 0x5),
+            Shr { x, y } => xyn(0x8000, x, y, 0x6),
+            Subn { x, y } => xyn(0x8000, x, y, 0x7),
+            Shl { x, y } => xyn(0x8000, x, y, 0xE),
+            SneVxVy { x, y } => xyn(0x9000, x, y, 0x0),
+            LdI(nnn) => 0xA000 | nnn & 0x0FFF,
+            JpV0(nnn) => 0xB000 | nnn & 0x0FFF,
+            Rnd { x, nn } => xnn(0xC000, x, nn),
+            Drw { x, y, n } => xyn(0xD000, x, y, n),
+            Skp { x } => xnn(0xE000, x, 0x9E),
+            Sknp { x } => xnn(0xE000, x, 0xA1),
+            LdVxDt { x } => xnn(0xF000, x, 0x07),
+            LdVxK { x } => xnn(0xF000, x, 0x0A),
+            LdDtVx { x } => xnn(0xF000, x, 0x15),
+            LdStVx { x } => xnn(0xF000, x, 0x18),
+            AddIVx { x } => xnn(0xF000, x, 0x1E),
+            LdFVx { x } => xnn(0xF000, x, 0x29),
+            LdBVx { x } => xnn(0xF000, x, 0x33),
+            LdIVx { x } => xnn(0xF000, x, 0x55),
+            LdVxI { x } => xnn(0xF000, x, 0x65),
+            Unknown(opcode) => opcode,
+        }
+    }
+
+    pub fn family(&self) -> Family {
+        match self {
+            Cls | Drw { .. } => Family::Draw,
+            LdVxByte { .. }
+            | AddVxByte { .. }
+            | LdVxVy { .. }
+            | Or { .. }
+            | And { .. }
+            | Xor { .. }
+            | AddVxVy { .. }
+            | Sub { .. }
+            | Shr { .. }
+            | Subn { .. }
+            | Shl { .. } => Family::Arithmetic,
+            SeVxByte { .. }
+            | SneVxByte { .. }
+            | SeVxVy { .. }
+            | SneVxVy { .. }
+            | Skp { .. }
+            | Sknp { .. } => Family::Skip,
+            Jp(_) | Call(_) | Ret | JpV0(_) => Family::Flow,
+            LdI(_) | AddIVx { .. } | LdFVx { .. } | LdBVx { .. } | LdIVx { .. } | LdVxI { .. } => {
+                Family::Memory
+            }
+            LdVxDt { .. } | LdDtVx { .. } | LdStVx { .. } => Family::Timer,
+            LdVxK { .. } => Family::Key,
+            Rnd { .. } => Family::Random,
+            Sys(_) | Unknown(_) => Family::Other,
+        }
+    }
+}
+
+// Coarse groups of instructions, for profiling what a ROM spends its time on.
+// The discriminants index `Family::ALL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    // CLS, DXYN
+    Draw,
+    // 6XNN, 7XNN, 8XYN
+    Arithmetic,
+    // 3XNN, 4XNN, 5XY0, 9XY0, EX9E, EXA1
+    Skip,
+    // 1NNN, 2NNN, 00EE, BNNN
+    Flow,
+    // ANNN and the FX instructions using I
+    Memory,
+    // FX07, FX15, FX18
+    Timer,
+    // FX0A
+    Key,
+    // CXNN
+    Random,
+    // SYS and unknown words
+    Other,
+}
+
+impl Family {
+    pub const ALL: [Family; 9] = [
+        Family::Draw,
+        Family::Arithmetic,
+        Family::Skip,
+        Family::Flow,
+        Family::Memory,
+        Family::Timer,
+        Family::Key,
+        Family::Random,
+        Family::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Draw => "draw",
+            Family::Arithmetic => "arithmetic",
+            Family::Skip => "skip",
+            Family::Flow => "flow",
+            Family::Memory => "memory",
+            Family::Timer => "timer",
+            Family::Key => "key",
+            Family::Random => "random",
+            Family::Other => "other",
+        }
+    }
+}
+
+// Assembler syntax: hex addresses and bytes, decimal sprite heights, and DW
+// for unknown words
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Sys(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            Jp(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            SeVxByte { x, nn } => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            SneVxByte { x, nn } => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            SeVxVy { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            LdVxByte { x, nn } => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            AddVxByte { x, nn } => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            LdVxVy { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddVxVy { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            // Vy is always shown, the shift quirk decides whether it's used
+            Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SneVxVy { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LdI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            JpV0(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Rnd { x, nn } => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp { x } => write!(f, "SKP V{:X}", x),
+            Sknp { x } => write!(f, "SKNP V{:X}", x),
+            LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            LdVxK { x } => write!(f, "LD V{:X}, K", x),
+            LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            AddIVx { x } => write!(f, "ADD I, V{:X}", x),
+            LdFVx { x } => write!(f, "LD F, V{:X}", x),
+            LdBVx { x } => write!(f, "LD B, V{:X}", x),
+            LdIVx { x } => write!(f, "LD [I], V{:X}", x),
+            LdVxI { x } => write!(f, "LD V{:X}, [I]", x),
+            Unknown(word) => write!(f, "DW 0x{:04X}", word),
+        }
+    }
+}
+
+fn xnn(base: u16, x: u8, nn: u8) -> u16 {
+    base | ((x as u16 & 0xF) << 8) | nn as u16
+}
+
+fn xyn(base: u16, x: u8, y: u8, n: u8) -> u16 {
+    base | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | (n as u16 & 0xF)
+}
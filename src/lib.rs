@@ -0,0 +1,61 @@
+// CHIP-8 interpreter core, free of any windowing, audio or OS randomness so it
+// can be driven by the SDL frontend, tests or a headless runner alike.
+//
+// Without the default `std` feature it builds as no_std with `alloc`, for
+// microcontrollers; file I/O and the real-time run loops need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod asm;
+mod audio;
+#[cfg(feature = "std")]
+mod breakpoints;
+mod builder;
+mod condition;
+mod config;
+mod disasm;
+mod error;
+mod frontend;
+mod instruction;
+mod observer;
+mod opcodes;
+mod rng;
+#[cfg(feature = "std")]
+mod rom;
+#[cfg(feature = "serde")]
+mod state;
+mod symbols;
+mod vm;
+mod watch;
+
+pub use asm::{AsmError, assemble};
+pub use audio::Tone;
+#[cfg(feature = "std")]
+pub use breakpoints::{Breakpoints, OpcodePattern};
+pub use builder::RomBuilder;
+pub use condition::{Cmp, Comparison, Condition, ConditionError, Operand};
+pub use config::{LowMemory, Profile, Quirk, Quirks, VmConfig};
+pub use disasm::{disassemble, disassemble_at, disassemble_rom};
+pub use error::Chip8Error;
+pub use frontend::{
+    AudioSink, AutoThrottle, Control, DEFAULT_CPU_HZ, DisplaySink, Frontend, InputSource,
+    NullFrontend, TIMER_HZ, instructions_per_frame,
+};
+#[cfg(feature = "std")]
+pub use frontend::{run, run_auto_throttle, run_lockstep, run_slowmo};
+pub use instruction::{Family, Instruction};
+pub use observer::{CodeWrite, FrameInfo, StepInfo, VmObserver};
+pub use opcodes::{OPCODES, OpcodeInfo, lookup};
+pub use rng::Rng;
+#[cfg(feature = "std")]
+pub use rom::{load_rom_entry, load_rom_from_path};
+#[cfg(feature = "serde")]
+pub use state::{STATE_VERSION, StateError};
+pub use symbols::{SymbolError, Symbols};
+pub use vm::{
+    Collision, FB_HEIGHT, FB_WIDTH, FONT, FONT_START, FrameOutcome, MEMORY_SIZE, ROM_START,
+    STACK_SIZE, Snapshot, StepResult, VM,
+};
+pub use watch::{Access, WatchHit, Watchpoint};
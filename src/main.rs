@@ -1,597 +1,1809 @@
-use rand;
-use sdl3::audio::{AudioCallback, AudioFormat, AudioSpec, AudioStream};
-use sdl3::event::Event;
-use sdl3::keyboard::Keycode;
-use sdl3::keyboard::Scancode;
-use sdl3::pixels::Color;
+use cheats::{CheatBook, CheatKey};
+#[cfg(not(feature = "sdl"))]
+use chip8_emu_rs::NullFrontend;
+use chip8_emu_rs::{
+    AudioSink, AutoThrottle, Breakpoints, Chip8Error, CodeWrite, Control, DisplaySink, FB_HEIGHT,
+    FB_WIDTH, Frontend, InputSource, LowMemory, OPCODES, Profile, Quirk, Quirks, ROM_START,
+    Symbols, VM, VmConfig, VmObserver, Watchpoint, assemble, disassemble_at,
+    instructions_per_frame, load_rom_entry, run, run_auto_throttle, run_lockstep, run_slowmo,
+};
+use debugger::{BreakSpec, Debugger};
+use event_log::{EventLog, EventLogger};
+use frames::{FrameCheck, HashedFrontend};
+use gdb::GdbStub;
+use hotloop::HotLoops;
+use input_log::{InputLog, InputRecorder, InputReplayer, LogHeader};
+use playlist::Playlist;
+use postmortem::History;
+use profile::{HostProfile, HostSampler, TimedFrontend};
+use rewind::Rewindable;
+use savestate::{RomNames, SaveStates};
+use script::{Script, Scripted};
+#[cfg(feature = "sdl")]
+use sdl::SdlFrontend;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+#[cfg(feature = "sdl")]
+use std::thread;
 use std::time::{Duration, Instant};
-use std::{env, fs, path::Path, process};
-
-// CHIP-8 framebuffer size
-const FB_WIDTH: u32 = 64;
-const FB_HEIGHT: u32 = 32;
-
-// Minifb window size
-const WINDOW_WIDTH: u32 = FB_WIDTH * 15;
-const WINDOW_HEIGHT: u32 = FB_HEIGHT * 15;
-
-const MEMORY_SIZE: usize = 4096;
-const ROM_START: usize = 0x200;
-
-const FONT_START: usize = 0x050;
-const FONT_BYTES: usize = 16 * 5;
-const FONT: [u8; FONT_BYTES] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+use std::{env, fs, process};
+use theme::{Palette, Theme, parse_color};
+use trace::{Trace, TraceOptions, Tracer};
+#[cfg(feature = "tui")]
+use tui::TuiFrontend;
+
+mod cheats;
+mod conformance;
+mod debugger;
+mod event_log;
+mod frames;
+mod gdb;
+#[cfg(feature = "sdl")]
+mod glyphs;
+mod hotloop;
+mod input_log;
+mod playlist;
+mod postmortem;
+mod profile;
+mod rewind;
+mod savestate;
+mod script;
+#[cfg(feature = "sdl")]
+mod sdl;
+mod search;
+mod sha1;
+mod statediff;
+mod theme;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "sdl")]
+mod wav;
+
+#[cfg(not(any(feature = "sdl", feature = "tui")))]
+compile_error!("the emulator binary needs the `sdl` or `tui` feature");
+
+// long enough that a 1-tick beep (17ms) is heard as a tone instead of a click
+#[cfg(feature = "sdl")]
+const DEFAULT_MIN_BEEP: Duration = Duration::from_millis(40);
+
+// draw collisions --log-collisions keeps, the latest ones
+const COLLISION_LOG_LEN: usize = 10_000;
+
+struct Config {
+    // more than one ROM makes a playlist, cycled with the N key
+    rom_paths: Vec<PathBuf>,
+    // which ROM to load from .zip archives holding several
+    entry: Option<String>,
+    // shortest audible beep; presentation only, the VM's sound timer is untouched
+    #[cfg(feature = "sdl")]
+    min_beep: Duration,
+    #[cfg(feature = "sdl")]
+    record_audio: Option<PathBuf>,
+    dump_on_error: Option<PathBuf>,
+    // one instruction per interval with a per-step trace, instead of full speed
+    slowmo: Option<Duration>,
+    // --deterministic: seeded CXNN and timers ticked once per frame, stall or not
+    deterministic: bool,
+    // run under the debugger prompt, paused from the start with --debug or
+    // until the first breakpoint or watchpoint with only --break/--watch
+    debug: bool,
+    start_paused: bool,
+    breakpoints: Breakpoints,
+    // listen for gdb there, and with gdb_wait hold the ROM until it attaches
+    gdb: Option<SocketAddr>,
+    gdb_wait: bool,
+    watch: Vec<Watchpoint>,
+    // adapt the instructions per frame to the ROM's drawing, within these bounds
+    auto_throttle: Option<(u32, u32)>,
+    // report ignored SYS calls / make them fatal
+    warn_sys: bool,
+    strict_sys: bool,
+    // fault on BNNN past the end of memory instead of wrapping
+    strict_jumps: bool,
+    // what FX33/FX55 writing below 0x200 does
+    low_memory: LowMemory,
+    // report FX33/FX55 writes over code that already ran / stop on them
+    log_smc: bool,
+    strict_smc: bool,
+    // stop cleanly when the program jumps to itself
+    halt_on_spin: bool,
+    // deterministic CXNN instead of OS randomness
+    seed: Option<u64>,
+    // CXNN always yields BYTE & NN, for rendering tests
+    fixed_rand: Option<u8>,
+    // --quirks, then the --quirk overrides in order
+    quirk_preset: Option<Profile>,
+    quirks: Vec<(Quirk, bool)>,
+    // print what the VM ended up configured with before running
+    show_quirks: bool,
+    // where the ROM is loaded and execution starts (0x600 for ETI-660 ROMs)
+    load_addr: u16,
+    // key events with their cycle counts, written on exit / fed back in
+    record_input: Option<PathBuf>,
+    replay_input: Option<(PathBuf, InputLog)>,
+    // quit when the replayed log ends
+    exit_after_end: bool,
+    // where to save the screen on exit
+    screenshot: Option<PathBuf>,
+    // the screen's hash at every frame, written to or checked against a file
+    frame_check: Option<FrameCheck>,
+    // where to write the draw collisions on exit
+    log_collisions: Option<PathBuf>,
+    // key presses, waits and screenshots to play against the running ROM
+    script: Option<Script>,
+    // save state to start from
+    state: Option<PathBuf>,
+    // from the ROMs' .cheats files
+    cheats: CheatBook,
+    // labels from --symbols
+    symbols: Symbols,
+    // run headless as fast as possible and report the speed instead
+    benchmark: bool,
+    // run headless for a number of instructions, writing the state before
+    // each one to a file for diffing against other emulators
+    conformance: Option<(PathBuf, u64)>,
+    // print the most executed addresses and instruction families on exit
+    profile: bool,
+    // write the same as JSON
+    profile_json: Option<PathBuf>,
+    // time instruction families and presents on the host clock
+    profile_host: bool,
+    // list the loops the PC got stuck in on exit
+    report_hot_loops: bool,
+    // log executed instructions to a file
+    trace: Option<TraceOptions>,
+    // log draws, keys, sounds and faults to a file, in these categories
+    // (empty for all)
+    event_log: Option<(PathBuf, Vec<event_log::Category>)>,
+    // --theme, with --fg/--bg applied on top
+    palette: Palette,
+    // start with the memory map overlay shown (F4 toggles it)
+    #[cfg(feature = "sdl")]
+    memmap: bool,
+    // start with the call stack overlay shown (F5 toggles it)
+    #[cfg(feature = "sdl")]
+    stack_overlay: bool,
+    // hold keys tapped between two frames down for a frame
+    #[cfg(feature = "sdl")]
+    latch_taps: bool,
+    // refresh even and odd rows on alternate frames
+    #[cfg(feature = "sdl")]
+    interlace: bool,
+    // pixel width over height, 1.0 for square pixels
+    #[cfg(feature = "sdl")]
+    pixel_aspect: f32,
+    // terminal instead of an SDL window; always on in builds without `sdl`
+    #[cfg(feature = "tui")]
+    tui: bool,
+}
+
+enum Command {
+    Run(Box<Config>),
+    Version,
+    ListOpcodes,
+    ShowQuirks,
+    // source file, ROM image to write
+    Assemble(PathBuf, PathBuf),
+    // ROM, listing file (stdout if none), load address, archive entry
+    Disassemble(PathBuf, Option<PathBuf>, u16, Option<String>, Symbols),
+    // the two save states to compare
+    DiffState(PathBuf, PathBuf),
+}
+
+const OPTIONS: &str = "\
+Options:
+  --entry NAME             the ROM to load from .zip archives holding more than one
+  --min-beep MS            stretch short beeps to at least MS milliseconds
+                           (default 40, 0 turns it off)
+  --record-audio FILE      write everything played to a 16-bit mono WAV file
+  --dump-on-error FILE     dump registers and RAM to FILE if the VM faults
+  --slowmo MS              run one instruction every MS milliseconds, tracing each
+  --debug                  start paused at a debugger prompt on stdin (type 'h' there
+                           for its commands)
+  --break ADDR             stop at the debugger prompt before running ADDR; may be
+                           repeated
+  --break-if COND          stop at the debugger prompt wherever COND holds, e.g.
+                           \"i >= 0xe00\", or only at ADDR with \"ADDR if COND\"
+  --break-op SPEC          stop at the debugger prompt before any opcode matching
+                           SPEC: \"PATTERN MASK\" in hex such as \"F00A F0FF\", or one
+                           of draw, keywait, rand, cls, call, ret, bcd, store, load
+  --watch SPEC             stop at the debugger prompt when memory at SPEC is
+                           accessed: ADDR or ADDR-END, then :r, :w (default) or :rw
+  --gdb ADDR               listen for gdb's remote protocol on ADDR, such as :3333
+                           (localhost) or 0.0.0.0:3333; attach with
+                           'target remote :3333'
+  --gdb-wait               hold the ROM until gdb attaches
+  --auto-throttle MIN:MAX  vary the instructions per frame between MIN and MAX to
+                           get about one screen update per frame
+  --quirks PRESET          behave like vip, chip48, schip or xo (which also gets
+                           64K of memory); see --show-quirks
+  --quirk NAME=on|off      set one quirk, after --quirks: shift, jump, clip,
+                           loadstore or vfreset; may be repeated
+  --load-addr ADDR         load the ROM and start execution at ADDR (default 0x200);
+                           it must be past the font, at 0x0A0 or above
+  --theme NAME             color scheme: default, gameboy, amber, c64 or matrix
+  --fg RRGGBB              pixel color, overriding the theme's
+  --bg RRGGBB              background color, overriding the theme's
+  --pixel-aspect W:H       draw pixels W:H wide, e.g. 2:1 (default 1:1)
+  --warn-sys               log every ignored 0NNN (SYS) call with its address
+  --strict-sys             stop with an error on any 0NNN (SYS) call but 0000
+  --strict-jumps           stop with an error when BNNN jumps past the end of
+                           memory instead of wrapping around to 0
+  --protect-low-memory MODE
+                           what FX33 or FX55 writing below 0x200, over the font,
+                           does: warn once per instruction (default), trap with
+                           an error, or off
+  --log-smc                log every FX33 or FX55 that overwrites an instruction
+                           that already ran, with the instruction before and after
+  --strict-smc             like --log-smc, and stop at the debugger prompt there
+  --halt-on-spin           stop with \"program finished\" when a jump goes to
+                           itself, the way test ROMs end
+  --seed N                 seed the random number generator for reproducible runs
+  --deterministic          make runs depend only on the ROM and input: seed 0
+                           unless --seed is given, and timers ticked once per
+                           frame even when the emulator falls behind
+  --fixed-rand BYTE        make CXNN always yield BYTE & NN; for tests only, games
+                           that roll dice get stuck on the same roll
+  --record-input FILE      log every key press and release with its cycle to FILE,
+                           along with the ROM, quirks, instructions per frame and
+                           seed
+  --play-input FILE        drive the keypad from a log written by --record-input,
+                           after checking it was recorded with the same ROM,
+                           quirks, instructions per frame and seed
+  --exit-after-end         quit where the --play-input recording stopped
+  --screenshot FILE        save the screen as a PPM image to FILE on exit
+  --record-frames FILE     write the screen's hash at every frame to FILE on exit;
+                           turns on --deterministic
+  --check-frames FILE      compare the screen's hash at every frame with FILE, as
+                           written by --record-frames, and fail at the first
+                           frame that differs; turns on --deterministic
+  --log-collisions FILE    write every DXYN that set VF to FILE on exit, with its
+                           cycle, address and coordinates; the last 10000
+  --script FILE            run the commands in FILE as the ROM plays: wait N
+                           (frames), press K, release K, reset, screenshot [FILE]
+                           and quit, one per line
+  --state FILE             start from a save state written with F8, checked
+                           against the ROM
+  --overlay-memmap         show RAM as a grid over the screen, with the font, ROM, I
+                           and PC highlighted (F4 toggles it)
+  --overlay-stack          show the call depth and the three innermost return
+                           addresses in a corner, red when outside the ROM (F5
+                           toggles it)
+  --interlace              update only every other row of the window per frame,
+                           alternating even and odd rows, like an interlaced display
+  --latch-taps             keep a key that was pressed and released within one
+                           frame down for that frame, so quick taps aren't lost
+  --tui                    draw in the terminal instead of a window (SSH friendly)
+  --benchmark              run the ROM headless at full speed for a few seconds and
+                           report instructions per second
+  --conformance FILE       run the ROM headless with no keys pressed and write
+                           PC, I and V0-VF in hex before every instruction to FILE,
+                           one line each, for diffing against other emulators
+  --cycles N               instructions run by --conformance (default 10000)
+  --profile                print the most executed instructions and a breakdown by
+                           instruction family on exit
+  --profile-json FILE      write the execution counts of every address and family
+                           to FILE as JSON on exit
+  --profile-host           time the emulator itself per instruction family and per
+                           rendered frame and print the averages on exit; slows it
+                           down, so only for comparing
+  --report-hot-loops       list the tight loops that ran for a long time without
+                           drawing or reading a key on exit, with cycles spent
+  --trace FILE             log every executed instruction with the registers it
+                           changed to FILE
+  --trace-filter KINDS     only log these comma-separated kinds of instruction:
+                           draw, cls, call, ret, jump, skip, key, timer, rand,
+                           mem, i, alu
+  --trace-range START-END  only log instructions at addresses START to END
+  --trace-after ADDR       start logging when execution first reaches ADDR
+  --trace-ring N           keep only the last N lines, written to the trace file
+                           if the VM faults
+  --event-log FILE         write what happened as the ROM ran to FILE, one JSON
+                           object per line with its frame and cycle: ROM loads,
+                           draws, keys, FX0A waits, sound and faults
+  --event-filter CATS      only log these comma-separated categories of event:
+                           rom, draw, key, wait, sound, fault
+  --assemble IN [-o] OUT   assemble the mnemonic source IN into the ROM image OUT
+  --symbols FILE           name addresses after the labels in FILE, NAME = ADDR per
+                           line or Octo's listing, in disassembly, the debugger,
+                           --break and --profile
+  --disassemble ROM [-o FILE]
+                           print a listing of ROM, or write it to FILE; addresses
+                           follow an earlier --load-addr, --entry and --symbols
+  --diff-state A B         compare two save states and print what differs; exit
+                           with 1 if they differ and 2 if one can't be read
+  --version                print the version and exit
+  --list-opcodes           print the supported opcodes and exit
+  --show-quirks            print the quirks, clock, memory and display in effect
+                           before running; without a ROM, print what each quirk
+                           does and each preset's settings and exit";
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [options] <path_to_rom>...", program);
+    eprintln!("{}", OPTIONS);
+    process::exit(1);
+}
+
+fn print_version() {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+}
+
+fn print_opcodes() {
+    print_version();
+    println!("{} opcode families implemented:", OPCODES.len());
+    for op in OPCODES {
+        println!("  {}  {:<20} {}", op.pattern, op.mnemonic, op.platform);
+    }
+}
+
+// --quirks names, in the order --show-quirks lists them
+const PRESETS: [(&str, Profile); 4] = [
+    ("vip", Profile::CosmacVip),
+    ("chip48", Profile::Chip48),
+    ("schip", Profile::SchipModern),
+    ("xo", Profile::XoChip),
 ];
 
-struct VM {
-    v: [u8; 16],
-    pc: u16,
-    i: u16,
-
-    memory: [u8; MEMORY_SIZE],
-    stack: Vec<u16>,
-    framebuffer: [u8; (FB_WIDTH * FB_HEIGHT) as usize],
-    keyboard: [bool; 16],
-
-    delay_timer: u8,
-    sound_timer: u8,
-}
-
-impl VM {
-    fn new() -> Self {
-        VM {
-            v: [0; 16],
-            pc: ROM_START as u16,
-            i: 0,
-            memory: [0; MEMORY_SIZE],
-            stack: Vec::new(),
-            framebuffer: [0; (FB_WIDTH * FB_HEIGHT) as usize],
-            delay_timer: 0,
-            sound_timer: 0,
-            keyboard: [false; 16],
-        }
-    }
-
-    fn step_timers(&mut self) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
-        }
-    }
-
-    fn step(&mut self) {
-        let opcode: u16 =
-            (self.memory[self.pc as usize] as u16) << 8 | self.memory[self.pc as usize + 1] as u16;
-        let nnn = opcode & 0x0FFF;
-        let nn = (opcode & 0x00FF) as usize;
-        let n = (opcode & 0x000F) as usize;
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        self.pc += 2;
-        match opcode & 0xF000 {
-            0x0000 => {
-                match opcode & 0x00FF {
-                    0x00E0 => {
-                        // CLEAR SCREEN
-                        for idx in 0..(FB_WIDTH * FB_HEIGHT) as usize {
-                            self.framebuffer[idx] = 0;
-                        }
-                    }
-
-                    0x00EE => {
-                        // RET
-                        let addr = self.stack.pop();
-                        self.pc = addr.expect("REASON");
-                    }
-
-                    _ => { /* SYS / ignored */ }
-                }
-            }
+// --quirk names, with what turning each on does
+const QUIRKS: [(&str, Quirk, &str); 6] = [
+    (
+        "shift",
+        Quirk::Shift,
+        "8XY6/8XYE shift Vy into Vx instead of Vx in place",
+    ),
+    (
+        "jump",
+        Quirk::Jump,
+        "BNNN jumps to XNN + VX instead of NNN + V0",
+    ),
+    (
+        "clip",
+        Quirk::Clip,
+        "sprites are cut off at the screen edges, not wrapped",
+    ),
+    (
+        "loadstore",
+        Quirk::LoadStore,
+        "FX55/FX65 leave I at I + X + 1, not unchanged",
+    ),
+    (
+        "vfreset",
+        Quirk::VfReset,
+        "8XY1/8XY2/8XY3 clear VF, as on the COSMAC VIP",
+    ),
+    (
+        "dispwait",
+        Quirk::DisplayWait,
+        "DXYN ends the frame, waiting for the display as the VIP did",
+    ),
+];
 
-            0x1000 => {
-                // JUMP nnn
-                self.pc = nnn;
-            }
+fn print_quirks() {
+    for (name, _, description) in QUIRKS {
+        println!("  {:<10} {}", name, description);
+    }
+    println!();
+    let header: Vec<String> = QUIRKS
+        .iter()
+        .map(|(name, _, _)| format!("{:<10}", name))
+        .collect();
+    println!("{:<10} {}", "", header.join(" ").trim_end());
+    let presets = PRESETS
+        .iter()
+        .map(|&(name, profile)| (name, Quirks::for_profile(profile)));
+    for (name, quirks) in [("default", Quirks::default())].into_iter().chain(presets) {
+        let cells: Vec<String> = QUIRKS
+            .iter()
+            .map(|&(_, quirk, _)| format!("{:<10}", if quirks.get(quirk) { "on" } else { "off" }))
+            .collect();
+        println!("{:<10} {}", name, cells.join(" ").trim_end());
+    }
+}
 
-            0x2000 => {
-                // CALL nnn
-                self.stack.push(self.pc);
-                self.pc = nnn;
-            }
+// the preset `quirks` match, or "default" if they're the defaults
+fn preset_name(quirks: &Quirks) -> Option<&'static str> {
+    let presets = PRESETS
+        .iter()
+        .map(|&(name, profile)| (name, Quirks::for_profile(profile)));
+    [("default", Quirks::default())]
+        .into_iter()
+        .chain(presets)
+        .find(|(_, preset)| preset == quirks)
+        .map(|(name, _)| name)
+}
 
-            0x3000 => {
-                // SE Vx, byte
-                if self.v[x] == nn as u8 {
-                    self.pc += 2;
-                }
-            }
+// --show-quirks with a ROM: the settings the VM was built with, after every
+// option was applied
+fn print_active_config(config: &Config, vm: &VM) {
+    let quirks: Vec<String> = QUIRKS
+        .iter()
+        .map(|&(name, quirk, _)| {
+            format!(
+                "{} {}",
+                name,
+                if vm.quirks.get(quirk) { "on" } else { "off" }
+            )
+        })
+        .collect();
+    println!("Quirks: {}", quirks.join(", "));
+    match config.auto_throttle {
+        Some((min, max)) => println!(
+            "Clock: {} Hz, {} to {} instructions per frame (--auto-throttle)",
+            vm.cpu_hz(),
+            min,
+            max
+        ),
+        None => println!(
+            "Clock: {} Hz, {} instructions per frame",
+            vm.cpu_hz(),
+            instructions_per_frame(vm)
+        ),
+    }
+    let rom = vm.rom_range();
+    println!(
+        "Memory: {} bytes, ROM at {:#05X}-{:#05X}",
+        vm.memory.len(),
+        rom.start,
+        rom.end.saturating_sub(1)
+    );
+    println!("Display: {}x{}", FB_WIDTH, FB_HEIGHT);
+}
 
-            0x4000 => {
-                // SNE Vx, byte
-                if self.v[x] != nn as u8 {
-                    self.pc += 2;
-                }
-            }
+// NAME=on or NAME=off, for --quirk
+fn parse_quirk(spec: &str) -> Option<(Quirk, bool)> {
+    let (name, value) = spec.split_once('=')?;
+    let &(_, quirk, _) = QUIRKS
+        .iter()
+        .find(|(n, _, _)| n.eq_ignore_ascii_case(name))?;
+    let on = match value {
+        "on" => true,
+        "off" => false,
+        _ => return None,
+    };
+    Some((quirk, on))
+}
 
-            0x5000 => {
-                // SE Vx, Vy
-                if self.v[x] == self.v[y] {
-                    self.pc += 2;
-                }
-            }
+// value following a `--option VALUE` argument
+fn option_value<'a>(args: &'a [String], idx: &mut usize) -> Result<&'a str, String> {
+    *idx += 1;
+    match args.get(*idx) {
+        Some(value) => Ok(value),
+        None => Err(format!("{} expects a value.", args[*idx - 1])),
+    }
+}
 
-            0x6000 => {
-                // LOAD Vx, nn
-                self.v[x] = nn as u8;
-            }
-
-            0x7000 => {
-                // ADD Vx, nn
-                self.v[x] = self.v[x].wrapping_add(nn as u8);
-            }
-
-            0x8000 => {
-                // Vx, Vy
-                match n as u8 {
-                    0 => {
-                        // LD Vx, Vy
-                        self.v[x] = self.v[y];
-                    }
-
-                    1 => {
-                        // OR Vx, Vy
-                        self.v[x] |= self.v[y];
-                    }
-
-                    2 => {
-                        // AND Vx, Vy
-                        self.v[x] &= self.v[y];
-                    }
-
-                    3 => {
-                        // XOR Vx, Vy
-                        self.v[x] ^= self.v[y];
-                    }
-
-                    4 => {
-                        // ADD Vx, Vy
-                        let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
-                        self.v[x] = sum;
-                        self.v[0xF] = if carry { 1 } else { 0 };
-                    }
-
-                    5 => {
-                        // SUB Vx, Vy
-                        let (diff, borrow) = self.v[x].overflowing_sub(self.v[y]);
-                        self.v[x] = diff;
-                        self.v[0xF] = if borrow { 0 } else { 1 };
-                    }
-
-                    6 => {
-                        // SHR Vx {, Vy}
-                        self.v[0xF] = self.v[x] & 0x01;
-                        self.v[x] >>= 1;
-                    }
-
-                    7 => {
-                        // SUBN Vx, Vy
-                        let (diff, borrow) = self.v[y].overflowing_sub(self.v[x]);
-                        self.v[x] = diff;
-                        self.v[0xF] = if borrow { 0 } else { 1 };
-                    }
-
-                    0x0E => {
-                        // SHL Vx {, Vy}
-                        self.v[0xF] = (self.v[x] & 0x80) >> 7;
-                        self.v[x] <<= 1;
-                    }
-
-                    _ => {
-                        // Unknown opcode
-                    }
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut rom_paths = Vec::new();
+    let mut entry = None;
+    let mut min_beep = None;
+    let mut record_audio = None;
+    let mut dump_on_error = None;
+    let mut slowmo = None;
+    let mut auto_throttle = None;
+    let mut seed = None;
+    let mut deterministic = false;
+    let mut fixed_rand = None;
+    let mut warn_sys = false;
+    let mut debug = false;
+    let mut gdb = None;
+    let mut gdb_wait = false;
+    let mut breakpoints = Breakpoints::new();
+    let mut watch = Vec::new();
+    let mut strict_sys = false;
+    let mut strict_jumps = false;
+    let mut low_memory = LowMemory::Warn;
+    let mut log_smc = false;
+    let mut strict_smc = false;
+    let mut halt_on_spin = false;
+    let mut quirk_preset = None;
+    let mut show_quirks = false;
+    let mut quirks = Vec::new();
+    let mut load_addr = ROM_START as u16;
+    let mut record_input = None;
+    let mut replay_input = None;
+    let mut exit_after_end = false;
+    let mut screenshot = None;
+    let mut frame_check = None;
+    let mut log_collisions = None;
+    let mut script = None;
+    let mut state = None;
+    let mut symbols = Symbols::default();
+    // --break and --break-if, resolved once every --symbols file is read
+    let mut break_specs = Vec::new();
+    let mut benchmark = false;
+    let mut conformance = None;
+    let mut cycles = None;
+    let mut profile = false;
+    let mut profile_json = None;
+    let mut profile_host = false;
+    let mut report_hot_loops = false;
+    let mut trace_path = None;
+    let mut event_log_path = None;
+    let mut event_categories = Vec::new();
+    let mut trace_kinds = Vec::new();
+    let mut trace_range = None;
+    let mut trace_after = None;
+    let mut trace_ring = None;
+    let mut tui = false;
+    let mut memmap = false;
+    let mut stack_overlay = false;
+    let mut interlace = false;
+    let mut latch_taps = false;
+    let mut pixel_aspect = None;
+    let mut theme = Theme::Default;
+    let mut fg = None;
+    let mut bg = None;
+
+    let mut idx = 1;
+    while idx < args.len() {
+        match args[idx].as_str() {
+            "--version" => return Ok(Command::Version),
+            "--list-opcodes" => return Ok(Command::ListOpcodes),
+            "--show-quirks" => show_quirks = true,
+            "--assemble" => {
+                let input = PathBuf::from(option_value(args, &mut idx)?);
+                if args.get(idx + 1).is_some_and(|arg| arg == "-o") {
+                    idx += 1;
                 }
+                let output = PathBuf::from(option_value(args, &mut idx)?);
+                return Ok(Command::Assemble(input, output));
             }
-
-            0x9000 => {
-                // SNE Vx, Vy
-                if self.v[x] != self.v[y] {
-                    self.pc += 2;
+            "--disassemble" => {
+                let rom = PathBuf::from(option_value(args, &mut idx)?);
+                let mut output = None;
+                if args.get(idx + 1).is_some_and(|arg| arg == "-o") {
+                    idx += 1;
+                    output = Some(PathBuf::from(option_value(args, &mut idx)?));
                 }
+                return Ok(Command::Disassemble(rom, output, load_addr, entry, symbols));
             }
-
-            0xA000 => {
-                // LOAD i, nnn
-                self.i = nnn;
-            }
-
-            0xB000 => {
-                // JUMP V0, nnn
-                self.pc = nnn + self.v[0] as u16;
-            }
-
-            0xC000 => {
-                // RND Vx, byte
-                let rnd_byte: u8 = rand::random::<u8>();
-                self.v[x] = rnd_byte & (nn as u8);
-            }
-
-            0xD000 => {
-                // DRAW Vx, Vy, n
-                let vx = self.v[x] as usize;
-                let vy = self.v[y] as usize;
-                self.v[0xF] = 0;
-                for row in 0usize..n as usize {
-                    let sprite_byte = self.memory[self.i as usize + row];
-                    for col in 0usize..8 {
-                        let fb_idx = (((vy + row) % FB_HEIGHT as usize) * FB_WIDTH as usize)
-                            + (vx + col) % FB_WIDTH as usize;
-                        let fb_byte: u8 = self.framebuffer[fb_idx];
-                        let sprite_pixel: u8 = (0b1000_0000 >> col) & sprite_byte;
-                        if sprite_pixel != 0 && fb_byte == 0x00 {
-                            // Light up pixel
-                            self.framebuffer[fb_idx] = 0xFF;
-                        } else if sprite_pixel != 0 && fb_byte == 0xFF {
-                            // Turn off pixel, and set VF because of collision
-                            self.v[0xF] = 1;
-                            self.framebuffer[fb_idx] = 0x00;
-                        }
-                    }
+            "--diff-state" => {
+                let a = PathBuf::from(option_value(args, &mut idx)?);
+                let b = PathBuf::from(option_value(args, &mut idx)?);
+                return Ok(Command::DiffState(a, b));
+            }
+            "--entry" => entry = Some(option_value(args, &mut idx)?.to_string()),
+            // --min-beep-ms is the option's old name
+            "--min-beep" | "--min-beep-ms" => {
+                let ms: u64 = option_value(args, &mut idx)?
+                    .parse()
+                    .map_err(|_| "--min-beep expects a number of milliseconds.")?;
+                min_beep = Some(Duration::from_millis(ms));
+            }
+            "--record-audio" => {
+                record_audio = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--dump-on-error" => {
+                dump_on_error = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--slowmo" => {
+                let ms: u64 = option_value(args, &mut idx)?
+                    .parse()
+                    .map_err(|_| "--slowmo expects a number of milliseconds.")?;
+                slowmo = Some(Duration::from_millis(ms));
+            }
+            "--auto-throttle" => {
+                auto_throttle = Some(
+                    parse_bounds(option_value(args, &mut idx)?)
+                        .ok_or("--auto-throttle expects bounds such as 5:50.")?,
+                );
+            }
+            "--deterministic" => deterministic = true,
+            "--seed" => {
+                let value = option_value(args, &mut idx)?
+                    .parse()
+                    .map_err(|_| "--seed expects a non-negative integer.")?;
+                seed = Some(value);
+            }
+            "--fixed-rand" => {
+                fixed_rand = Some(
+                    parse_byte(option_value(args, &mut idx)?)
+                        .ok_or("--fixed-rand expects a byte such as 0x55.")?,
+                );
+            }
+            "--quirks" => {
+                let name = option_value(args, &mut idx)?;
+                let &(_, profile) = PRESETS
+                    .iter()
+                    .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+                    .ok_or("--quirks expects vip, chip48, schip or xo.")?;
+                quirk_preset = Some(profile);
+            }
+            "--quirk" => quirks.push(
+                parse_quirk(option_value(args, &mut idx)?)
+                    .ok_or("--quirk expects NAME=on or NAME=off, see --show-quirks.")?,
+            ),
+            "--load-addr" => {
+                load_addr = parse_addr(option_value(args, &mut idx)?)
+                    .ok_or("--load-addr expects an address such as 0x600.")?;
+            }
+            "--benchmark" => benchmark = true,
+            "--conformance" => conformance = Some(PathBuf::from(option_value(args, &mut idx)?)),
+            "--cycles" => {
+                let value = option_value(args, &mut idx)?
+                    .parse()
+                    .map_err(|_| "--cycles expects a non-negative integer.")?;
+                cycles = Some(value);
+            }
+            "--profile" => profile = true,
+            "--profile-host" => profile_host = true,
+            "--report-hot-loops" => report_hot_loops = true,
+            "--profile-json" => profile_json = Some(PathBuf::from(option_value(args, &mut idx)?)),
+            "--trace" => trace_path = Some(PathBuf::from(option_value(args, &mut idx)?)),
+            "--trace-filter" => {
+                for name in option_value(args, &mut idx)?.split(',') {
+                    trace_kinds.push(trace::Kind::from_name(name.trim()).ok_or_else(|| {
+                        format!(
+                            "unknown --trace-filter kind '{}', expected some of {}.",
+                            name,
+                            trace::kind_names()
+                        )
+                    })?);
                 }
             }
-
-            0xE000 => {
-                match nn as u8 {
-                    0x9E => {
-                        // SKP Vx
-                        let key = self.v[x] as usize;
-                        if self.keyboard[key] {
-                            self.pc += 2;
-                        }
-                    }
-
-                    0xA1 => {
-                        // SKNP Vx
-                        let key = self.v[x] as usize;
-                        if !self.keyboard[key] {
-                            self.pc += 2;
-                        }
-                    }
-
-                    _ => {
-                        // Unknown opcode
-                    }
+            "--event-log" => {
+                event_log_path = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--event-filter" => {
+                for name in option_value(args, &mut idx)?.split(',') {
+                    event_categories.push(event_log::Category::from_name(name.trim()).ok_or_else(
+                        || {
+                            format!(
+                                "unknown --event-filter category '{}', expected some of {}.",
+                                name,
+                                event_log::category_names()
+                            )
+                        },
+                    )?);
                 }
             }
-
-            0xF000 => {
-                match nn as u8 {
-                    0x07 => {
-                        // Vx = get_delay()
-                        self.v[x] = self.delay_timer;
-                    }
-
-                    0x0A => {
-                        // Vx = get_key()
-                        for key in 0..16 {
-                            if self.keyboard[key] {
-                                self.v[x] = key as u8;
-                                return;
-                            }
-                        }
-                        self.pc -= 2;
-                    }
-
-                    0x15 => {
-                        // delay_timer(Vx)
-                        self.delay_timer = self.v[x];
-                    }
-
-                    0x18 => {
-                        // sound_timer(Vx)
-                        self.sound_timer = self.v[x];
-                    }
-
-                    0x1E => {
-                        // ADD I, Vx
-                        self.i = self.i.wrapping_add(self.v[x] as u16);
-                    }
-
-                    0x29 => {
-                        // I = sprite_addr[Vx]
-                        let digit = self.v[x] as u16;
-                        self.i = FONT_START as u16 + (digit * 5);
-                    }
-
-                    0x33 => {
-                        // set_BCD(Vx) *(I+0) = BCD(3); *(I+1) = BCD(2); *(I+2) = BCD(1);
-                        let vx = self.v[x];
-                        self.memory[self.i as usize] = vx / 100;
-                        self.memory[self.i as usize + 1] = (vx % 100) / 10;
-                        self.memory[self.i as usize + 2] = vx % 10;
-                    }
-
-                    0x55 => {
-                        // LD [I], V0..Vx
-                        for idx in 0..=x {
-                            self.memory[self.i as usize + idx] = self.v[idx];
-                        }
-                    }
-
-                    0x65 => {
-                        // LD V0..Vx, [I]
-                        for idx in 0..=x {
-                            self.v[idx] = self.memory[self.i as usize + idx];
-                        }
-                    }
-
-                    _ => {
-                        // Unknown opcode
-                    }
+            "--trace-range" => {
+                let (start, end) = parse_range(option_value(args, &mut idx)?)
+                    .ok_or("--trace-range expects addresses such as 0x200-0x2FF.")?;
+                trace_range = Some(start..=end);
+            }
+            "--trace-after" => {
+                trace_after = Some(
+                    parse_addr(option_value(args, &mut idx)?)
+                        .ok_or("--trace-after expects an address such as 0x230.")?,
+                );
+            }
+            "--trace-ring" => {
+                let lines: usize = option_value(args, &mut idx)?
+                    .parse()
+                    .map_err(|_| "--trace-ring expects a number of lines.")?;
+                if lines == 0 {
+                    return Err("--trace-ring expects a number of lines.".to_string());
                 }
+                trace_ring = Some(lines);
             }
-
-            _ => {
-                // Unknown opcode
+            "--debug" => debug = true,
+            "--gdb" => {
+                let value = option_value(args, &mut idx)?;
+                gdb = Some(parse_socket_addr(value).ok_or_else(|| {
+                    format!(
+                        "--gdb expects an address such as :3333 or localhost:3333, got '{}'.",
+                        value
+                    )
+                })?);
+            }
+            "--gdb-wait" => gdb_wait = true,
+            "--watch" => {
+                watch.push(
+                    debugger::parse_watch(option_value(args, &mut idx)?)
+                        .ok_or("--watch expects a range such as 0x300-0x302:w.")?,
+                );
+            }
+            option @ ("--break" | "--break-if") => {
+                break_specs.push((option, option_value(args, &mut idx)?));
+            }
+            "--break-op" => {
+                let spec: Vec<&str> = option_value(args, &mut idx)?.split_whitespace().collect();
+                breakpoints.add_opcode(debugger::parse_opcode_pattern(&spec).ok_or(
+                    "--break-op expects \"PATTERN MASK\" in hex or a name such as keywait.",
+                )?);
+            }
+            "--warn-sys" => warn_sys = true,
+            "--strict-sys" => strict_sys = true,
+            "--strict-jumps" => strict_jumps = true,
+            "--protect-low-memory" => {
+                low_memory = match option_value(args, &mut idx)? {
+                    "warn" => LowMemory::Warn,
+                    "trap" => LowMemory::Trap,
+                    "off" => LowMemory::Off,
+                    _ => return Err("--protect-low-memory expects warn, trap or off.".to_string()),
+                };
+            }
+            "--log-smc" => log_smc = true,
+            "--strict-smc" => strict_smc = true,
+            "--halt-on-spin" => halt_on_spin = true,
+            "--tui" => tui = true,
+            "--overlay-memmap" => memmap = true,
+            "--overlay-stack" => stack_overlay = true,
+            "--interlace" => interlace = true,
+            "--latch-taps" => latch_taps = true,
+            "--pixel-aspect" => {
+                pixel_aspect = Some(
+                    parse_aspect(option_value(args, &mut idx)?)
+                        .ok_or("--pixel-aspect expects a ratio such as 2:1.")?,
+                );
+            }
+            "--theme" => theme = Theme::from_name(option_value(args, &mut idx)?)?,
+            "--fg" => {
+                fg = Some(
+                    parse_color(option_value(args, &mut idx)?)
+                        .ok_or("--fg expects a color such as 33FF66.")?,
+                );
+            }
+            "--bg" => {
+                bg = Some(
+                    parse_color(option_value(args, &mut idx)?)
+                        .ok_or("--bg expects a color such as 101010.")?,
+                );
+            }
+            "--record-input" => {
+                record_input = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            // --replay-input is its older name
+            "--play-input" | "--replay-input" => {
+                let path = PathBuf::from(option_value(args, &mut idx)?);
+                let log = InputLog::load(&path)
+                    .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+                replay_input = Some((path, log));
+            }
+            "--exit-after-end" => exit_after_end = true,
+            "--screenshot" => {
+                screenshot = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--record-frames" => {
+                frame_check = Some(FrameCheck::Record(PathBuf::from(option_value(
+                    args, &mut idx,
+                )?)));
+            }
+            "--check-frames" => {
+                let path = PathBuf::from(option_value(args, &mut idx)?);
+                let hashes = frames::load(&path)
+                    .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+                frame_check = Some(FrameCheck::Verify(path, hashes));
+            }
+            "--log-collisions" => {
+                log_collisions = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--script" => {
+                let path = option_value(args, &mut idx)?;
+                script = Some(
+                    Script::load(Path::new(path))
+                        .map_err(|e| format!("cannot read '{}': {}", path, e))?,
+                );
+            }
+            "--state" => {
+                state = Some(PathBuf::from(option_value(args, &mut idx)?));
+            }
+            "--symbols" => {
+                let path = option_value(args, &mut idx)?;
+                let text = fs::read_to_string(path)
+                    .map_err(|e| format!("cannot read '{}': {}", path, e))?;
+                symbols = Symbols::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+            }
+            arg if arg.starts_with("--") => {
+                return Err(format!("unknown option '{}'.", arg));
             }
+            arg => rom_paths.push(PathBuf::from(arg)),
         }
+        idx += 1;
     }
 
-    fn load_font(&mut self) {
-        self.memory[FONT_START..FONT_START + FONT_BYTES].copy_from_slice(&FONT);
+    for (option, spec) in break_specs {
+        if option == "--break" {
+            let addr = parse_addr(spec).or_else(|| symbols.addr(spec));
+            breakpoints.insert(addr.ok_or_else(|| {
+                if spec.starts_with(|c: char| c.is_ascii_digit()) {
+                    "--break expects an address such as 0x230, or a label.".to_string()
+                } else {
+                    format!("--break: {}", debugger::unknown_label(spec, &symbols))
+                }
+            })?);
+            continue;
+        }
+        match debugger::parse_break(spec, &symbols).map_err(|msg| format!("--break-if: {}", msg))? {
+            BreakSpec::At(addr, None) => {
+                breakpoints.insert(addr);
+            }
+            BreakSpec::At(addr, Some(condition)) => breakpoints.insert_if(addr, condition),
+            BreakSpec::Anywhere(condition) => breakpoints.add_condition(condition),
+        }
     }
 
-    fn load_rom(&mut self, rom: &[u8]) {
-        const START: usize = ROM_START;
-        let end = START + rom.len();
-        if end > self.memory.len() {
-            eprintln!(
-                "Error: ROM too large ({} bytes). Max allowed from 0x200 is {} bytes.",
-                rom.len(),
-                self.memory.len() - START
-            );
-            process::exit(1);
+    if show_quirks && rom_paths.is_empty() {
+        return Ok(Command::ShowQuirks);
+    }
+    if rom_paths.is_empty() {
+        return Err("missing ROM path.".to_string());
+    }
+    if record_input.is_some() && replay_input.is_some() {
+        return Err("--record-input and --play-input can't be combined.".to_string());
+    }
+    // the log would override the script's keys
+    if script.is_some() && replay_input.is_some() {
+        return Err("--script and --play-input can't be combined.".to_string());
+    }
+    if exit_after_end && replay_input.is_none() {
+        return Err("--exit-after-end needs --play-input FILE.".to_string());
+    }
+    if tui && !cfg!(feature = "tui") {
+        return Err("--tui needs a build with the `tui` feature.".to_string());
+    }
+    let start_paused = debug;
+    let debug = debug || !breakpoints.is_empty() || !watch.is_empty() || strict_smc;
+    // the prompt and the terminal frontend would both read stdin
+    if tui && debug {
+        return Err("the debugger options can't be combined with --tui.".to_string());
+    }
+    if gdb_wait && gdb.is_none() {
+        return Err("--gdb-wait needs --gdb ADDR.".to_string());
+    }
+    if gdb.is_some() && debug {
+        return Err("--gdb can't be combined with the debugger options.".to_string());
+    }
+    if gdb.is_some()
+        && (slowmo.is_some() || auto_throttle.is_some() || benchmark || conformance.is_some())
+    {
+        return Err(
+            "--gdb can't be combined with --slowmo, --auto-throttle, --benchmark or --conformance."
+                .to_string(),
+        );
+    }
+    if debug && (slowmo.is_some() || auto_throttle.is_some()) {
+        return Err(
+            "the debugger options can't be combined with --slowmo or --auto-throttle.".to_string(),
+        );
+    }
+    let tui = tui || !cfg!(feature = "sdl");
+    if tui && record_audio.is_some() {
+        return Err("--record-audio needs the SDL frontend.".to_string());
+    }
+    if tui && min_beep.is_some() {
+        return Err("--min-beep needs the SDL frontend.".to_string());
+    }
+    if tui && memmap {
+        return Err("--overlay-memmap needs the SDL frontend.".to_string());
+    }
+    if tui && stack_overlay {
+        return Err("--overlay-stack needs the SDL frontend.".to_string());
+    }
+    if tui && latch_taps {
+        return Err("--latch-taps needs the SDL frontend.".to_string());
+    }
+    if tui && interlace {
+        return Err("--interlace needs the SDL frontend.".to_string());
+    }
+    if tui && pixel_aspect.is_some() {
+        return Err("--pixel-aspect needs the SDL frontend.".to_string());
+    }
+    if slowmo.is_some() && auto_throttle.is_some() {
+        return Err("--slowmo and --auto-throttle can't be combined.".to_string());
+    }
+    if seed.is_some() && fixed_rand.is_some() {
+        return Err("--seed and --fixed-rand can't be combined.".to_string());
+    }
+    // CXNN rolls what it rolled while recording; a --seed of its own that
+    // doesn't match is caught once the VM is up
+    if let Some((_, log)) = &replay_input
+        && seed.is_none()
+        && fixed_rand.is_none()
+    {
+        seed = log.header.seed;
+        fixed_rand = log.header.fixed_rand;
+    }
+    // the frames only repeat when nothing but the input decides them
+    if frame_check.is_some() {
+        if debug || benchmark || conformance.is_some() {
+            return Err("--record-frames and --check-frames can't be combined with \
+                 --benchmark, --conformance or the debugger options."
+                .to_string());
         }
-
-        self.memory[START..end].copy_from_slice(rom);
+        deterministic = true;
+    }
+    if deterministic && slowmo.is_some() {
+        return Err("--deterministic can't be combined with --slowmo.".to_string());
+    }
+    if deterministic && seed.is_none() && fixed_rand.is_none() {
+        seed = Some(0);
+    }
+    // so the log can replay CXNN's numbers
+    if record_input.is_some() && seed.is_none() && fixed_rand.is_none() {
+        seed = Some(rand::random());
+    }
+    if conformance.is_none() && cycles.is_some() {
+        return Err("--cycles needs --conformance FILE.".to_string());
     }
+    if conformance.is_some() && (benchmark || debug) {
+        return Err(
+            "--conformance can't be combined with --benchmark or the debugger options.".to_string(),
+        );
+    }
+    let conformance = conformance.map(|path| (path, cycles.unwrap_or(conformance::DEFAULT_CYCLES)));
+    let trace_narrowed = !trace_kinds.is_empty()
+        || trace_range.is_some()
+        || trace_after.is_some()
+        || trace_ring.is_some();
+    if trace_path.is_none() && trace_narrowed {
+        return Err("the --trace-* options need --trace FILE.".to_string());
+    }
+    let trace = trace_path.map(|path| TraceOptions {
+        path,
+        kinds: trace_kinds,
+        range: trace_range,
+        after: trace_after,
+        ring: trace_ring,
+    });
+    if event_log_path.is_none() && !event_categories.is_empty() {
+        return Err("--event-filter needs --event-log FILE.".to_string());
+    }
+    let event_log = event_log_path.map(|path| (path, event_categories));
+    let mut palette = theme.palette();
+    if let Some(fg) = fg {
+        palette.0[1] = fg;
+    }
+    if let Some(bg) = bg {
+        palette.0[0] = bg;
+    }
+    let mut cheats = CheatBook::default();
+    for rom in &rom_paths {
+        let path = cheats::sidecar(rom);
+        if path.is_file() {
+            let list = cheats::load(&path)
+                .map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+            cheats.add(&read_rom(rom, entry.as_deref()), list);
+        }
+    }
+    Ok(Command::Run(Box::new(Config {
+        rom_paths,
+        entry,
+        cheats,
+        symbols,
+        #[cfg(feature = "sdl")]
+        min_beep: min_beep.unwrap_or(DEFAULT_MIN_BEEP),
+        #[cfg(feature = "sdl")]
+        record_audio,
+        dump_on_error,
+        slowmo,
+        deterministic,
+        debug,
+        start_paused,
+        gdb,
+        gdb_wait,
+        breakpoints,
+        watch,
+        auto_throttle,
+        warn_sys,
+        strict_sys,
+        strict_jumps,
+        low_memory,
+        log_smc: log_smc || strict_smc,
+        strict_smc,
+        halt_on_spin,
+        seed,
+        fixed_rand,
+        quirk_preset,
+        quirks,
+        show_quirks,
+        load_addr,
+        record_input,
+        replay_input,
+        exit_after_end,
+        screenshot,
+        frame_check,
+        log_collisions,
+        script,
+        state,
+        benchmark,
+        conformance,
+        profile,
+        profile_json,
+        profile_host,
+        report_hot_loops,
+        trace,
+        event_log,
+        palette,
+        #[cfg(feature = "sdl")]
+        memmap,
+        #[cfg(feature = "sdl")]
+        stack_overlay,
+        #[cfg(feature = "sdl")]
+        latch_taps,
+        #[cfg(feature = "sdl")]
+        interlace,
+        #[cfg(feature = "sdl")]
+        pixel_aspect: pixel_aspect.unwrap_or(1.0),
+        // the debugger without SDL runs headless, `fb` shows the screen
+        #[cfg(feature = "tui")]
+        tui: tui && !debug,
+    })))
 }
 
-fn parse_args() -> Vec<u8> {
-    // return ROM data
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <path_to_rom>", args[0]);
-        process::exit(1);
+// hex with a 0x prefix, decimal otherwise
+fn parse_addr(value: &str) -> Option<u16> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
     }
-    let rom_path = &args[1];
-    let path = Path::new(rom_path);
+}
+
+// a byte, hex with a 0x prefix or decimal
+fn parse_byte(value: &str) -> Option<u8> {
+    parse_addr(value)?.try_into().ok()
+}
+
+// W:H, both positive, as W / H
+fn parse_aspect(value: &str) -> Option<f32> {
+    let (w, h) = value.split_once(':')?;
+    let (w, h): (u32, u32) = (w.parse().ok()?, h.parse().ok()?);
+    (w > 0 && h > 0).then(|| w as f32 / h as f32)
+}
+
+// HOST:PORT, or :PORT for localhost as gdb writes it
+fn parse_socket_addr(value: &str) -> Option<SocketAddr> {
+    let value = match value.strip_prefix(':') {
+        Some(port) => format!("127.0.0.1:{}", port),
+        None => value.to_string(),
+    };
+    value.to_socket_addrs().ok()?.next()
+}
+
+// START-END, both addresses as parse_addr reads them, START <= END
+fn parse_range(value: &str) -> Option<(u16, u16)> {
+    let (start, end) = value.split_once('-')?;
+    let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+    (start <= end).then_some((start, end))
+}
+
+// MIN:MAX instructions per frame, 0 < MIN <= MAX
+fn parse_bounds(value: &str) -> Option<(u32, u32)> {
+    let (min, max) = value.split_once(':')?;
+    let (min, max): (u32, u32) = (min.parse().ok()?, max.parse().ok()?);
+    (min > 0 && min <= max).then_some((min, max))
+}
+
+fn read_rom(path: &Path, entry: Option<&str>) -> Vec<u8> {
     if !path.exists() {
-        eprintln!("Error: ROM file '{}' does not exist.", rom_path);
+        eprintln!("Error: ROM file '{}' does not exist.", path.display());
         process::exit(1);
     }
     if !path.is_file() {
-        eprintln!("Error: '{}' is not a file.", rom_path);
+        eprintln!("Error: '{}' is not a file.", path.display());
         process::exit(1);
     }
-    let rom_data: Vec<u8> = match fs::read(path) {
+    match load_rom_entry(path, entry) {
         Ok(data) => data,
+        Err(Chip8Error::AmbiguousArchive { entries }) => {
+            eprintln!(
+                "Error: '{}' holds more than one ROM, pick one with --entry NAME:",
+                path.display()
+            );
+            for entry in entries {
+                eprintln!("  {}", entry);
+            }
+            process::exit(1);
+        }
         Err(e) => {
             eprintln!("Failed to read ROM: {}", e);
             process::exit(1);
         }
-    };
-    return rom_data;
+    }
 }
 
-fn main() {
-    // VM setup
-    let mut vm: VM = VM::new();
-    let rom_data: Vec<u8> = parse_args();
-    vm.load_rom(&rom_data);
-    vm.load_font();
-
-    // Window setup
-    let sdl_context = sdl3::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-
-    let window = video_subsystem
-        .window("chip8-emu-rs", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .build()
-        .unwrap();
-
-    let mut canvas = window.into_canvas();
-
-    // this allows to treat the canvas as FB_WIDTH x FB_WIDTH surface and then
-    // SDL automatically scales it to the window resolution
-    let _ = canvas.set_logical_size(
-        FB_WIDTH,
-        FB_HEIGHT,
-        sdl3_sys::render::SDL_RendererLogicalPresentation(2), //STRETCH
-    );
+fn assemble_file(input: &Path, output: &Path) {
+    let source = match fs::read_to_string(input) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: cannot read '{}': {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+    let rom = match assemble(&source) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Error: {}: {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(output, &rom) {
+        eprintln!("Error: cannot write '{}': {}", output.display(), e);
+        process::exit(1);
+    }
+    println!("Wrote {} bytes to '{}'.", rom.len(), output.display());
+}
 
-    let mut prev_framebuffer: Vec<u8> = vec![0; (FB_WIDTH * FB_HEIGHT) as usize];
-    // Audio setup
-    struct SquareWave {
-        phase_inc: f32,
-        phase: f32,
-        volume: f32,
-    }
-
-    impl AudioCallback<f32> for SquareWave {
-        fn callback(&mut self, stream: &mut AudioStream, requested: i32) {
-            let mut out = Vec::<f32>::with_capacity(requested as usize);
-            // Generate a square wave
-            for _ in 0..requested {
-                out.push(if self.phase <= 0.5 {
-                    self.volume
-                } else {
-                    -self.volume
-                });
-                self.phase = (self.phase + self.phase_inc) % 1.0;
+fn disassemble_file(
+    rom: &Path,
+    output: Option<&Path>,
+    start: u16,
+    entry: Option<&str>,
+    symbols: &Symbols,
+) {
+    let rom = read_rom(rom, entry);
+    let mut listing = symbols.disassemble_rom(&rom, start).join("\n");
+    listing.push('\n');
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, listing) {
+                eprintln!("Error: cannot write '{}': {}", path.display(), e);
+                process::exit(1);
             }
-            let _ = stream.put_data_f32(&out);
         }
+        None => print!("{}", listing),
     }
+}
 
-    let sdl_context = sdl3::init().unwrap();
-    let audio_subsystem = sdl_context.audio().unwrap();
+// --log-collisions: one line per DXYN that set VF, oldest first, as
+//
+//     <cycle> <pc> <x> <y> <pixels turned off>
+//
+// with the address in hex and the rest in decimal, for scripts to pick up
+fn write_collisions(path: &Path, vm: &VM) -> io::Result<()> {
+    let mut out = BufWriter::new(fs::File::create(path)?);
+    for collision in vm.collision_log() {
+        writeln!(
+            out,
+            "{} {:04X} {} {} {}",
+            collision.cycle, collision.pc, collision.x, collision.y, collision.pixels
+        )?;
+    }
+    out.flush()
+}
 
-    let source_freq = 44100;
-    let source_spec = AudioSpec {
-        freq: Some(source_freq),
-        channels: Some(1),                    // mono
-        format: Some(AudioFormat::f32_sys()), // floating 32 bit samples
-    };
+// the command line's say on how the VM behaves, gathered in one place
+fn vm_config(config: &Config) -> VmConfig {
+    let mut vm_config = VmConfig::new(rand::random::<u8>)
+        .start_address(config.load_addr)
+        .strict_sys(config.strict_sys)
+        .strict_jumps(config.strict_jumps)
+        .halt_on_spin(config.halt_on_spin)
+        .track_hot_spots(config.profile || config.profile_json.is_some())
+        .protect_low_memory(config.low_memory)
+        .track_executed(config.log_smc)
+        .strict_smc(config.strict_smc)
+        .log_collisions(match config.log_collisions {
+            Some(_) => COLLISION_LOG_LEN,
+            None => 0,
+        });
+    if let Some(profile) = config.quirk_preset {
+        vm_config = vm_config.profile(profile);
+    }
+    for &(quirk, on) in &config.quirks {
+        vm_config = vm_config.quirk(quirk, on);
+    }
+    // the debugger steps back by running forward again from a snapshot, so
+    // CXNN has to roll the same numbers the second time
+    if config.debug {
+        vm_config = vm_config.rng_seed(rand::random());
+    }
+    if let Some(seed) = config.seed {
+        vm_config = vm_config.rng_seed(seed);
+    }
+    if let Some(byte) = config.fixed_rand {
+        vm_config = vm_config.rng_fixed(byte);
+    }
+    vm_config
+}
 
-    // Initialize the audio callback
-    let device = audio_subsystem
-        .open_playback_stream(
-            &source_spec,
-            SquareWave {
-                phase_inc: 440.0 / source_freq as f32,
-                phase: 0.0,
-                volume: 0.25,
-            },
-        )
-        .unwrap();
+// --trace, once the VM has stopped
+fn finish_trace(trace: &RefCell<Trace>, fault: Option<&Chip8Error>) {
+    let mut trace = trace.borrow_mut();
+    match trace.finish(fault) {
+        Ok(0) => {}
+        Ok(lines) => eprintln!(
+            "Last {} instructions written to '{}'.",
+            lines,
+            trace.path().display()
+        ),
+        Err(e) => eprintln!("Failed to write trace: {}", e),
+    }
+}
 
-    // timings
-    let cpu_hz = 600.0;
-    let cpu_dt = Duration::from_secs_f64(1.0 / cpu_hz);
-    let timer_dt = Duration::from_secs_f64(1.0 / 60.0);
+// --warn-sys
+struct SysWarner;
 
-    let mut last = Instant::now();
-    let mut cpu_acc = Duration::ZERO;
-    let mut timer_acc = Duration::ZERO;
-    let mut frame_acc = Duration::ZERO;
+impl VmObserver for SysWarner {
+    fn on_sys(&mut self, addr: u16, pc: u16) {
+        eprintln!("Warning: ignored SYS {:03X} at {:04X}", addr, pc);
+    }
+}
 
-    'running: loop {
-        let now = Instant::now();
-        let dt = now - last;
-        last = now;
+// --protect-low-memory warn, once per offending instruction
+#[derive(Default)]
+struct LowMemoryWarner(HashSet<u16>);
 
-        cpu_acc += dt;
-        timer_acc += dt;
-        frame_acc += dt;
+impl VmObserver for LowMemoryWarner {
+    fn on_low_memory_write(&mut self, addr: u16, pc: u16) {
+        if self.0.insert(pc) {
+            eprintln!(
+                "Warning: the instruction at {:04X} writes below 0x200, at {:04X}; the font \
+                 may get corrupted",
+                pc, addr
+            );
+        }
+    }
+}
+
+// --log-smc
+struct SmcLogger;
+
+impl VmObserver for SmcLogger {
+    fn on_code_write(&mut self, write: &CodeWrite) {
+        eprintln!(
+            "Self-modifying code: {:#06X} written {:02X} -> {:02X} by {}\n  was {}\n  now {}",
+            write.addr,
+            write.old,
+            write.new,
+            disassemble_at(write.pc, write.opcode),
+            disassemble_at(write.instruction, write.before),
+            disassemble_at(write.instruction, write.after)
+        );
+    }
+}
 
-        // run as many CPU cycles as needed
-        while cpu_acc >= cpu_dt {
-            vm.step();
-            cpu_acc -= cpu_dt;
+// Set by SIGTERM, and by Ctrl+C unless the debugger pauses on it instead
+static QUIT: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+
+// Turns those signals into a quit like Esc's, so the run ends normally and
+// traces, input logs and reports still get written
+fn catch_quit_signals(debug: bool) {
+    let mut signals = vec![signal_hook::consts::SIGTERM];
+    if !debug {
+        signals.push(signal_hook::consts::SIGINT);
+    }
+    for signal in signals {
+        if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&QUIT)) {
+            eprintln!("Warning: cannot catch signal {}: {}", signal, e);
         }
+    }
+}
+
+// Quits once QUIT is set
+struct Quittable<'a> {
+    inner: &'a mut dyn Frontend,
+}
+
+impl DisplaySink for Quittable<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
 
-        // timers at 60Hz
-        while timer_acc >= timer_dt {
-            vm.step_timers();
-            timer_acc -= timer_dt;
+impl AudioSink for Quittable<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for Quittable<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        if QUIT.load(Ordering::Relaxed) {
+            return Control::Quit;
         }
+        self.inner.poll(vm)
+    }
+}
+
+const BENCHMARK_DURATION: Duration = Duration::from_secs(5);
+
+fn benchmark(vm: &mut VM) -> Result<(), Chip8Error> {
+    // checking the clock every instruction would dominate the measurement.
+    // Not run_frame: a key wait, a jump to itself or a draw with display-wait
+    // would end every batch after an instruction or two
+    const BATCH: u64 = 100_000;
+    let mut cycles: u64 = 0;
+    let start = Instant::now();
+    while start.elapsed() < BENCHMARK_DURATION && !QUIT.load(Ordering::Relaxed) {
+        vm.run_cycles(BATCH)?;
+        cycles += BATCH;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let per_second = cycles as f64 / elapsed;
+    println!("Ran {} instructions in {:.2}s", cycles, elapsed);
+    println!(
+        "{:.0} instructions/s ({:.2} MHz effective), {:.0}x the configured {} Hz",
+        per_second,
+        per_second / 1_000_000.0,
+        per_second / vm.cpu_hz(),
+        vm.cpu_hz()
+    );
+    Ok(())
+}
 
-        // render at 60Hz
-        while frame_acc >= timer_dt {
-            for i in 0..(FB_WIDTH * FB_HEIGHT) as usize {
-                prev_framebuffer[i] = ((prev_framebuffer[i] as f32) * 0.7).round() as u8;
-                prev_framebuffer[i] = prev_framebuffer[i].saturating_add(vm.framebuffer[i]);
-                let v = prev_framebuffer[i] as u16;
-                let r = ((255 * (255 - v) + 60 * v) / 255) as u8;
-                let g = ((176 * (255 - v) + 57 * v) / 255) as u8;
-                let b = ((0 * (255 - v) + 60 * v) / 255) as u8;
-                canvas.set_draw_color(Color::RGB(r, g, b));
-                let _ = canvas.draw_point(sdl3::render::FPoint {
-                    x: (i % FB_WIDTH as usize) as f32,
-                    y: (i / FB_WIDTH as usize) as f32,
-                });
+// full speed, --slowmo or the --debug prompt, whatever sits in front of the VM
+fn drive(
+    config: &Config,
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    hot_loops: Option<&HotLoops>,
+) -> Result<(), Chip8Error> {
+    let frontend = &mut Quittable { inner: frontend };
+    if let Some(addr) = config.gdb {
+        let mut stub = match GdbStub::bind(addr, vm) {
+            Ok(stub) => stub,
+            Err(e) => {
+                eprintln!("Error: cannot listen for gdb on {}: {}", addr, e);
+                process::exit(1);
             }
-            canvas.present();
-            frame_acc -= timer_dt;
+        };
+        if config.gdb_wait
+            && let Err(e) = stub.wait_for_gdb()
+        {
+            eprintln!("Error: waiting for gdb: {}", e);
+            process::exit(1);
         }
+        return stub.run(vm, frontend);
+    }
+    if config.debug {
+        let breakpoints = config.breakpoints.clone();
+        let hot_loops = hot_loops.map(|hot_loops| hot_loops.0.clone());
+        let cheats = config.cheats.clone();
+        let mut debugger = match Debugger::new(
+            vm,
+            breakpoints,
+            config.start_paused,
+            hot_loops,
+            cheats,
+            config.symbols.clone(),
+        ) {
+            Ok(debugger) => debugger,
+            Err(e) => {
+                eprintln!("Error: cannot set up the debugger: {}", e);
+                process::exit(1);
+            }
+        };
+        return debugger.run(vm, frontend);
+    }
+    if let Some(interval) = config.slowmo {
+        return run_slowmo(vm, frontend, interval);
+    }
+    let frontend = &mut Rewindable::new(frontend, vm);
+    let throttle = config
+        .auto_throttle
+        .map(|(min, max)| AutoThrottle::new(min, max));
+    match throttle {
+        _ if config.deterministic => run_lockstep(vm, frontend, throttle),
+        Some(throttle) => run_auto_throttle(vm, frontend, throttle),
+        None => run(vm, frontend),
+    }
+}
+
+// `drive`, with the keypad recorded to or replayed from a log if asked, a
+// script played against it, the frontend timed for --profile-host and the
+// frames hashed for --record-frames and --check-frames
+fn drive_logged(
+    config: &Config,
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    host: Option<&RefCell<HostProfile>>,
+    frames: Option<&RefCell<Vec<u64>>>,
+    hot_loops: Option<&HotLoops>,
+) -> Result<(), Chip8Error> {
+    if let Some(host) = host {
+        let mut timed = TimedFrontend::new(frontend, host);
+        return drive_logged(config, vm, &mut timed, None, frames, hot_loops);
+    }
+    if let Some(frames) = frames {
+        let mut hashed = HashedFrontend::new(frontend, frames);
+        return drive_logged(config, vm, &mut hashed, None, None, hot_loops);
+    }
+    // inside the recorder, so the log has the script's key presses too
+    if let Some(script) = &config.script {
+        let mut scripted = Scripted::new(frontend, script.clone(), config.palette);
+        return drive_recorded(config, vm, &mut scripted, hot_loops);
+    }
+    drive_recorded(config, vm, frontend, hot_loops)
+}
+
+fn drive_recorded(
+    config: &Config,
+    vm: &mut VM,
+    frontend: &mut dyn Frontend,
+    hot_loops: Option<&HotLoops>,
+) -> Result<(), Chip8Error> {
+    if let Some(path) = &config.record_input {
+        let mut recorder = InputRecorder::new(frontend, log_header(config, vm));
+        let result = drive(config, vm, &mut recorder, hot_loops);
+        // saved even when the VM faulted, that's the run worth reproducing
+        if let Err(e) = recorder.save(path, vm) {
+            eprintln!("Failed to write input log: {}", e);
+        }
+        result
+    } else if let Some((_, log)) = &config.replay_input {
+        let mut replayer = InputReplayer::new(frontend, log.clone(), config.exit_after_end);
+        drive(config, vm, &mut replayer, hot_loops)
+    } else {
+        drive(config, vm, frontend, hot_loops)
+    }
+}
 
-        if vm.sound_timer > 0 {
-            if device.queued_bytes() == Ok(0) {
-                device.resume().expect("Failed to start playback");
+// What a run depends on besides the keys, noted by --record-input and
+// checked by --play-input
+fn log_header(config: &Config, vm: &VM) -> LogHeader {
+    LogHeader {
+        rom: Some(sha1::hex(&sha1::sha1(vm.rom()))),
+        quirks: Some(preset_name(&vm.quirks).unwrap_or("custom").to_string()),
+        ipf: Some(match config.auto_throttle {
+            Some((min, max)) => format!("{}:{}", min, max),
+            None => instructions_per_frame(vm).to_string(),
+        }),
+        seed: config.seed,
+        fixed_rand: config.fixed_rand,
+    }
+}
+
+// What's printed and written when the VM faults
+fn report_fault(config: &Config, vm: &VM, fault: &Chip8Error, history: Option<&History>) {
+    // --halt-on-spin: not a fault at all
+    if let Chip8Error::Halted { pc } = fault {
+        eprintln!("Program finished at {:#05X}.", pc);
+        return;
+    }
+    eprintln!("Error: {}", fault);
+    match postmortem::write_report(vm, fault, history, &config.symbols) {
+        Ok(path) => eprintln!("Post-mortem written to '{}'.", path.display()),
+        Err(e) => eprintln!("Failed to write post-mortem: {}", e),
+    }
+    if let Some(path) = &config.dump_on_error {
+        match vm.dump_memory(path) {
+            Ok(()) => eprintln!("Memory dumped to '{}'.", path.display()),
+            Err(e) => eprintln!("Failed to dump memory: {}", e),
+        }
+    }
+}
+
+// Leaves the last frame up after a fault until the window is closed
+#[cfg(feature = "sdl")]
+fn hold_screen(vm: &mut VM, frontend: &mut dyn Frontend) {
+    frontend.set_beep(false);
+    while !matches!(frontend.poll(vm), Control::Quit) {
+        frontend.present(vm);
+        thread::sleep(Duration::from_millis(16));
+    }
+}
+
+// Opens the frontend picked on the command line and runs the VM until it
+// quits, reporting a fault while the frontend is still up
+fn run_frontend(
+    config: &Config,
+    vm: &mut VM,
+    playlist: Playlist,
+    history: Option<&History>,
+    host: Option<&RefCell<HostProfile>>,
+    frames: Option<&RefCell<Vec<u64>>>,
+    hot_loops: Option<&HotLoops>,
+) -> Result<(), Chip8Error> {
+    let roms = RomNames::new(&playlist);
+    #[cfg(feature = "tui")]
+    if config.tui {
+        let mut frontend = match TuiFrontend::new(playlist, config.palette) {
+            Ok(frontend) => frontend,
+            Err(e) => {
+                eprintln!("Error: cannot set up the terminal: {}", e);
+                process::exit(1);
             }
-        } else {
-            let _ = device.pause();
+        };
+        let states = &mut SaveStates::new(&mut frontend, &roms);
+        let cheats = &mut CheatKey::new(states, &config.cheats);
+        let result = drive_logged(config, vm, cheats, host, frames, hot_loops);
+        // restores the terminal before the report gets printed
+        drop(frontend);
+        if let Err(e) = &result {
+            report_fault(config, vm, e, history);
         }
+        return result;
+    }
 
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                _ => {}
+    #[cfg(feature = "sdl")]
+    {
+        let mut frontend = match SdlFrontend::new(
+            config.min_beep,
+            config.record_audio.as_deref(),
+            playlist,
+            vm,
+            config.palette,
+            config.pixel_aspect,
+        ) {
+            Ok(frontend) => frontend,
+            Err(msg) => {
+                eprintln!("Error: {}", msg);
+                process::exit(1);
             }
+        };
+        if config.slowmo.is_some() {
+            frontend.set_trace(true);
         }
-        vm.keyboard = [false; 16];
-        let keys = event_pump.keyboard_state();
-        if keys.is_scancode_pressed(Scancode::_1) {
-            vm.keyboard[1] = true;
+        frontend.set_memmap(config.memmap);
+        frontend.set_stack_overlay(config.stack_overlay);
+        frontend.set_interlace(config.interlace);
+        frontend.set_latch_taps(config.latch_taps);
+        let states = &mut SaveStates::new(&mut frontend, &roms);
+        let cheats = &mut CheatKey::new(states, &config.cheats);
+        let result = drive_logged(config, vm, cheats, host, frames, hot_loops);
+        if let Err(e) = &result {
+            report_fault(config, vm, e, history);
+            eprintln!("Close the window or press Esc to exit.");
+            hold_screen(vm, &mut frontend);
         }
-        if keys.is_scancode_pressed(Scancode::_2) {
-            vm.keyboard[2] = true;
+        frontend.finish();
+        result
+    }
+
+    // without `sdl`, parse_args always turns on --tui unless the debugger is
+    // on, and the debugger needs no frontend
+    #[cfg(not(feature = "sdl"))]
+    {
+        debug_assert!(config.debug);
+        let result = drive_logged(config, vm, &mut NullFrontend, host, frames, hot_loops);
+        if let Err(e) = &result {
+            report_fault(config, vm, e, history);
         }
-        if keys.is_scancode_pressed(Scancode::_3) {
-            vm.keyboard[3] = true;
+        result
+    }
+}
+
+// file name shown in the window title
+fn rom_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.display().to_string(),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config = match parse_args(&args) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Version) => {
+            print_version();
+            return;
         }
-        if keys.is_scancode_pressed(Scancode::Q) {
-            vm.keyboard[4] = true;
+        Ok(Command::ListOpcodes) => {
+            print_opcodes();
+            return;
         }
-        if keys.is_scancode_pressed(Scancode::W) {
-            vm.keyboard[5] = true;
+        Ok(Command::ShowQuirks) => {
+            print_quirks();
+            return;
         }
-        if keys.is_scancode_pressed(Scancode::E) {
-            vm.keyboard[6] = true;
+        Ok(Command::Assemble(input, output)) => {
+            assemble_file(&input, &output);
+            return;
         }
-        if keys.is_scancode_pressed(Scancode::A) {
-            vm.keyboard[7] = true;
+        Ok(Command::Disassemble(rom, output, start, entry, symbols)) => {
+            disassemble_file(&rom, output.as_deref(), start, entry.as_deref(), &symbols);
+            return;
         }
-        if keys.is_scancode_pressed(Scancode::S) {
-            vm.keyboard[8] = true;
+        Ok(Command::DiffState(a, b)) => match statediff::diff_files(&a, &b) {
+            Ok(differs) => process::exit(differs as i32),
+            Err(msg) => {
+                eprintln!("Error: {}", msg);
+                process::exit(2);
+            }
+        },
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            usage(&args[0]);
         }
-        if keys.is_scancode_pressed(Scancode::D) {
-            vm.keyboard[9] = true;
+    };
+
+    // VM setup
+    let mut vm = match vm_config(&config).build() {
+        Ok(vm) => vm,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
         }
-        if keys.is_scancode_pressed(Scancode::Z) {
-            vm.keyboard[0xA] = true;
+    };
+    let playlist = Playlist::new(
+        config
+            .rom_paths
+            .iter()
+            .map(|path| (rom_name(path), read_rom(path, config.entry.as_deref())))
+            .collect(),
+    );
+    if let Err(e) = vm.load_rom(playlist.rom()) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &config.state {
+        match savestate::load(path, &vm) {
+            Ok(state) => vm.replace_state(state),
+            Err(msg) => {
+                eprintln!("Error: {}", msg);
+                process::exit(1);
+            }
         }
-        if keys.is_scancode_pressed(Scancode::X) {
-            vm.keyboard[0x0] = true;
+    }
+    if let Some((path, log)) = &config.replay_input
+        && let Some(mismatch) = log.header.mismatch(&log_header(&config, &vm))
+    {
+        eprintln!(
+            "Error: '{}' was recorded with {}.",
+            path.display(),
+            mismatch
+        );
+        process::exit(1);
+    }
+    if config.show_quirks {
+        print_active_config(&config, &vm);
+    }
+    let trace = config
+        .trace
+        .as_ref()
+        .map(|options| match Trace::create(options) {
+            Ok(trace) => Rc::new(RefCell::new(trace)),
+            Err(e) => {
+                eprintln!("Error: cannot create '{}': {}", options.path.display(), e);
+                process::exit(1);
+            }
+        });
+    let event_log = config.event_log.as_ref().map(|(path, categories)| {
+        match EventLog::create(path, categories.clone(), &vm) {
+            Ok(log) => Rc::new(RefCell::new(log)),
+            Err(e) => {
+                eprintln!("Error: cannot create '{}': {}", path.display(), e);
+                process::exit(1);
+            }
         }
-        if keys.is_scancode_pressed(Scancode::C) {
-            vm.keyboard[0xB] = true;
+    });
+    // not kept for --benchmark, which measures the VM on its own
+    let history = (!config.benchmark).then(History::new);
+    let mut observers: Vec<Box<dyn VmObserver>> = Vec::new();
+    if config.warn_sys {
+        observers.push(Box::new(SysWarner));
+    }
+    if config.low_memory == LowMemory::Warn {
+        observers.push(Box::new(LowMemoryWarner::default()));
+    }
+    if config.log_smc {
+        observers.push(Box::new(SmcLogger));
+    }
+    if let Some(trace) = &trace {
+        observers.push(Box::new(Tracer(trace.clone())));
+    }
+    if let Some(log) = &event_log {
+        observers.push(Box::new(EventLogger(log.clone())));
+    }
+    if let Some(history) = &history {
+        observers.push(Box::new(History(history.0.clone())));
+    }
+    let host = config.profile_host.then(|| {
+        eprintln!(
+            "Warning: --profile-host slows the emulator down to time it, so its timings are \
+             only good for comparing."
+        );
+        HostProfile::new()
+    });
+    if let Some(host) = &host {
+        observers.push(Box::new(HostSampler(host.clone())));
+    }
+    let frames = config
+        .frame_check
+        .as_ref()
+        .map(|_| RefCell::new(Vec::new()));
+    // the debugger announces them as they're found
+    let hot_loops = (config.report_hot_loops || config.debug).then(HotLoops::new);
+    if let Some(hot_loops) = &hot_loops {
+        observers.push(Box::new(HotLoops(hot_loops.0.clone())));
+    }
+    if let Some(observer) = observers
+        .into_iter()
+        .reduce(|first, second| Box::new((first, second)))
+    {
+        vm.set_observer(observer);
+    }
+    for &watchpoint in &config.watch {
+        vm.add_watchpoint(watchpoint);
+    }
+
+    catch_quit_signals(config.debug);
+    let result = if config.benchmark {
+        let result = benchmark(&mut vm);
+        if let Err(e) = &result {
+            report_fault(&config, &vm, e, None);
         }
-        if keys.is_scancode_pressed(Scancode::_4) {
-            vm.keyboard[0xC] = true;
+        result
+    } else if let Some((path, cycles)) = &config.conformance {
+        let result = match conformance::run(&mut vm, path, *cycles) {
+            Ok(None) => Ok(()),
+            Ok(Some(e)) => Err(e),
+            Err(e) => {
+                eprintln!("Error: cannot write '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        };
+        if let Err(e) = &result {
+            report_fault(&config, &vm, e, history.as_ref());
         }
-        if keys.is_scancode_pressed(Scancode::R) {
-            vm.keyboard[0xD] = true;
+        result
+    } else {
+        run_frontend(
+            &config,
+            &mut vm,
+            playlist,
+            history.as_ref(),
+            host.as_deref(),
+            frames.as_ref(),
+            hot_loops.as_ref(),
+        )
+    };
+    let frames_differ = match (&config.frame_check, &frames) {
+        (Some(FrameCheck::Record(path)), Some(frames)) => {
+            if let Err(e) = frames::save(path, &frames.borrow()) {
+                eprintln!("Failed to write '{}': {}", path.display(), e);
+            }
+            false
         }
-        if keys.is_scancode_pressed(Scancode::F) {
-            vm.keyboard[0xE] = true;
+        (Some(FrameCheck::Verify(path, golden)), Some(frames)) => {
+            match frames::mismatch(golden, &frames.borrow()) {
+                Some(mismatch) => {
+                    eprintln!("Error: '{}': {}.", path.display(), mismatch);
+                    true
+                }
+                None => false,
+            }
         }
-        if keys.is_scancode_pressed(Scancode::V) {
-            vm.keyboard[0xF] = true;
+        _ => false,
+    };
+    if let Some(path) = &config.screenshot
+        && let Err(e) = script::write_ppm(path, &vm, config.palette)
+    {
+        eprintln!("Failed to write '{}': {}", path.display(), e);
+    }
+    if let Some(path) = &config.log_collisions
+        && let Err(e) = write_collisions(path, &vm)
+    {
+        eprintln!("Failed to write '{}': {}", path.display(), e);
+    }
+    // reported already, and a normal way to stop
+    let result = match result {
+        Err(Chip8Error::Halted { .. }) => Ok(()),
+        result => result,
+    };
+    if config.profile {
+        profile::print(&vm, &config.symbols);
+    }
+    if let Some(host) = &host {
+        host.borrow().print();
+    }
+    if let Some(hot_loops) = &hot_loops
+        && config.report_hot_loops
+    {
+        hotloop::print_report(&vm, &mut hot_loops.0.borrow_mut());
+    }
+    if let Some(path) = &config.profile_json
+        && let Err(e) = profile::write_json(&vm, path)
+    {
+        eprintln!("Failed to write '{}': {}", path.display(), e);
+    }
+    if let Some(trace) = &trace {
+        finish_trace(trace, result.as_ref().err());
+    }
+    if let Some(log) = &event_log {
+        let mut log = log.borrow_mut();
+        if let Err(e) = log.finish() {
+            eprintln!("Failed to write '{}': {}", log.path().display(), e);
         }
     }
+    if result.is_err() || frames_differ {
+        process::exit(1);
+    }
 }
@@ -0,0 +1,204 @@
+use alloc::boxed::Box;
+
+use crate::error::Chip8Error;
+use crate::vm::StepResult;
+
+// Callbacks for tooling that wants to follow what the VM does without
+// patching `step`: debuggers, trace loggers, event logs. Every method defaults
+// to doing nothing, so an observer only implements what it needs. Install one
+// with `VM::set_observer`; without one the VM only pays for an Option check.
+pub trait VmObserver {
+    // a DXYN finished
+    fn on_draw(&mut self, _frame: &FrameInfo) {}
+    // the sound timer started (true) or stopped (false) running
+    fn on_sound_changed(&mut self, _on: bool) {}
+    // FX0A found no key down; called on every cycle spent waiting
+    fn on_key_wait(&mut self, _x: u8) {}
+    // FX0A found `key` down and stored it in Vx, waited for or not
+    fn on_key_taken(&mut self, _x: u8, _key: u8) {}
+    // a key went down (true) or up (false) since the last instruction;
+    // called before the next one runs
+    fn on_key_changed(&mut self, _key: u8, _pressed: bool) {}
+    // 2NNN, with the called address
+    fn on_call(&mut self, _addr: u16) {}
+    // 00EE, with the address returned to
+    fn on_ret(&mut self, _addr: u16) {}
+    // 0NNN other than CLS/RET and 0000, ignored since there's no machine code
+    // to run
+    fn on_sys(&mut self, _addr: u16, _pc: u16) {}
+    // a word that isn't an instruction, executed as a no-op
+    fn on_unknown_opcode(&mut self, _opcode: u16, _pc: u16) {}
+    // FX33 or FX55 at `pc` is about to write below ROM_START, starting at
+    // `addr`, with VmConfig::protect_low_memory(LowMemory::Warn)
+    fn on_low_memory_write(&mut self, _addr: u16, _pc: u16) {}
+    // FX33 or FX55 is about to change a byte of an instruction that already
+    // ran, with VmConfig::track_executed; once per byte changed
+    fn on_code_write(&mut self, _write: &CodeWrite) {}
+    // any instruction that ran without faulting, after the more specific
+    // callback above if it has one
+    fn on_step(&mut self, _step: &StepInfo) {}
+    // `step` failed with `fault`
+    fn on_fault(&mut self, _fault: &Chip8Error) {}
+    // the timers ticked; `frames` is `VM::frames` after the tick
+    fn on_frame(&mut self, _frames: u64) {}
+    // a reset or `VM::load_new_rom` put `rom` back in memory, and the cycle
+    // and frame counts started over
+    fn on_rom_loaded(&mut self, _rom: &[u8]) {}
+}
+
+// So boxed observers can be combined as below
+impl<T: VmObserver + ?Sized> VmObserver for Box<T> {
+    fn on_draw(&mut self, frame: &FrameInfo) {
+        (**self).on_draw(frame);
+    }
+    fn on_sound_changed(&mut self, on: bool) {
+        (**self).on_sound_changed(on);
+    }
+    fn on_key_wait(&mut self, x: u8) {
+        (**self).on_key_wait(x);
+    }
+    fn on_key_taken(&mut self, x: u8, key: u8) {
+        (**self).on_key_taken(x, key);
+    }
+    fn on_key_changed(&mut self, key: u8, pressed: bool) {
+        (**self).on_key_changed(key, pressed);
+    }
+    fn on_call(&mut self, addr: u16) {
+        (**self).on_call(addr);
+    }
+    fn on_ret(&mut self, addr: u16) {
+        (**self).on_ret(addr);
+    }
+    fn on_sys(&mut self, addr: u16, pc: u16) {
+        (**self).on_sys(addr, pc);
+    }
+    fn on_unknown_opcode(&mut self, opcode: u16, pc: u16) {
+        (**self).on_unknown_opcode(opcode, pc);
+    }
+    fn on_low_memory_write(&mut self, addr: u16, pc: u16) {
+        (**self).on_low_memory_write(addr, pc);
+    }
+    fn on_code_write(&mut self, write: &CodeWrite) {
+        (**self).on_code_write(write);
+    }
+    fn on_step(&mut self, step: &StepInfo) {
+        (**self).on_step(step);
+    }
+    fn on_fault(&mut self, fault: &Chip8Error) {
+        (**self).on_fault(fault);
+    }
+    fn on_frame(&mut self, frames: u64) {
+        (**self).on_frame(frames);
+    }
+    fn on_rom_loaded(&mut self, rom: &[u8]) {
+        (**self).on_rom_loaded(rom);
+    }
+}
+
+// Both observers see every event, the first one first
+impl<A: VmObserver, B: VmObserver> VmObserver for (A, B) {
+    fn on_draw(&mut self, frame: &FrameInfo) {
+        self.0.on_draw(frame);
+        self.1.on_draw(frame);
+    }
+    fn on_sound_changed(&mut self, on: bool) {
+        self.0.on_sound_changed(on);
+        self.1.on_sound_changed(on);
+    }
+    fn on_key_wait(&mut self, x: u8) {
+        self.0.on_key_wait(x);
+        self.1.on_key_wait(x);
+    }
+    fn on_key_taken(&mut self, x: u8, key: u8) {
+        self.0.on_key_taken(x, key);
+        self.1.on_key_taken(x, key);
+    }
+    fn on_key_changed(&mut self, key: u8, pressed: bool) {
+        self.0.on_key_changed(key, pressed);
+        self.1.on_key_changed(key, pressed);
+    }
+    fn on_call(&mut self, addr: u16) {
+        self.0.on_call(addr);
+        self.1.on_call(addr);
+    }
+    fn on_ret(&mut self, addr: u16) {
+        self.0.on_ret(addr);
+        self.1.on_ret(addr);
+    }
+    fn on_sys(&mut self, addr: u16, pc: u16) {
+        self.0.on_sys(addr, pc);
+        self.1.on_sys(addr, pc);
+    }
+    fn on_unknown_opcode(&mut self, opcode: u16, pc: u16) {
+        self.0.on_unknown_opcode(opcode, pc);
+        self.1.on_unknown_opcode(opcode, pc);
+    }
+    fn on_low_memory_write(&mut self, addr: u16, pc: u16) {
+        self.0.on_low_memory_write(addr, pc);
+        self.1.on_low_memory_write(addr, pc);
+    }
+    fn on_code_write(&mut self, write: &CodeWrite) {
+        self.0.on_code_write(write);
+        self.1.on_code_write(write);
+    }
+    fn on_step(&mut self, step: &StepInfo) {
+        self.0.on_step(step);
+        self.1.on_step(step);
+    }
+    fn on_fault(&mut self, fault: &Chip8Error) {
+        self.0.on_fault(fault);
+        self.1.on_fault(fault);
+    }
+    fn on_frame(&mut self, frames: u64) {
+        self.0.on_frame(frames);
+        self.1.on_frame(frames);
+    }
+    fn on_rom_loaded(&mut self, rom: &[u8]) {
+        self.0.on_rom_loaded(rom);
+        self.1.on_rom_loaded(rom);
+    }
+}
+
+// One sprite draw, as passed to `VmObserver::on_draw`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub pc: u16,
+    // screen position of the sprite's top left pixel
+    pub x: u8,
+    pub y: u8,
+    // sprite rows, N of DXYN
+    pub height: u8,
+    // address the sprite was read from
+    pub sprite: u16,
+    // pixels turned off; VF is set when this isn't zero
+    pub collisions: u32,
+}
+
+// A write over code that already ran, as passed to
+// `VmObserver::on_code_write`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeWrite {
+    // the writing instruction
+    pub pc: u16,
+    pub opcode: u16,
+    // the byte written, what it held and what it's about to hold
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+    // where the instruction overwritten starts, addr or the byte before,
+    // and its opcode before and after the write
+    pub instruction: u16,
+    pub before: u16,
+    pub after: u16,
+}
+
+// One executed instruction and the registers it left behind, as passed to
+// `VmObserver::on_step`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepInfo {
+    // `VM::cycles` counting this instruction
+    pub cycle: u64,
+    pub step: StepResult,
+    pub v: [u8; 16],
+    pub i: u16,
+}
@@ -0,0 +1,75 @@
+// Every opcode family the interpreter implements, printed by `--list-opcodes`.
+// Patterns use X/Y for register nibbles and N for immediates; `platform` is the
+// mode an opcode is gated behind (SCHIP/XO-CHIP extensions get their own).
+// tests/opcodes.rs holds the table to `Instruction::decode` and the
+// disassembler's mnemonics, so an instruction added to one without the other
+// fails the tests.
+pub struct OpcodeInfo {
+    pub pattern: &'static str,
+    pub mnemonic: &'static str,
+    pub platform: &'static str,
+}
+
+impl OpcodeInfo {
+    const fn chip8(pattern: &'static str, mnemonic: &'static str) -> Self {
+        OpcodeInfo {
+            pattern,
+            mnemonic,
+            platform: "CHIP-8",
+        }
+    }
+
+    // hex digits in the pattern must match, X/Y/N accept any nibble
+    pub fn matches(&self, opcode: u16) -> bool {
+        self.pattern.chars().enumerate().all(|(idx, c)| {
+            let nibble = (opcode >> (12 - 4 * idx)) & 0xF;
+            match c.to_digit(16) {
+                Some(digit) => nibble == digit as u16,
+                None => true,
+            }
+        })
+    }
+}
+
+// Table entry describing `opcode`, if it is one the interpreter knows
+pub fn lookup(opcode: u16) -> Option<&'static OpcodeInfo> {
+    OPCODES.iter().find(|op| op.matches(opcode))
+}
+
+pub const OPCODES: &[OpcodeInfo] = &[
+    OpcodeInfo::chip8("00E0", "CLS"),
+    OpcodeInfo::chip8("00EE", "RET"),
+    OpcodeInfo::chip8("0NNN", "SYS NNN (ignored)"),
+    OpcodeInfo::chip8("1NNN", "JP NNN"),
+    OpcodeInfo::chip8("2NNN", "CALL NNN"),
+    OpcodeInfo::chip8("3XNN", "SE Vx, NN"),
+    OpcodeInfo::chip8("4XNN", "SNE Vx, NN"),
+    OpcodeInfo::chip8("5XY0", "SE Vx, Vy"),
+    OpcodeInfo::chip8("6XNN", "LD Vx, NN"),
+    OpcodeInfo::chip8("7XNN", "ADD Vx, NN"),
+    OpcodeInfo::chip8("8XY0", "LD Vx, Vy"),
+    OpcodeInfo::chip8("8XY1", "OR Vx, Vy"),
+    OpcodeInfo::chip8("8XY2", "AND Vx, Vy"),
+    OpcodeInfo::chip8("8XY3", "XOR Vx, Vy"),
+    OpcodeInfo::chip8("8XY4", "ADD Vx, Vy"),
+    OpcodeInfo::chip8("8XY5", "SUB Vx, Vy"),
+    OpcodeInfo::chip8("8XY6", "SHR Vx {, Vy}"),
+    OpcodeInfo::chip8("8XY7", "SUBN Vx, Vy"),
+    OpcodeInfo::chip8("8XYE", "SHL Vx {, Vy}"),
+    OpcodeInfo::chip8("9XY0", "SNE Vx, Vy"),
+    OpcodeInfo::chip8("ANNN", "LD I, NNN"),
+    OpcodeInfo::chip8("BNNN", "JP V0, NNN"),
+    OpcodeInfo::chip8("CXNN", "RND Vx, NN"),
+    OpcodeInfo::chip8("DXYN", "DRW Vx, Vy, N"),
+    OpcodeInfo::chip8("EX9E", "SKP Vx"),
+    OpcodeInfo::chip8("EXA1", "SKNP Vx"),
+    OpcodeInfo::chip8("FX07", "LD Vx, DT"),
+    OpcodeInfo::chip8("FX0A", "LD Vx, K"),
+    OpcodeInfo::chip8("FX15", "LD DT, Vx"),
+    OpcodeInfo::chip8("FX18", "LD ST, Vx"),
+    OpcodeInfo::chip8("FX1E", "ADD I, Vx"),
+    OpcodeInfo::chip8("FX29", "LD F, Vx"),
+    OpcodeInfo::chip8("FX33", "LD B, Vx"),
+    OpcodeInfo::chip8("FX55", "LD [I], Vx"),
+    OpcodeInfo::chip8("FX65", "LD Vx, [I]"),
+];
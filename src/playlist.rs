@@ -0,0 +1,33 @@
+// ROMs given on the command line, cycled through with a hotkey
+pub struct Playlist {
+    entries: Vec<(String, Vec<u8>)>,
+    current: usize,
+}
+
+impl Playlist {
+    pub fn new(entries: Vec<(String, Vec<u8>)>) -> Self {
+        assert!(!entries.is_empty(), "playlist needs at least one ROM");
+        Playlist {
+            entries,
+            current: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.entries[self.current].0
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.entries[self.current].1
+    }
+
+    // every ROM with its name, in order
+    pub fn entries(&self) -> &[(String, Vec<u8>)] {
+        &self.entries
+    }
+
+    // moves to the next ROM, wrapping around at the end of the list
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.entries.len();
+    }
+}
@@ -0,0 +1,174 @@
+use chip8_emu_rs::{Chip8Error, Instruction, StepInfo, Symbols, VM, VmObserver};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::trace;
+
+// instructions kept for the report
+const HISTORY: usize = 32;
+// bytes shown before and after PC and I
+const CONTEXT: usize = 32;
+
+// The last instructions executed, for the report
+pub struct History(pub Rc<RefCell<VecDeque<StepInfo>>>);
+
+impl History {
+    pub fn new() -> Self {
+        History(Rc::new(RefCell::new(VecDeque::with_capacity(HISTORY))))
+    }
+}
+
+impl VmObserver for History {
+    fn on_step(&mut self, info: &StepInfo) {
+        let mut steps = self.0.borrow_mut();
+        if steps.len() == HISTORY {
+            steps.pop_front();
+        }
+        steps.push_back(*info);
+    }
+}
+
+// Writes what the VM looked like when `fault` stopped it to
+// chip8-<UTC date>-<time>.dump in the working directory: registers, the
+// stack with the calls behind it, the last instructions if `history` was
+// installed, and memory around PC and I. Returns the file's name.
+pub fn write_report(
+    vm: &VM,
+    fault: &Chip8Error,
+    history: Option<&History>,
+    symbols: &Symbols,
+) -> io::Result<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = PathBuf::from(format!("chip8-{}.dump", timestamp(now, "", "-", "")));
+    let mut out = BufWriter::new(File::create(&path)?);
+
+    writeln!(
+        out,
+        "{} {} post-mortem, {} UTC",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        timestamp(now, "-", " ", ":")
+    )?;
+    writeln!(out, "Fault: {}", fault)?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "PC={:04X} I={:04X} SP={} DT={:02X} ST={:02X} cycles={}",
+        vm.pc,
+        vm.i,
+        vm.stack().len(),
+        vm.delay_timer,
+        vm.sound_timer,
+        vm.cycles()
+    )?;
+    for (idx, v) in vm.v.iter().enumerate() {
+        write!(out, "V{:X}={:02X} ", idx, v)?;
+    }
+    writeln!(out)?;
+
+    writeln!(out)?;
+    writeln!(out, "Stack, innermost last:")?;
+    if vm.stack().is_empty() {
+        writeln!(out, "  (empty)")?;
+    }
+    for &ret in vm.stack() {
+        writeln!(out, "  {}", describe_frame(vm, ret, symbols))?;
+    }
+
+    if let Some(history) = history {
+        let steps = history.0.borrow();
+        writeln!(out)?;
+        writeln!(out, "Last {} instructions:", steps.len())?;
+        for info in steps.iter() {
+            trace::write_line(&mut out, info)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Memory around PC:")?;
+    hexdump(&mut out, vm, vm.pc as usize)?;
+    writeln!(out)?;
+    writeln!(out, "Memory around I:")?;
+    hexdump(&mut out, vm, vm.i as usize)?;
+    out.flush()?;
+    Ok(path)
+}
+
+// A return address with the CALL that pushed it, which sits just before
+// the address returned to, flagged if it points outside the ROM or at
+// something that isn't a CALL: signs of the stack being overwritten
+pub fn describe_frame(vm: &VM, ret: u16, symbols: &Symbols) -> String {
+    let call = ret.wrapping_sub(2);
+    let mut frame = format!("{:04X}", ret);
+    if let Some(label) = symbols.describe(call) {
+        frame.push_str(&format!(" in {}", label));
+    }
+    if let Some(word) = word_at(vm, call) {
+        frame.push_str(&format!("  from {}", symbols.disassemble_at(call, word)));
+    }
+    if !vm.rom_range().contains(&(ret as usize)) {
+        frame.push_str("  <- outside the ROM, stack corrupted?");
+    } else if !word_at(vm, call)
+        .is_some_and(|word| matches!(Instruction::decode(word), Instruction::Call(_)))
+    {
+        frame.push_str("  <- not after a CALL, stack corrupted?");
+    }
+    frame
+}
+
+fn word_at(vm: &VM, addr: u16) -> Option<u16> {
+    let addr = addr as usize;
+    Some(u16::from_be_bytes([
+        *vm.memory.get(addr)?,
+        *vm.memory.get(addr + 1)?,
+    ]))
+}
+
+// whole 16-byte rows from CONTEXT bytes before `addr` to CONTEXT after it
+fn hexdump(out: &mut impl Write, vm: &VM, addr: usize) -> io::Result<()> {
+    let start = addr.saturating_sub(CONTEXT) & !0xF;
+    let end = (addr + CONTEXT).min(vm.memory.len());
+    for row in (start..end).step_by(16) {
+        write!(out, "{:04X}:", row)?;
+        for byte in &vm.memory[row..(row + 16).min(vm.memory.len())] {
+            write!(out, " {:02X}", byte)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// `secs` since the epoch as a UTC date and time, e.g. 2026-10-16 14:25:30
+// with the separators "-", " ", ":"
+fn timestamp(secs: u64, date_sep: &str, sep: &str, time_sep: &str) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // civil_from_days from Howard Hinnant's date algorithms
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}{ds}{:02}{ds}{:02}{}{:02}{ts}{:02}{ts}{:02}",
+        year,
+        month,
+        day,
+        sep,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        ds = date_sep,
+        ts = time_sep
+    )
+}
@@ -0,0 +1,235 @@
+use chip8_emu_rs::{
+    AudioSink, Control, DisplaySink, Family, Frontend, InputSource, StepInfo, Symbols, VM,
+    VmObserver, disassemble,
+};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Instant;
+
+// addresses in the --profile table
+const HOT_SPOTS: usize = 20;
+
+// --profile: the most executed addresses and how the instructions split
+// across families, for the ROM running at exit, and across the labels of
+// --symbols if there are any
+pub fn print(vm: &VM, symbols: &Symbols) {
+    let percent = |count: u64| 100.0 * count as f64 / vm.cycles() as f64;
+    println!("Most executed of {} instructions:", vm.cycles());
+    for (addr, count) in vm.hot_spots(HOT_SPOTS) {
+        println!(
+            "{:>12} {:>5.1}%  {}",
+            count,
+            percent(count),
+            symbols.disassemble_at(addr, word_at(vm, addr))
+        );
+    }
+    println!("By family:");
+    for (family, count) in vm.family_counts() {
+        println!("{:>12} {:>5.1}%  {}", count, percent(count), family.name());
+    }
+    if symbols.is_empty() {
+        return;
+    }
+    println!("By label:");
+    for (label, count) in label_counts(vm, symbols).into_iter().take(HOT_SPOTS) {
+        println!("{:>12} {:>5.1}%  {}", count, percent(count), label);
+    }
+}
+
+// Executions summed from each label to the next, most first; code before
+// the first label goes under "-"
+fn label_counts<'a>(vm: &VM, symbols: &'a Symbols) -> Vec<(&'a str, u64)> {
+    let mut counts: Vec<(&str, u64)> = Vec::new();
+    for (addr, count) in vm.hot_spots(usize::MAX) {
+        let label = symbols.locate(addr).map_or("-", |(name, _)| name);
+        match counts.iter_mut().find(|(known, _)| *known == label) {
+            Some((_, total)) => *total += count,
+            None => counts.push((label, count)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+}
+
+// --profile-json: the same for tools, with every executed address
+//
+//     {"cycles": N,
+//      "families": {"draw": N, ...},
+//      "addresses": [{"addr": N, "count": N, "instruction": "LD I, 0x22A"}, ...]}
+pub fn write_json(vm: &VM, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{{\"cycles\": {},", vm.cycles())?;
+    write!(out, " \"families\": {{")?;
+    for (idx, (family, count)) in vm.family_counts().into_iter().enumerate() {
+        let comma = if idx == 0 { "" } else { ", " };
+        write!(out, "{}\"{}\": {}", comma, family.name(), count)?;
+    }
+    writeln!(out, "}},")?;
+    write!(out, " \"addresses\": [")?;
+    for (idx, (addr, count)) in vm.hot_spots(usize::MAX).into_iter().enumerate() {
+        let comma = if idx == 0 { "" } else { ",\n  " };
+        // mnemonics never contain quotes or backslashes, so need no escaping
+        write!(
+            out,
+            "{}{{\"addr\": {}, \"count\": {}, \"instruction\": \"{}\"}}",
+            comma,
+            addr,
+            count,
+            disassemble(word_at(vm, addr))
+        )?;
+    }
+    writeln!(out, "]}}")?;
+    out.flush()
+}
+
+// the word at `addr`, zero past the end of memory
+fn word_at(vm: &VM, addr: u16) -> u16 {
+    let at = addr as usize;
+    let byte = |at: usize| vm.memory.get(at).copied().unwrap_or(0);
+    u16::from_be_bytes([byte(at), byte(at + 1)])
+}
+
+// --profile-host: where the emulator itself spends wall-clock time. The gap
+// between about every SAMPLE_EVERY-th instruction and the next one is charged
+// to the next one's family, and every present is timed whole. The gaps
+// between samples are random, since a fixed stride would keep landing on
+// the same few instructions of a tight loop. The clock reads,
+// the observer call and the bookkeeping all land in what's measured, so the
+// numbers are for comparing families and builds, not absolute costs.
+const SAMPLE_EVERY: u64 = 64;
+
+const FAMILIES: usize = Family::ALL.len();
+
+#[derive(Default)]
+pub struct HostProfile {
+    // per Family::ALL index
+    counts: [u64; FAMILIES],
+    samples: [u64; FAMILIES],
+    nanos: [u64; FAMILIES],
+    // cycle of the sampled instruction and when it finished; dropped at
+    // frame boundaries so presents and sleeps aren't charged to the next one
+    pending: Option<(u64, Instant)>,
+    next_sample: u64,
+    // xorshift64 state for the sample gaps
+    jitter: u64,
+    // what reading the clock twice costs on its own, taken off each sample
+    clock_nanos: u64,
+    presents: u64,
+    present_nanos: u64,
+}
+
+impl HostProfile {
+    pub fn new() -> Rc<RefCell<HostProfile>> {
+        let clock_nanos = (0..1000)
+            .map(|_| Instant::now().elapsed().as_nanos() as u64)
+            .min()
+            .unwrap_or(0);
+        Rc::new(RefCell::new(HostProfile {
+            jitter: 0x9E37_79B9_7F4A_7C15,
+            clock_nanos,
+            ..HostProfile::default()
+        }))
+    }
+
+    fn record(&mut self, info: &StepInfo) {
+        let family = info.step.instruction.family() as usize;
+        self.counts[family] += 1;
+        if let Some((cycle, start)) = self.pending.take()
+            && cycle + 1 == info.cycle
+        {
+            self.samples[family] += 1;
+            self.nanos[family] +=
+                (start.elapsed().as_nanos() as u64).saturating_sub(self.clock_nanos);
+        }
+        if info.cycle >= self.next_sample {
+            self.jitter ^= self.jitter << 13;
+            self.jitter ^= self.jitter >> 7;
+            self.jitter ^= self.jitter << 17;
+            self.next_sample = info.cycle + 1 + self.jitter % (2 * SAMPLE_EVERY - 1);
+            self.pending = Some((info.cycle, Instant::now()));
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Host time, perturbed by measuring it:");
+        println!(
+            "{:<12} {:>14} {:>10} {:>12} {:>12}",
+            "family", "instructions", "samples", "avg ns", "est. ms"
+        );
+        for family in Family::ALL {
+            let idx = family as usize;
+            if self.samples[idx] == 0 {
+                continue;
+            }
+            let average = self.nanos[idx] as f64 / self.samples[idx] as f64;
+            println!(
+                "{:<12} {:>14} {:>10} {:>12.1} {:>12.1}",
+                family.name(),
+                self.counts[idx],
+                self.samples[idx],
+                average,
+                average * self.counts[idx] as f64 / 1e6
+            );
+        }
+        if self.presents > 0 {
+            println!(
+                "render       {:>14} frames, {:.1} us avg, {:.1} ms total",
+                self.presents,
+                self.present_nanos as f64 / self.presents as f64 / 1e3,
+                self.present_nanos as f64 / 1e6
+            );
+        }
+    }
+}
+
+// The observer half of --profile-host
+pub struct HostSampler(pub Rc<RefCell<HostProfile>>);
+
+impl VmObserver for HostSampler {
+    fn on_step(&mut self, info: &StepInfo) {
+        self.0.borrow_mut().record(info);
+    }
+}
+
+// The frontend half: times each present and marks the frame boundary
+pub struct TimedFrontend<'a> {
+    inner: &'a mut dyn Frontend,
+    profile: &'a RefCell<HostProfile>,
+}
+
+impl<'a> TimedFrontend<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, profile: &'a RefCell<HostProfile>) -> Self {
+        TimedFrontend { inner, profile }
+    }
+}
+
+impl DisplaySink for TimedFrontend<'_> {
+    fn present(&mut self, vm: &VM) {
+        let start = Instant::now();
+        self.inner.present(vm);
+        let mut profile = self.profile.borrow_mut();
+        profile.presents += 1;
+        profile.present_nanos += start.elapsed().as_nanos() as u64;
+        profile.pending = None;
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for TimedFrontend<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for TimedFrontend<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        self.profile.borrow_mut().pending = None;
+        self.inner.poll(vm)
+    }
+}
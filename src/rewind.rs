@@ -0,0 +1,336 @@
+use chip8_emu_rs::{
+    AudioSink, Control, DisplaySink, Frontend, InputSource, Snapshot, TIMER_HZ, VM,
+};
+use std::collections::VecDeque;
+
+// instructions between snapshots
+const INTERVAL: u64 = 1000;
+// snapshots kept, so `rs` reaches back about this many times INTERVAL
+const SNAPSHOTS: usize = 64;
+
+// What happened to the VM between instructions, in the order it happened
+enum Event {
+    Keys([bool; 16]),
+    Tick,
+}
+
+// The debugger's history for `rs`: a snapshot every INTERVAL instructions
+// and the key changes and timer ticks since the oldest one. Going back n
+// instructions restores the last snapshot before the target and runs forward
+// again, feeding in the same events at the same cycles. That only repeats
+// the past if CXNN does, so the debugger seeds the generator.
+pub struct Rewind {
+    // each with the number of the first event after it
+    snapshots: VecDeque<(Snapshot, u64)>,
+    events: VecDeque<(u64, Event)>,
+    // events dropped from the front along with old snapshots
+    dropped: u64,
+    keys: [bool; 16],
+}
+
+impl Rewind {
+    pub fn new(vm: &VM) -> Self {
+        let mut rewind = Rewind {
+            snapshots: VecDeque::with_capacity(SNAPSHOTS),
+            events: VecDeque::new(),
+            dropped: 0,
+            keys: vm.keys(),
+        };
+        rewind.restart(vm);
+        rewind
+    }
+
+    // Forgets the history, for when the VM changed in a way replaying can't
+    // repeat: an edit at the prompt or a reset
+    pub fn restart(&mut self, vm: &VM) {
+        self.snapshots.clear();
+        self.dropped += self.events.len() as u64;
+        self.events.clear();
+        self.keys = vm.keys();
+        self.snapshots.push_back((vm.snapshot(), self.dropped));
+    }
+
+    // Between instructions: after polling the frontend, which may have
+    // changed the keys, and between the frames or steps of one command. A
+    // snapshot is taken here when one is due.
+    pub fn checkpoint(&mut self, vm: &VM) {
+        let newest = self
+            .snapshots
+            .back()
+            .map_or(0, |(snapshot, _)| snapshot.cycles());
+        if vm.cycles() < newest {
+            // the ROM was reset or switched
+            self.restart(vm);
+            return;
+        }
+        if vm.keys() != self.keys {
+            self.keys = vm.keys();
+            self.events.push_back((vm.cycles(), Event::Keys(vm.keys())));
+        }
+        if vm.cycles() >= newest + INTERVAL {
+            if self.snapshots.len() == SNAPSHOTS {
+                self.snapshots.pop_front();
+                let (_, first) = self.snapshots[0];
+                while self.dropped < first {
+                    self.events.pop_front();
+                    self.dropped += 1;
+                }
+            }
+            let next = self.dropped + self.events.len() as u64;
+            self.snapshots.push_back((vm.snapshot(), next));
+        }
+    }
+
+    // right after the VM's timers ticked
+    pub fn record_tick(&mut self, vm: &VM) {
+        self.events.push_back((vm.cycles(), Event::Tick));
+    }
+
+    // Puts the VM back `count` instructions, or says how far back it can go
+    pub fn step_back(&mut self, vm: &mut VM, count: u64) -> Result<(), String> {
+        let oldest = self
+            .snapshots
+            .front()
+            .map_or(0, |(snapshot, _)| snapshot.cycles());
+        let Some(target) = vm.cycles().checked_sub(count).filter(|&t| t >= oldest) else {
+            return Err(format!(
+                "Can only go back {} instruction(s) from here.",
+                vm.cycles() - oldest.min(vm.cycles())
+            ));
+        };
+        let (snapshot, first) = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|(snapshot, _)| snapshot.cycles() <= target)
+            .expect("the oldest snapshot is at or before the target");
+        // only the VM's state goes back, so tracing and the other observers
+        // don't see the replayed instructions twice
+        let observer = vm.take_observer();
+        vm.restore(snapshot);
+        let mut next = (*first - self.dropped) as usize;
+        let mut result = Ok(());
+        loop {
+            while let Some((cycle, event)) = self.events.get(next)
+                && *cycle <= vm.cycles()
+            {
+                match event {
+                    Event::Keys(keys) => vm.set_keys(*keys),
+                    Event::Tick => vm.step_timers(1),
+                }
+                next += 1;
+            }
+            if vm.cycles() == target {
+                break;
+            }
+            if let Err(e) = vm.step() {
+                result = Err(format!("Replaying failed at {:#06X}: {}", vm.pc, e));
+                break;
+            }
+        }
+        vm.take_watch_hit();
+        if let Some(observer) = observer {
+            vm.set_observer(observer);
+        }
+        // what comes after the target happens again as the program runs on
+        self.events.truncate(next);
+        self.keys = vm.keys();
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|(snapshot, _)| snapshot.cycles() > target)
+        {
+            self.snapshots.pop_back();
+        }
+        result
+    }
+}
+
+// snapshots for the rewind key: one every this many frames, as many as fit
+// in REWIND_BUDGET bytes up to about 10 seconds' worth
+const REWIND_EVERY: u32 = 2;
+const REWIND_SECONDS: usize = 10;
+const REWIND_BUDGET: usize = 4 << 20;
+
+// Wraps a frontend and keeps the last few seconds of play, so holding the
+// rewind key (Backspace) walks back through them instead of running. Each
+// poll while it's held puts the VM back one snapshot, REWIND_EVERY frames,
+// and play resumes from wherever it was let go. A snapshot copies all of
+// memory, so with 64K of it the ring covers less time.
+pub struct Rewindable<'a> {
+    inner: &'a mut dyn Frontend,
+    ring: VecDeque<Snapshot>,
+    capacity: usize,
+    // frames since the last snapshot
+    frames: u32,
+}
+
+impl<'a> Rewindable<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, vm: &VM) -> Self {
+        let per_second = TIMER_HZ as usize / REWIND_EVERY as usize;
+        // the framebuffer and registers on top of memory
+        let size = vm.memory.len() + 512;
+        Rewindable {
+            inner,
+            ring: VecDeque::new(),
+            capacity: (REWIND_BUDGET / size).clamp(1, REWIND_SECONDS * per_second),
+            frames: 0,
+        }
+    }
+}
+
+impl DisplaySink for Rewindable<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for Rewindable<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for Rewindable<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let control = self.inner.poll(vm);
+        if let Control::Rewind = control {
+            if let Some(snapshot) = self.ring.pop_back() {
+                vm.restore(&snapshot);
+            }
+            self.frames = 0;
+            return control;
+        }
+        if self
+            .ring
+            .back()
+            .is_some_and(|snapshot| vm.cycles() < snapshot.cycles())
+        {
+            // the ROM was reset or switched
+            self.ring.clear();
+        }
+        if self.frames == 0 {
+            if self.ring.len() == self.capacity {
+                self.ring.pop_front();
+            }
+            self.ring.push_back(vm.snapshot());
+        }
+        self.frames = (self.frames + 1) % REWIND_EVERY;
+        control
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{INTERVAL, Rewind};
+    use chip8_emu_rs::{VM, VmConfig, assemble};
+
+    const IPF: u64 = 10;
+
+    // waits on DT, reads a key and draws random bytes from the ROM, keeping
+    // a running score in memory
+    const PROGRAM: &str = "
+        loop:
+            LD V0, 3
+            LD DT, V0
+        wait:
+            LD V0, DT
+            SE V0, 0
+            JP wait
+            RND V1, 0x3F
+            RND V2, 0x1F
+            LD I, loop
+            DRW V1, V2, 4
+            ADD V3, VF
+            SKNP V4
+            ADD V3, 2
+            ADD V4, 1
+            LD I, 0x400
+            LD B, V3
+            JP loop
+    ";
+
+    // everything replaying has to get back
+    #[derive(Debug, PartialEq)]
+    struct State {
+        cycles: u64,
+        frames: u64,
+        pc: u16,
+        i: u16,
+        v: [u8; 16],
+        delay_timer: u8,
+        sound_timer: u8,
+        stack: Vec<u16>,
+        keys: [bool; 16],
+        screen: u64,
+        memory: Vec<u8>,
+    }
+
+    fn state(vm: &VM) -> State {
+        State {
+            cycles: vm.cycles(),
+            frames: vm.frames(),
+            pc: vm.pc,
+            i: vm.i,
+            v: vm.v,
+            delay_timer: vm.delay_timer,
+            sound_timer: vm.sound_timer,
+            stack: vm.stack().to_vec(),
+            keys: vm.keys(),
+            screen: vm.framebuffer_hash(),
+            memory: vm.memory.clone(),
+        }
+    }
+
+    // what the debugger's loop does before each instruction: poll the
+    // frontend, which finds a different key held every so often
+    fn poll(vm: &mut VM, rewind: &mut Rewind) {
+        let mut keys = [false; 16];
+        keys[(vm.cycles() / 37 % 16) as usize] = true;
+        vm.set_keys(keys);
+        rewind.checkpoint(vm);
+    }
+
+    // one instruction the way `s` runs it
+    fn step(vm: &mut VM, rewind: &mut Rewind) {
+        vm.step().unwrap();
+        if vm.cycles().is_multiple_of(IPF) {
+            vm.step_timers(1);
+            rewind.record_tick(vm);
+        }
+        rewind.checkpoint(vm);
+    }
+
+    #[test]
+    fn stepping_back_matches_the_state_seen_going_forward() {
+        let mut vm = VmConfig::new(|| 0).rng_seed(3).build().unwrap();
+        vm.load_rom(&assemble(PROGRAM).unwrap()).unwrap();
+        let mut rewind = Rewind::new(&vm);
+        let mut seen = Vec::new();
+        for _ in 0..2 * INTERVAL + 500 {
+            poll(&mut vm, &mut rewind);
+            seen.push(state(&vm));
+            step(&mut vm, &mut rewind);
+        }
+
+        // back over a snapshot to where a key changed, one instruction
+        // further, then to just after a timer tick
+        for (count, to) in [(INTERVAL + 20, 37 * 40), (1, 37 * 40 - 1), (IPF - 1, 1470)] {
+            rewind.step_back(&mut vm, count).unwrap();
+            assert_eq!(vm.cycles(), to);
+            poll(&mut vm, &mut rewind);
+            assert_eq!(state(&vm), seen[to as usize], "{} back", count);
+        }
+
+        // and forward again, the same way as the first time
+        for expected in &seen[vm.cycles() as usize + 1..] {
+            step(&mut vm, &mut rewind);
+            poll(&mut vm, &mut rewind);
+            assert_eq!(&state(&vm), expected);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+// Where CXNN's random bytes come from.
+//
+// Seeded mode is xorshift64* (Vigna, 2014): the 64-bit state starts as
+// splitmix64(seed), or 1 if that comes out as zero, and each byte is the top
+// 8 bits of `state * 0x2545F4914F6CDD1D` after the 12/25/27 xorshift round.
+// Golden hashes of seeded runs depend on this exact sequence, so it must not
+// change between releases.
+#[derive(Clone, Copy, Debug)]
+pub enum Rng {
+    // injected by the frontend, e.g. the OS-seeded `rand::random`
+    External(fn() -> u8),
+    Seeded { seed: u64, state: u64 },
+    // the same byte every time, so CXNN is `byte & NN`; for tests only
+    Fixed(u8),
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Rng::Seeded {
+            seed,
+            state: initial_state(seed),
+        }
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        match self {
+            Rng::External(rng) => rng(),
+            Rng::Seeded { state, .. } => {
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+                (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            Rng::Fixed(byte) => *byte,
+        }
+    }
+
+    // back to the start of the seeded sequence, so a reset replays identically
+    pub fn reseed(&mut self) {
+        if let Rng::Seeded { seed, state } = self {
+            *state = initial_state(*seed);
+        }
+    }
+}
+
+fn initial_state(seed: u64) -> u64 {
+    // splitmix64, so similar seeds still start far apart
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    if z == 0 { 1 } else { z }
+}
@@ -0,0 +1,127 @@
+use crate::error::Chip8Error;
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "zip")]
+use crate::config::MAX_MEMORY_SIZE;
+#[cfg(feature = "zip")]
+use std::io::{Cursor, Read};
+
+pub fn load_rom_from_path(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    load_rom_entry(path, None)
+}
+
+// Like `load_rom_from_path`, but for a .zip archive (by extension or magic
+// bytes) it loads `entry`, matched against full names and then file names,
+// or otherwise the archive's only .ch8/.rom file. A .hex file is read as
+// text, see `parse_hex_rom`. Plain files ignore `entry`.
+pub fn load_rom_entry(path: &Path, entry: Option<&str>) -> Result<Vec<u8>, Chip8Error> {
+    if has_extension(path, "hex") {
+        return parse_hex_rom(&fs::read_to_string(path)?);
+    }
+    let data = fs::read(path)?;
+    #[cfg(feature = "zip")]
+    if is_zip(path, &data) {
+        return extract_rom(data, entry);
+    }
+    #[cfg(not(feature = "zip"))]
+    let _ = entry;
+    Ok(data)
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+// A ROM typed out as hex, the way tutorials list programs: bytes separated
+// by spaces or newlines, e.g. `A2 2A 60 0C`, or run together into words such
+// as `A22A 600C`. Anything from ';' to the end of the line is a comment.
+fn parse_hex_rom(text: &str) -> Result<Vec<u8>, Chip8Error> {
+    let mut rom = Vec::new();
+    for (line_idx, line) in text.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or("");
+        let error = |at: usize, reason: String| Chip8Error::HexRom {
+            line: line_idx + 1,
+            column: code[..at].chars().count() + 1,
+            reason,
+        };
+        let mut at = 0;
+        for token in code.split_inclusive(char::is_whitespace) {
+            let start = at;
+            at += token.len();
+            let token = token.trim_end();
+            if let Some((bad, c)) = token.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+                return Err(error(start + bad, format!("'{}' is not a hex digit", c)));
+            }
+            if token.len() % 2 != 0 {
+                return Err(error(
+                    start,
+                    format!("'{}' has an odd number of hex digits", token),
+                ));
+            }
+            // all ASCII, so every two bytes are a digit pair
+            for pair in (0..token.len()).step_by(2) {
+                rom.push(u8::from_str_radix(&token[pair..pair + 2], 16).unwrap_or(0));
+            }
+        }
+    }
+    Ok(rom)
+}
+
+#[cfg(feature = "zip")]
+fn is_zip(path: &Path, data: &[u8]) -> bool {
+    has_extension(path, "zip") || data.starts_with(b"PK\x03\x04")
+}
+
+#[cfg(feature = "zip")]
+fn extract_rom(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, Chip8Error> {
+    let archive_error = |e: zip::result::ZipError| Chip8Error::Archive(e.to_string());
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(archive_error)?;
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+    let name = match entry {
+        Some(entry) => names
+            .iter()
+            .find(|name| *name == entry)
+            .or_else(|| names.iter().find(|name| file_name(name) == entry))
+            .ok_or_else(|| Chip8Error::Archive(format!("no entry named '{}'", entry)))?
+            .clone(),
+        None => {
+            let mut roms: Vec<String> =
+                names.into_iter().filter(|name| is_rom_name(name)).collect();
+            match roms.len() {
+                0 => return Err(Chip8Error::Archive("no .ch8 or .rom file".to_string())),
+                1 => roms.remove(0),
+                _ => return Err(Chip8Error::AmbiguousArchive { entries: roms }),
+            }
+        }
+    };
+    let file = archive.by_name(&name).map_err(archive_error)?;
+    let too_large = |size: usize| Chip8Error::RomTooLarge {
+        size,
+        max: MAX_MEMORY_SIZE,
+    };
+    if file.size() > MAX_MEMORY_SIZE as u64 {
+        return Err(too_large(file.size() as usize));
+    }
+    // the header's size can't be trusted either, so read at most one byte
+    // past the largest address space to tell an oversized entry apart
+    let mut rom = Vec::new();
+    file.take(MAX_MEMORY_SIZE as u64 + 1)
+        .read_to_end(&mut rom)?;
+    if rom.len() > MAX_MEMORY_SIZE {
+        return Err(too_large(rom.len()));
+    }
+    Ok(rom)
+}
+
+#[cfg(feature = "zip")]
+fn file_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+#[cfg(feature = "zip")]
+fn is_rom_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    !name.ends_with('/') && (name.ends_with(".ch8") || name.ends_with(".rom"))
+}
@@ -0,0 +1,228 @@
+use chip8_emu_rs::{AudioSink, Control, DisplaySink, Frontend, InputSource, STATE_VERSION, VM};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::playlist::Playlist;
+use crate::sha1::{hex, sha1};
+
+// Save states: F8 writes the whole VM to a file for the running ROM, F9 reads
+// it back, and --state FILE starts from one. A file is MAGIC, the format
+// version, a header and then the VM as the library serializes it, all in
+// postcard. The version comes before the header so a file from another
+// release is refused with a message instead of failing to parse. Nothing is
+// written as it lies in memory. PC, I and the stack are big-endian byte pairs,
+// which the library writes itself; every other integer is in postcard's own
+// encoding, a varint with the least significant 7 bits first, and floats are
+// little-endian. That encoding is the file format, the same on every host.
+const MAGIC: &[u8; 4] = b"C8ST";
+// bump whenever Header changes shape
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    // the VM's own layout version; postcard loses the library's error
+    // message, so it's checked here first
+    state_version: u32,
+    pub rom_name: String,
+    pub rom_sha1: [u8; 20],
+    // the quirk preset the VM's quirks matched when saved, or "custom"
+    pub profile: String,
+    // seconds since the Unix epoch
+    pub saved_at: u64,
+}
+
+// the save state file the hotkeys use for a ROM: one per ROM, the hash
+// telling apart ROMs that share a file name
+fn state_path(rom_name: &str, sha1: &[u8; 20]) -> Result<PathBuf, String> {
+    let dir = data_dir().ok_or("no data directory: neither HOME nor APPDATA is set")?;
+    Ok(dir.join("chip8-emu-rs").join("states").join(format!(
+        "{}-{}.state",
+        rom_name,
+        &hex(sha1)[..8]
+    )))
+}
+
+// $XDG_DATA_HOME or ~/.local/share, ~/Library/Application Support on macOS
+// and %APPDATA% on Windows
+fn data_dir() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").filter(|home| !home.is_empty());
+    if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| Path::new(&home).join(".local/share")))
+    }
+}
+
+pub fn save(path: &Path, vm: &VM, rom_name: &str) -> Result<(), String> {
+    let header = Header {
+        state_version: STATE_VERSION,
+        rom_name: rom_name.to_string(),
+        rom_sha1: sha1(vm.rom()),
+        profile: crate::preset_name(&vm.quirks)
+            .unwrap_or("custom")
+            .to_string(),
+        saved_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()),
+    };
+    let bytes = postcard::to_extend(&FORMAT_VERSION, MAGIC.to_vec())
+        .and_then(|bytes| postcard::to_extend(&header, bytes))
+        .and_then(|bytes| postcard::to_extend(vm, bytes))
+        .map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    // through a temporary file, so a failed write leaves the old state
+    let partial = path.with_extension("state.partial");
+    fs::write(&partial, bytes)
+        .and_then(|()| fs::rename(&partial, path))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Reads a state for the ROM `vm` is running, refusing one saved by another
+// format version or for another ROM
+pub fn load(path: &Path, vm: &VM) -> Result<VM, String> {
+    let (header, state) = read(path)?;
+    if header.rom_sha1 != sha1(vm.rom()) {
+        return Err(format!(
+            "'{}' was saved with a different ROM: {} (SHA-1 {})",
+            path.display(),
+            header.rom_name,
+            hex(&header.rom_sha1)
+        ));
+    }
+    Ok(state)
+}
+
+// Reads a state for any ROM, refusing one saved by another format version
+pub fn read(path: &Path) -> Result<(Header, VM), String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let not_a_state = || format!("'{}' is not a save state", path.display());
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err(not_a_state());
+    };
+    let (version, rest) = postcard::take_from_bytes::<u32>(rest).map_err(|_| not_a_state())?;
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "'{}' is save state format version {}, this build reads version {}",
+            path.display(),
+            version,
+            FORMAT_VERSION
+        ));
+    }
+    let (header, rest) = postcard::take_from_bytes::<Header>(rest)
+        .map_err(|e| format!("'{}' is corrupt: {}", path.display(), e))?;
+    if header.state_version != STATE_VERSION {
+        return Err(format!(
+            "'{}' holds VM state version {}, this build reads version {}",
+            path.display(),
+            header.state_version,
+            STATE_VERSION
+        ));
+    }
+    let state = postcard::from_bytes::<VM>(rest)
+        .map_err(|e| format!("'{}' is corrupt: {}", path.display(), e))?;
+    Ok((header, state))
+}
+
+// The names of the ROMs on the command line by hash, to tell which one the
+// VM is running when the frontend switched to another
+pub struct RomNames(Vec<([u8; 20], String)>);
+
+impl RomNames {
+    pub fn new(playlist: &Playlist) -> Self {
+        RomNames(
+            playlist
+                .entries()
+                .iter()
+                .map(|(name, rom)| (sha1(rom), name.clone()))
+                .collect(),
+        )
+    }
+}
+
+// Wraps a frontend and answers its save and load state hotkeys with the
+// running ROM's file
+pub struct SaveStates<'a> {
+    inner: &'a mut dyn Frontend,
+    roms: &'a RomNames,
+}
+
+impl<'a> SaveStates<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, roms: &'a RomNames) -> Self {
+        SaveStates { inner, roms }
+    }
+
+    // the running ROM's name and state file
+    fn file(&self, vm: &VM) -> Result<(&str, PathBuf), String> {
+        let sha1 = sha1(vm.rom());
+        let name = self
+            .roms
+            .0
+            .iter()
+            .find(|(hash, _)| *hash == sha1)
+            .map_or("rom", |(_, name)| name);
+        Ok((name, state_path(name, &sha1)?))
+    }
+
+    fn save(&self, vm: &VM) -> Result<PathBuf, String> {
+        let (name, path) = self.file(vm)?;
+        save(&path, vm, name)?;
+        Ok(path)
+    }
+
+    fn load(&self, vm: &mut VM) -> Result<PathBuf, String> {
+        let (name, path) = self.file(vm)?;
+        if !path.exists() {
+            return Err(format!("no state saved for {} yet", name));
+        }
+        vm.replace_state(load(&path, vm)?);
+        Ok(path)
+    }
+}
+
+impl DisplaySink for SaveStates<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for SaveStates<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for SaveStates<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        match self.inner.poll(vm) {
+            Control::SaveState => {
+                match self.save(vm) {
+                    Ok(path) => eprintln!("State saved to '{}'.", path.display()),
+                    Err(msg) => eprintln!("Warning: cannot save state: {}", msg),
+                }
+                Control::Continue
+            }
+            Control::LoadState => {
+                match self.load(vm) {
+                    Ok(path) => eprintln!("State loaded from '{}'.", path.display()),
+                    Err(msg) => eprintln!("Warning: cannot load state: {}", msg),
+                }
+                Control::Continue
+            }
+            control => control,
+        }
+    }
+}
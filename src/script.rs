@@ -0,0 +1,178 @@
+use chip8_emu_rs::{
+    AudioSink, Control, DisplaySink, FB_HEIGHT, FB_WIDTH, Frontend, InputSource, VM,
+};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::theme::Palette;
+
+// --script: one command per line, run in order as the ROM plays
+//
+//     wait N            let N frames pass
+//     press K           hold CHIP-8 key K (a hex digit) down until released
+//     release K
+//     reset             restart the ROM
+//     screenshot [FILE] save the screen as a PPM image, screenshot-<n>.ppm
+//                       by default
+//     quit
+//
+// Blank lines and lines starting with '#' are skipped. Every command up to
+// the next `wait` runs in the same frame. Once the script ends the ROM keeps
+// running as usual.
+#[derive(Clone)]
+enum Command {
+    Wait(u32),
+    Press(usize),
+    Release(usize),
+    Reset,
+    Screenshot(Option<PathBuf>),
+    Quit,
+}
+
+// screen pixels per CHIP-8 pixel in screenshots
+const SCREENSHOT_SCALE: usize = 8;
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let key = |word: &str| {
+        usize::from_str_radix(word, 16)
+            .ok()
+            .filter(|&key| key <= 0xF)
+            .ok_or_else(|| format!("expected a key from 0 to F, got '{}'", word))
+    };
+    match words.as_slice() {
+        ["wait", frames] => frames
+            .parse()
+            .map(Command::Wait)
+            .map_err(|_| format!("expected a number of frames, got '{}'", frames)),
+        ["press", k] => key(k).map(Command::Press),
+        ["release", k] => key(k).map(Command::Release),
+        ["reset"] => Ok(Command::Reset),
+        ["screenshot"] => Ok(Command::Screenshot(None)),
+        ["screenshot", path] => Ok(Command::Screenshot(Some(PathBuf::from(path)))),
+        ["quit"] => Ok(Command::Quit),
+        _ => Err(format!("unknown command '{}'", line)),
+    }
+}
+
+#[derive(Clone)]
+pub struct Script(VecDeque<Command>);
+
+impl Script {
+    pub fn load(path: &Path) -> io::Result<Script> {
+        let mut commands = VecDeque::new();
+        for (idx, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command = parse_command(line).map_err(|msg| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", idx + 1, msg),
+                )
+            })?;
+            commands.push_back(command);
+        }
+        Ok(Script(commands))
+    }
+}
+
+// Wraps a frontend and plays a script against it, one step per poll
+pub struct Scripted<'a> {
+    inner: &'a mut dyn Frontend,
+    commands: VecDeque<Command>,
+    // frames left in the current `wait`
+    waiting: u32,
+    keys: [bool; 16],
+    palette: Palette,
+    screenshots: u32,
+}
+
+impl<'a> Scripted<'a> {
+    pub fn new(inner: &'a mut dyn Frontend, script: Script, palette: Palette) -> Self {
+        Scripted {
+            inner,
+            commands: script.0,
+            waiting: 0,
+            keys: [false; 16],
+            palette,
+            screenshots: 0,
+        }
+    }
+
+    fn screenshot(&mut self, vm: &VM, path: Option<PathBuf>) {
+        let path = path.unwrap_or_else(|| {
+            self.screenshots += 1;
+            PathBuf::from(format!("screenshot-{}.ppm", self.screenshots))
+        });
+        if let Err(e) = write_ppm(&path, vm, self.palette) {
+            eprintln!("Warning: cannot write '{}': {}", path.display(), e);
+        }
+    }
+}
+
+// binary PPM, the simplest format image viewers and converters all read
+pub fn write_ppm(path: &Path, vm: &VM, palette: Palette) -> io::Result<()> {
+    let (width, height) = (FB_WIDTH as usize, FB_HEIGHT as usize);
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(
+        out,
+        "P6\n{} {}\n255\n",
+        width * SCREENSHOT_SCALE,
+        height * SCREENSHOT_SCALE
+    )?;
+    for y in 0..height * SCREENSHOT_SCALE {
+        for x in 0..width * SCREENSHOT_SCALE {
+            let on = vm.pixel((x / SCREENSHOT_SCALE) as u32, (y / SCREENSHOT_SCALE) as u32);
+            out.write_all(&if on { palette.fg() } else { palette.bg() })?;
+        }
+    }
+    out.flush()
+}
+
+impl DisplaySink for Scripted<'_> {
+    fn present(&mut self, vm: &VM) {
+        self.inner.present(vm);
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.inner.show_message(text);
+    }
+}
+
+impl AudioSink for Scripted<'_> {
+    fn set_beep(&mut self, on: bool) {
+        self.inner.set_beep(on);
+    }
+}
+
+impl InputSource for Scripted<'_> {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let control = self.inner.poll(vm);
+        if self.waiting > 0 {
+            self.waiting -= 1;
+        }
+        while self.waiting == 0
+            && let Some(command) = self.commands.pop_front()
+        {
+            match command {
+                Command::Wait(frames) => self.waiting = frames,
+                Command::Press(key) => self.keys[key] = true,
+                Command::Release(key) => self.keys[key] = false,
+                Command::Reset => vm.reset(),
+                Command::Screenshot(path) => self.screenshot(vm, path),
+                Command::Quit => return Control::Quit,
+            }
+        }
+        // on top of the keys actually pressed
+        for (key, &down) in self.keys.iter().enumerate() {
+            if down {
+                vm.key_down(key);
+            }
+        }
+        control
+    }
+}
@@ -0,0 +1,983 @@
+use chip8_emu_rs::{
+    AudioSink, Control, DisplaySink, FB_HEIGHT, FB_WIDTH, FONT, FONT_START, InputSource, Tone, VM,
+    disassemble, instructions_per_frame,
+};
+use sdl3::EventPump;
+use sdl3::audio::{AudioCallback, AudioFormat, AudioSpec, AudioStream, AudioStreamWithCallback};
+use sdl3::event::{Event, WindowEvent};
+use sdl3::keyboard::Keycode;
+use sdl3::keyboard::Scancode;
+use sdl3::pixels::Color;
+use sdl3::render::{BlendMode, FRect, WindowCanvas};
+use std::array;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::debugger::sprite_rows;
+use crate::glyphs::{GLYPH_HEIGHT, GLYPH_WIDTH, glyph};
+use crate::playlist::Playlist;
+use crate::theme::Palette;
+use crate::wav::WavWriter;
+
+// Minifb window size
+const WINDOW_WIDTH: u32 = FB_WIDTH * 15;
+const WINDOW_HEIGHT: u32 = FB_HEIGHT * 15;
+
+// Audio
+const SAMPLE_RATE: i32 = 44100;
+const BEEP_HZ: f32 = 440.0;
+const BEEP_VOLUME: f32 = 0.25;
+// length of the linear attack/release ramp around each beep
+const ENVELOPE_MS: f32 = 5.0;
+// samples generated at a time, into a buffer the audio callback owns
+const AUDIO_CHUNK: usize = 1024;
+// chunks the WAV writer thread can fall behind by, about a third of a second
+const RECORD_BUFFERS: usize = 16;
+
+// host key for each CHIP-8 key, laid out as the left side of a QWERTY keyboard
+const KEYMAP: [(Scancode, usize); 16] = [
+    (Scancode::_1, 0x1),
+    (Scancode::_2, 0x2),
+    (Scancode::_3, 0x3),
+    (Scancode::_4, 0xC),
+    (Scancode::Q, 0x4),
+    (Scancode::W, 0x5),
+    (Scancode::E, 0x6),
+    (Scancode::R, 0xD),
+    (Scancode::A, 0x7),
+    (Scancode::S, 0x8),
+    (Scancode::D, 0x9),
+    (Scancode::F, 0xE),
+    (Scancode::Z, 0xA),
+    (Scancode::X, 0x0),
+    (Scancode::C, 0xB),
+    (Scancode::V, 0xF),
+];
+
+// polls between frame steps while `.` is held, 10 per second at 60Hz
+const FRAME_STEP_REPEAT: u32 = 6;
+
+// the hex keypad as laid out on the COSMAC VIP, drawn by the F2 overlay
+const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+// overlay pixel size in framebuffer pixels, so the glyphs stay small
+const OVERLAY_PIXEL: f32 = 1.0 / 3.0;
+// a 4x5 font glyph with a one pixel border
+const OVERLAY_CELL_W: f32 = 6.0;
+const OVERLAY_CELL_H: f32 = 7.0;
+
+// values in the debug overlay: V0-VF, then PC, I, SP, DT and ST
+const DEBUG_VALUES: usize = 21;
+// debug overlay text cells, in glyph pixels, and its width in cells
+const DEBUG_CELL_W: f32 = (GLYPH_WIDTH + 1) as f32;
+const DEBUG_CELL_H: f32 = (GLYPH_HEIGHT + 1) as f32;
+const DEBUG_COLUMNS: usize = 27;
+// how long a show_message notice stays up
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+// the memory map shows the first 4K as 64 rows of 64 bytes, each byte half a
+// framebuffer pixel wide, over the right half of the screen
+const MEMMAP_BYTES: usize = 4096;
+const MEMMAP_COLUMNS: usize = 64;
+const MEMMAP_CELL: f32 = 0.5;
+
+// The audio thread's end of --record-audio. Played samples go to the WAV
+// writer thread in buffers it hands back once they're written, so recording
+// neither allocates nor blocks in the callback; if the writer falls more than
+// RECORD_BUFFERS chunks behind, the samples are dropped and counted instead.
+struct Recorder {
+    filled: SyncSender<Vec<f32>>,
+    empty: Receiver<Vec<f32>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Recorder {
+    fn record(&mut self, samples: &[f32]) {
+        match self.empty.try_recv() {
+            Ok(mut buffer) => {
+                buffer.clear();
+                // at most AUDIO_CHUNK samples, which the buffer has room for
+                buffer.extend_from_slice(samples);
+                let _ = self.filled.try_send(buffer);
+            }
+            Err(_) => {
+                self.dropped.fetch_add(samples.len(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+struct SquareWave {
+    tone: Tone,
+    gate: Arc<AtomicBool>,
+    // AUDIO_CHUNK samples, allocated once up front
+    buffer: Vec<f32>,
+    // everything played is teed to the WAV writer thread when recording
+    recorder: Option<Recorder>,
+}
+
+impl AudioCallback<f32> for SquareWave {
+    fn callback(&mut self, stream: &mut AudioStream, requested: i32) {
+        let gate = self.gate.load(Ordering::Relaxed);
+        let mut left = requested.max(0) as usize;
+        while left > 0 {
+            let chunk = &mut self.buffer[..left.min(AUDIO_CHUNK)];
+            self.tone.fill(gate, chunk);
+            let _ = stream.put_data_f32(chunk);
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(chunk);
+            }
+            left -= chunk.len();
+        }
+    }
+}
+
+// Where the framebuffer lands in the window: the largest integer scale that
+// fits, centered between black bars. Pixels are `aspect` times wider than
+// that scale, so square at 1.0, with only the vertical scale kept integer.
+#[derive(Clone, Copy)]
+struct Layout {
+    scale_x: f32,
+    scale_y: f32,
+    left: f32,
+    top: f32,
+}
+
+impl Layout {
+    fn fit((width, height): (u32, u32), aspect: f32) -> Self {
+        let columns = FB_WIDTH as f32 * aspect;
+        let scale = ((width as f32 / columns) as u32)
+            .min(height / FB_HEIGHT)
+            .max(1);
+        let scale_x = scale as f32 * aspect;
+        Layout {
+            scale_x,
+            scale_y: scale as f32,
+            left: ((width as f32 - FB_WIDTH as f32 * scale_x) / 2.0)
+                .max(0.0)
+                .floor(),
+            top: (height.saturating_sub(FB_HEIGHT * scale) / 2) as f32,
+        }
+    }
+
+    // a rectangle in framebuffer pixels, in window pixels
+    fn rect(&self, x: f32, y: f32, w: f32, h: f32) -> FRect {
+        FRect::new(
+            self.left + x * self.scale_x,
+            self.top + y * self.scale_y,
+            w * self.scale_x,
+            h * self.scale_y,
+        )
+    }
+}
+
+pub struct SdlFrontend {
+    canvas: WindowCanvas,
+    event_pump: EventPump,
+    prev_framebuffer: Vec<u8>,
+
+    device: AudioStreamWithCallback<SquareWave>,
+    beep_gate: Arc<AtomicBool>,
+    // shortest audible beep; presentation only, the VM's sound timer is untouched
+    min_beep: Duration,
+    was_beeping: bool,
+    beep_until: Instant,
+    recorder_thread: Option<JoinHandle<()>>,
+
+    playlist: Playlist,
+    // print the machine state on every present, for --slowmo
+    trace: bool,
+    // F2: keypad overlay showing which CHIP-8 keys are down
+    show_keypad: bool,
+    // F4: memory map overlay
+    show_memmap: bool,
+    // F5: call stack overlay
+    show_stack: bool,
+    // --latch-taps: keypad keys pressed since the last poll count as down
+    // for the coming frame even if they were released again
+    latch_taps: bool,
+    tapped: [bool; 16],
+    // polls `.` has been held for
+    step_held: u32,
+    // F6: the sprite at I, magnified
+    show_sprite: bool,
+    // F7: registers, timers and the next instruction over the game
+    show_debug: bool,
+    // the debug values at the last present and the cycle count then, and
+    // which of them the one instruction before it changed, if only one ran
+    debug_seen: (u64, [u16; DEBUG_VALUES]),
+    debug_changed: [bool; DEBUG_VALUES],
+    // recomputed whenever the window's pixel size changes
+    layout: Layout,
+    // --pixel-aspect
+    pixel_aspect: f32,
+    // F11
+    fullscreen: bool,
+    palette: Palette,
+    // --interlace: each present takes only the even or the odd rows of the
+    // new frame, alternating, and leaves the others as they were
+    interlace: bool,
+    // the row parity the next present takes
+    field: u32,
+    // the latest show_message notice and when it goes away
+    message: Option<(String, Instant)>,
+}
+
+impl SdlFrontend {
+    pub fn new(
+        min_beep: Duration,
+        record_audio: Option<&Path>,
+        playlist: Playlist,
+        vm: &VM,
+        palette: Palette,
+        pixel_aspect: f32,
+    ) -> Result<Self, String> {
+        // Window setup
+        let sdl_context = sdl3::init().unwrap();
+        let video_subsystem = sdl_context.video().unwrap();
+        let event_pump = sdl_context.event_pump().unwrap();
+
+        // wider pixels get a wider window, at the same height
+        let window_width = (WINDOW_WIDTH as f32 * pixel_aspect) as u32;
+        let mut window = video_subsystem
+            .window(&window_title(&playlist, vm), window_width, WINDOW_HEIGHT)
+            .position_centered()
+            .resizable()
+            .build()
+            .unwrap();
+        let _ = window.set_minimum_size(FB_WIDTH, FB_HEIGHT);
+        let canvas = window.into_canvas();
+        let layout = Layout::fit(
+            canvas
+                .output_size()
+                .unwrap_or((window_width, WINDOW_HEIGHT)),
+            pixel_aspect,
+        );
+
+        // Audio setup
+        let audio_subsystem = sdl_context.audio().unwrap();
+
+        let source_spec = AudioSpec {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),                    // mono
+            format: Some(AudioFormat::f32_sys()), // floating 32 bit samples
+        };
+
+        // WAV recording: samples are written on their own thread so file IO never
+        // stalls the audio callback
+        let mut recorder = None;
+        let mut recorder_thread = None;
+        if let Some(path) = record_audio {
+            let mut wav = WavWriter::create(path, SAMPLE_RATE as u32)
+                .map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
+            let (filled_tx, filled_rx) = mpsc::sync_channel::<Vec<f32>>(RECORD_BUFFERS);
+            let (empty_tx, empty_rx) = mpsc::sync_channel::<Vec<f32>>(RECORD_BUFFERS);
+            for _ in 0..RECORD_BUFFERS {
+                let _ = empty_tx.send(Vec::with_capacity(AUDIO_CHUNK));
+            }
+            let dropped = Arc::new(AtomicUsize::new(0));
+            recorder = Some(Recorder {
+                filled: filled_tx,
+                empty: empty_rx,
+                dropped: Arc::clone(&dropped),
+            });
+            recorder_thread = Some(thread::spawn(move || {
+                for samples in filled_rx {
+                    if let Err(e) = wav.write_samples(&samples) {
+                        eprintln!("Audio recording stopped: {}", e);
+                        return;
+                    }
+                    let _ = empty_tx.send(samples);
+                }
+                if let Err(e) = wav.finish() {
+                    eprintln!("Failed to finalize audio recording: {}", e);
+                }
+                let dropped = dropped.load(Ordering::Relaxed);
+                if dropped > 0 {
+                    eprintln!(
+                        "Audio recording is missing {} samples the disk couldn't keep up with",
+                        dropped
+                    );
+                }
+            }));
+        }
+
+        // The stream runs for the whole session and the beep is gated through the
+        // envelope, so the release tail plays out instead of being cut by a pause
+        let beep_gate = Arc::new(AtomicBool::new(false));
+        let device = audio_subsystem
+            .open_playback_stream(
+                &source_spec,
+                SquareWave {
+                    tone: Tone::new(SAMPLE_RATE as u32, BEEP_HZ, BEEP_VOLUME, ENVELOPE_MS),
+                    gate: Arc::clone(&beep_gate),
+                    buffer: vec![0.0; AUDIO_CHUNK],
+                    recorder,
+                },
+            )
+            .unwrap();
+        device.resume().expect("Failed to start playback");
+
+        Ok(SdlFrontend {
+            canvas,
+            event_pump,
+            prev_framebuffer: vec![0; (FB_WIDTH * FB_HEIGHT) as usize],
+            device,
+            beep_gate,
+            min_beep,
+            was_beeping: false,
+            beep_until: Instant::now(),
+            recorder_thread,
+            playlist,
+            trace: false,
+            show_keypad: false,
+            show_memmap: false,
+            show_stack: false,
+            latch_taps: false,
+            tapped: [false; 16],
+            step_held: 0,
+            show_sprite: false,
+            show_debug: false,
+            debug_seen: (0, [0; DEBUG_VALUES]),
+            debug_changed: [false; DEBUG_VALUES],
+            layout,
+            pixel_aspect,
+            fullscreen: false,
+            palette,
+            interlace: false,
+            field: 0,
+            message: None,
+        })
+    }
+
+    // Closes the audio stream, which drops the recorder's sender and lets the
+    // writer thread drain and patch the WAV header
+    pub fn finish(self) {
+        drop(self.device);
+        if let Some(handle) = self.recorder_thread {
+            let _ = handle.join();
+        }
+    }
+
+    pub fn set_trace(&mut self, on: bool) {
+        self.trace = on;
+    }
+
+    pub fn set_memmap(&mut self, on: bool) {
+        self.show_memmap = on;
+    }
+
+    pub fn set_stack_overlay(&mut self, on: bool) {
+        self.show_stack = on;
+    }
+
+    pub fn set_latch_taps(&mut self, on: bool) {
+        self.latch_taps = on;
+    }
+
+    pub fn set_interlace(&mut self, on: bool) {
+        self.interlace = on;
+    }
+
+    // One cell per byte, brighter for larger values: the font tinted blue, the
+    // ROM green, and the bytes at I and PC marked in yellow and magenta, so
+    // self-modifying code shows up as the ROM region changing
+    fn draw_memmap(&mut self, vm: &VM) {
+        let layout = self.layout;
+        let left = FB_WIDTH as f32 - MEMMAP_COLUMNS as f32 * MEMMAP_CELL;
+        let font = FONT_START..FONT_START + FONT.len();
+        let rom = vm.rom_range();
+        let (i, pc) = (vm.i as usize, vm.pc as usize);
+        for (addr, &byte) in vm.memory.iter().take(MEMMAP_BYTES).enumerate() {
+            let v = byte as u16;
+            let color = if addr == pc || addr == pc + 1 {
+                Color::RGB(255, 0, 255)
+            } else if addr == i {
+                Color::RGB(255, 255, 0)
+            } else if font.contains(&addr) {
+                Color::RGB((v / 2) as u8, (v / 2) as u8, (64 + v * 3 / 4) as u8)
+            } else if rom.contains(&addr) {
+                Color::RGB((v / 2) as u8, (64 + v * 3 / 4) as u8, (v / 2) as u8)
+            } else {
+                Color::RGB(byte, byte, byte)
+            };
+            self.canvas.set_draw_color(color);
+            let _ = self.canvas.fill_rect(layout.rect(
+                left + (addr % MEMMAP_COLUMNS) as f32 * MEMMAP_CELL,
+                (addr / MEMMAP_COLUMNS) as f32 * MEMMAP_CELL,
+                MEMMAP_CELL,
+                MEMMAP_CELL,
+            ));
+        }
+    }
+
+    // 4x4 keypad in the top-right corner, pressed keys highlighted
+    fn draw_keypad(&mut self, vm: &VM) {
+        let layout = self.layout;
+        let px = OVERLAY_PIXEL;
+        let left = FB_WIDTH as f32 - 1.0 - 4.0 * OVERLAY_CELL_W * px;
+        let top = 1.0;
+        for (row, keys) in KEYPAD.iter().enumerate() {
+            for (col, &key) in keys.iter().enumerate() {
+                let x = left + col as f32 * OVERLAY_CELL_W * px;
+                let y = top + row as f32 * OVERLAY_CELL_H * px;
+                let (background, glyph) = if vm.is_key_down(key) {
+                    (Color::RGB(255, 176, 0), Color::RGB(40, 20, 0))
+                } else {
+                    (Color::RGB(40, 40, 40), Color::RGB(160, 160, 160))
+                };
+                self.canvas.set_draw_color(background);
+                let _ = self.canvas.fill_rect(layout.rect(
+                    x,
+                    y,
+                    (OVERLAY_CELL_W - 0.5) * px,
+                    (OVERLAY_CELL_H - 0.5) * px,
+                ));
+                self.draw_glyph(x, y, key, glyph);
+            }
+        }
+    }
+
+    // Call depth, then the three innermost return addresses, innermost
+    // first, in the bottom-left corner. Addresses outside the ROM, which
+    // usually means the stack got overwritten, are shown on red.
+    fn draw_stack(&mut self, vm: &VM) {
+        let layout = self.layout;
+        let px = OVERLAY_PIXEL;
+        let stack = vm.stack();
+        let rom = vm.rom_range();
+        let mut lines = vec![(stack.len() as u16, 2, false)];
+        lines.extend(
+            stack
+                .iter()
+                .rev()
+                .take(3)
+                .map(|&ret| (ret, 4, !rom.contains(&(ret as usize)))),
+        );
+        let bottom = FB_HEIGHT as f32 - 1.0;
+        for (row, &(value, digits, corrupt)) in lines.iter().enumerate() {
+            let y = bottom - (lines.len() - row) as f32 * OVERLAY_CELL_H * px;
+            for digit in 0..digits {
+                let x = 1.0 + digit as f32 * OVERLAY_CELL_W * px;
+                let background = if corrupt {
+                    Color::RGB(160, 0, 0)
+                } else {
+                    Color::RGB(40, 40, 40)
+                };
+                self.canvas.set_draw_color(background);
+                let _ = self.canvas.fill_rect(layout.rect(
+                    x,
+                    y,
+                    OVERLAY_CELL_W * px,
+                    (OVERLAY_CELL_H - 0.5) * px,
+                ));
+                let nibble = (value >> (4 * (digits - 1 - digit))) & 0xF;
+                self.draw_glyph(x, y, nibble as usize, Color::RGB(220, 220, 220));
+            }
+        }
+    }
+
+    // Registers, PC, I, the frame count, stack depth, timers, the instruction
+    // at PC, then the clock, memory size and quirks on a translucent panel in
+    // the top-left corner. The glyph pixels are whole window pixels, about a
+    // third of a CHIP-8 pixel, so the text stays crisp and grows with the
+    // window. When exactly one instruction ran since the last frame, as when
+    // stepping in the debugger or with --slowmo, the values it changed are
+    // shown in yellow.
+    fn draw_debug(&mut self, vm: &VM) {
+        let values = debug_values(vm);
+        let (cycles, seen) = self.debug_seen;
+        if vm.cycles() != cycles {
+            self.debug_changed = if vm.cycles() == cycles + 1 {
+                array::from_fn(|idx| values[idx] != seen[idx])
+            } else {
+                [false; DEBUG_VALUES]
+            };
+            self.debug_seen = (vm.cycles(), values);
+        }
+
+        let layout = self.layout;
+        let unit = (layout.scale_y / 3.0).round().max(1.0);
+        let origin = (layout.left + layout.scale_x, layout.top + layout.scale_y);
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let _ = self.canvas.fill_rect(FRect::new(
+            origin.0 - unit,
+            origin.1 - unit,
+            (DEBUG_COLUMNS as f32 * DEBUG_CELL_W + 1.0) * unit,
+            (9.0 * DEBUG_CELL_H + 1.0) * unit,
+        ));
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        let mut text = |col: usize, line: usize, s: &str, idx: Option<usize>| {
+            let color = match idx {
+                Some(idx) if self.debug_changed[idx] => Color::RGB(255, 220, 0),
+                Some(_) => Color::RGB(255, 255, 255),
+                None => Color::RGB(150, 150, 150),
+            };
+            let x = origin.0 + col as f32 * DEBUG_CELL_W * unit;
+            let y = origin.1 + line as f32 * DEBUG_CELL_H * unit;
+            self.draw_text(x, y, unit, s, color);
+        };
+        for (x, value) in values[..16].iter().enumerate() {
+            let (col, line) = (x % 4 * 7, x / 4);
+            text(col, line, &format!("V{:X}", x), None);
+            text(col + 3, line, &format!("{:02X}", value), Some(x));
+        }
+        text(0, 4, "PC", None);
+        text(3, 4, &format!("{:04X}", values[16]), Some(16));
+        text(9, 4, "I", None);
+        text(11, 4, &format!("{:04X}", values[17]), Some(17));
+        text(17, 4, "FR", None);
+        text(0, 5, "SP", None);
+        text(3, 5, &values[18].to_string(), Some(18));
+        text(7, 5, "DT", None);
+        text(10, 5, &format!("{:02X}", values[19]), Some(19));
+        text(14, 5, "ST", None);
+        text(17, 5, &format!("{:02X}", values[20]), Some(20));
+        let pc = vm.pc as usize;
+        let mnemonic = match (vm.memory.get(pc), vm.memory.get(pc + 1)) {
+            (Some(&hi), Some(&lo)) => disassemble(u16::from_be_bytes([hi, lo])),
+            _ => "-".to_string(),
+        };
+        let mnemonic: String = mnemonic.chars().take(DEBUG_COLUMNS).collect();
+        // the last digits once it outgrows the panel
+        let frames = vm.frames().to_string();
+        let frames = &frames[frames.len().saturating_sub(DEBUG_COLUMNS - 20)..];
+        let (x, y) = (20.0 * DEBUG_CELL_W * unit, 4.0 * DEBUG_CELL_H * unit);
+        self.draw_text(
+            origin.0 + x,
+            origin.1 + y,
+            unit,
+            frames,
+            Color::RGB(255, 255, 255),
+        );
+        let y = origin.1 + 6.0 * DEBUG_CELL_H * unit;
+        self.draw_text(origin.0, y, unit, &mnemonic, Color::RGB(255, 255, 255));
+        // what --show-quirks prints, quirks that are off dimmed
+        let clock = format!(
+            "{} HZ {} IPF {}K",
+            vm.cpu_hz().round(),
+            instructions_per_frame(vm),
+            vm.memory.len() / 1024
+        );
+        let y = origin.1 + 7.0 * DEBUG_CELL_H * unit;
+        self.draw_text(origin.0, y, unit, &clock, Color::RGB(150, 150, 150));
+        let quirks = [
+            ("SHIFT", vm.quirks.shift_uses_vy),
+            ("JUMP", vm.quirks.jump_uses_vx),
+            ("CLIP", vm.quirks.clip_sprites),
+            ("LDST", vm.quirks.load_store_increments_i),
+            ("VF", vm.quirks.vf_reset_on_logic),
+            ("WAIT", vm.quirks.display_wait),
+        ];
+        let (mut x, y) = (origin.0, origin.1 + 8.0 * DEBUG_CELL_H * unit);
+        for (name, on) in quirks {
+            let color = if on {
+                Color::RGB(255, 255, 255)
+            } else {
+                Color::RGB(70, 70, 70)
+            };
+            self.draw_text(x, y, unit, name, color);
+            x += (name.len() + 1) as f32 * DEBUG_CELL_W * unit;
+        }
+    }
+
+    // The bytes at I that the next DXYN would draw (see `sprite_rows`) in
+    // the bottom-right corner, each byte in hex beside its row of the sprite
+    // at a text line per sprite row, under I's value
+    fn draw_sprite(&mut self, vm: &VM) {
+        let layout = self.layout;
+        let unit = (layout.scale_y / 3.0).round().max(1.0);
+        let start = vm.i as usize;
+        let rows = sprite_rows(vm).min(vm.memory.len().saturating_sub(start));
+        let (cell_w, cell_h) = (DEBUG_CELL_W * unit, DEBUG_CELL_H * unit);
+        // 3 text columns of hex, then 8 sprite pixels as wide as a line is tall
+        let (width, height) = (3.0 * cell_w + 8.0 * cell_h, (rows + 1) as f32 * cell_h);
+        let right = layout.left + (FB_WIDTH as f32 - 1.0) * layout.scale_x;
+        let bottom = layout.top + (FB_HEIGHT as f32 - 1.0) * layout.scale_y;
+        let (left, top) = (right - width, bottom - height);
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let _ = self.canvas.fill_rect(FRect::new(
+            left - unit,
+            top - unit,
+            width + 2.0 * unit,
+            height + 2.0 * unit,
+        ));
+        self.canvas.set_blend_mode(BlendMode::None);
+        self.draw_text(
+            left,
+            top,
+            unit,
+            &format!("I {:04X}", vm.i),
+            Color::RGB(150, 150, 150),
+        );
+        for (row, &byte) in vm.memory[start..start + rows].iter().enumerate() {
+            let y = top + (row + 1) as f32 * cell_h;
+            self.draw_text(
+                left,
+                y,
+                unit,
+                &format!("{:02X}", byte),
+                Color::RGB(255, 255, 255),
+            );
+            for bit in 0..8 {
+                let color = if byte & (0x80 >> bit) != 0 {
+                    Color::RGB(255, 255, 255)
+                } else {
+                    Color::RGB(50, 50, 50)
+                };
+                self.canvas.set_draw_color(color);
+                let _ = self.canvas.fill_rect(FRect::new(
+                    left + 3.0 * cell_w + bit as f32 * cell_h,
+                    y,
+                    cell_h - unit,
+                    cell_h - unit,
+                ));
+            }
+        }
+    }
+
+    // the notice in the bottom-left corner, in capitals since the overlay
+    // font has no lowercase
+    fn draw_message(&mut self, text: &str) {
+        let layout = self.layout;
+        let unit = (layout.scale_y / 3.0).round().max(1.0);
+        let text = text.to_uppercase();
+        let width = (text.chars().count() as f32 * DEBUG_CELL_W + 1.0) * unit;
+        let height = (DEBUG_CELL_H + 1.0) * unit;
+        let (x, y) = (
+            layout.left + layout.scale_x,
+            layout.top + (FB_HEIGHT - 1) as f32 * layout.scale_y - height,
+        );
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let _ = self.canvas.fill_rect(FRect::new(x, y, width, height));
+        self.canvas.set_blend_mode(BlendMode::None);
+        self.draw_text(x + unit, y + unit, unit, &text, Color::RGB(255, 255, 255));
+    }
+
+    // `text` in the overlay font with its top-left corner at x, y in window
+    // pixels, each glyph pixel `unit` window pixels square
+    fn draw_text(&mut self, x: f32, y: f32, unit: f32, text: &str, color: Color) {
+        self.canvas.set_draw_color(color);
+        for (idx, c) in text.chars().enumerate() {
+            let left = x + idx as f32 * DEBUG_CELL_W * unit;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for bit in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - bit)) != 0 {
+                        let _ = self.canvas.fill_rect(FRect::new(
+                            left + bit as f32 * unit,
+                            y + row as f32 * unit,
+                            unit,
+                            unit,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // the font's glyph for the hex digit inside an overlay cell at x, y
+    fn draw_glyph(&mut self, x: f32, y: f32, digit: usize, color: Color) {
+        let layout = self.layout;
+        let px = OVERLAY_PIXEL;
+        self.canvas.set_draw_color(color);
+        for (line, bits) in FONT[digit * 5..digit * 5 + 5].iter().enumerate() {
+            for bit in 0..4 {
+                if bits & (0x80 >> bit) != 0 {
+                    let _ = self.canvas.fill_rect(layout.rect(
+                        x + (1 + bit) as f32 * px,
+                        y + (1 + line) as f32 * px,
+                        px,
+                        px,
+                    ));
+                }
+            }
+        }
+    }
+
+    // N: restart the VM with the next ROM of the playlist
+    fn next_rom(&mut self, vm: &mut VM) -> Result<(), String> {
+        self.playlist.advance();
+        vm.load_new_rom(self.playlist.rom())
+            .map_err(|e| format!("{}: {}", self.playlist.name(), e))?;
+        self.update_title(vm);
+        Ok(())
+    }
+
+    fn update_title(&mut self, vm: &VM) {
+        let _ = self
+            .canvas
+            .window_mut()
+            .set_title(&window_title(&self.playlist, vm));
+    }
+}
+
+// registers and the instruction about to execute, one line per step
+fn print_trace(vm: &VM) {
+    let pc = vm.pc as usize;
+    let opcode = match (vm.memory.get(pc), vm.memory.get(pc + 1)) {
+        (Some(&hi), Some(&lo)) => u16::from_be_bytes([hi, lo]),
+        _ => 0,
+    };
+    let mnemonic = disassemble(opcode);
+    let regs: Vec<String> = vm.v.iter().map(|v| format!("{:02X}", v)).collect();
+    println!(
+        "{:04X}  {:04X}  {:<18} V={} I={:04X} DT={:02X} ST={:02X}",
+        vm.pc,
+        opcode,
+        mnemonic,
+        regs.join(" "),
+        vm.i,
+        vm.delay_timer,
+        vm.sound_timer
+    );
+}
+
+// V0-VF, PC, I, SP, DT and ST, for the debug overlay
+fn debug_values(vm: &VM) -> [u16; DEBUG_VALUES] {
+    let mut values = [0; DEBUG_VALUES];
+    for (value, &v) in values.iter_mut().zip(&vm.v) {
+        *value = v as u16;
+    }
+    values[16..].copy_from_slice(&[
+        vm.pc,
+        vm.i,
+        vm.stack().len() as u16,
+        vm.delay_timer as u16,
+        vm.sound_timer as u16,
+    ]);
+    values
+}
+
+// ROM name and the live-switchable quirks
+fn window_title(playlist: &Playlist, vm: &VM) -> String {
+    let shift = if vm.quirks.shift_uses_vy { "Vy" } else { "Vx" };
+    format!("chip8-emu-rs - {} - shift: {} (F3)", playlist.name(), shift)
+}
+
+impl DisplaySink for SdlFrontend {
+    fn present(&mut self, vm: &VM) {
+        if self.trace {
+            print_trace(vm);
+        }
+        // clears the letterbox bars too
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        let layout = self.layout;
+        for (i, prev) in self.prev_framebuffer.iter_mut().enumerate() {
+            let (x, y) = (i as u32 % FB_WIDTH, i as u32 / FB_WIDTH);
+            // rows of the other field keep showing the previous frame
+            if !self.interlace || y % 2 == self.field {
+                *prev = ((*prev as f32) * 0.7).round() as u8;
+                if vm.pixel(x, y) {
+                    *prev = 0xFF;
+                }
+            }
+            // fades from the foreground back to the background, also after
+            // a 00E0, which clears vm.framebuffer outright
+            let v = *prev as u16;
+            let [r, g, b] = [0, 1, 2].map(|c| {
+                let (bg, fg) = (self.palette.bg()[c] as u16, self.palette.fg()[c] as u16);
+                ((bg * (255 - v) + fg * v) / 255) as u8
+            });
+            self.canvas.set_draw_color(Color::RGB(r, g, b));
+            let _ = self
+                .canvas
+                .fill_rect(layout.rect(x as f32, y as f32, 1.0, 1.0));
+        }
+        if self.show_memmap {
+            self.draw_memmap(vm);
+        }
+        if self.show_keypad {
+            self.draw_keypad(vm);
+        }
+        if self.show_stack {
+            self.draw_stack(vm);
+        }
+        if self.show_sprite {
+            self.draw_sprite(vm);
+        }
+        if self.show_debug {
+            self.draw_debug(vm);
+        }
+        if let Some((text, until)) = self.message.take()
+            && Instant::now() < until
+        {
+            self.draw_message(&text);
+            self.message = Some((text, until));
+        }
+        self.canvas.present();
+        self.field ^= 1;
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.message = Some((text.to_string(), Instant::now() + MESSAGE_TIME));
+    }
+}
+
+impl AudioSink for SdlFrontend {
+    fn set_beep(&mut self, on: bool) {
+        // a beep lasts while the sound timer runs, stretched to --min-beep
+        let now = Instant::now();
+        if on && !self.was_beeping {
+            self.beep_until = now + self.min_beep;
+        }
+        self.was_beeping = on;
+        self.beep_gate
+            .store(on || now < self.beep_until, Ordering::Relaxed);
+    }
+}
+
+impl InputSource for SdlFrontend {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let mut next_rom = false;
+        let mut resized = false;
+        let mut retitle = false;
+        let mut step_frame = false;
+        let mut control = Control::Continue;
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Control::Quit,
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    repeat: false,
+                    ..
+                } => next_rom = true,
+                // `.`: one frame while the debugger is paused
+                Event::KeyDown {
+                    keycode: Some(Keycode::Period),
+                    repeat: false,
+                    ..
+                } => {
+                    step_frame = true;
+                    self.step_held = 0;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => self.show_keypad = !self.show_keypad,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    repeat: false,
+                    ..
+                } => self.show_memmap = !self.show_memmap,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => self.show_stack = !self.show_stack,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    repeat: false,
+                    ..
+                } => self.show_sprite = !self.show_sprite,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => self.show_debug = !self.show_debug,
+                // compare both shift behaviours on the same ROM without a restart
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    vm.quirks.shift_uses_vy = !vm.quirks.shift_uses_vy;
+                    retitle = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    repeat: false,
+                    ..
+                } => control = Control::SaveState,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => control = Control::LoadState,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => control = Control::NextCheat,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    self.fullscreen = !self.fullscreen;
+                    let _ = self.canvas.window_mut().set_fullscreen(self.fullscreen);
+                }
+                Event::Window {
+                    win_event: WindowEvent::PixelSizeChanged(..),
+                    ..
+                } => resized = true,
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } if self.latch_taps => {
+                    if let Some(&(_, key)) = KEYMAP.iter().find(|&&(s, _)| s == scancode) {
+                        self.tapped[key] = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if resized && let Ok(size) = self.canvas.output_size() {
+            self.layout = Layout::fit(size, self.pixel_aspect);
+        }
+        if next_rom && let Err(msg) = self.next_rom(vm) {
+            eprintln!("Error: {}", msg);
+            return Control::Quit;
+        }
+        if retitle {
+            self.update_title(vm);
+        }
+        // The keypad mirrors which keys are down right now rather than
+        // following KeyDown/KeyUp events, so OS auto-repeat (KeyDown with
+        // `repeat` set) can't press a key twice and a release always clears
+        // it. FX0A and EX9E/EXA1 see one continuous press for as long as the
+        // key is held. The hotkeys above match `repeat: false` for the same
+        // reason.
+        // With --latch-taps a key pressed and released between two polls
+        // still shows as down for one frame, so EX9E doesn't miss the tap.
+        let keys = self.event_pump.keyboard_state();
+        for (scancode, key) in KEYMAP {
+            vm.set_key(key, keys.is_scancode_pressed(scancode) || self.tapped[key]);
+        }
+        self.tapped = [false; 16];
+        // held, it keeps stepping at a steady pace for scrubbing through an
+        // animation, whatever the OS repeat rate is
+        if keys.is_scancode_pressed(Scancode::Period) {
+            self.step_held += 1;
+            step_frame |= self.step_held.is_multiple_of(FRAME_STEP_REPEAT);
+        } else {
+            self.step_held = 0;
+        }
+        if keys.is_scancode_pressed(Scancode::Backspace) {
+            Control::Rewind
+        } else if step_frame {
+            Control::StepFrame
+        } else {
+            control
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use chip8_emu_rs::VM;
+
+// The debugger's `search`, for finding where a game keeps a value such as
+// the lives left: the first `search eq 3` keeps every address holding 3, and
+// each later refinement drops the candidates that don't match. `changed` and
+// `unchanged` compare with memory as the previous refinement saw it.
+pub struct Search {
+    // ascending
+    candidates: Vec<usize>,
+    // memory at the last refinement
+    previous: Vec<u8>,
+}
+
+// How to narrow the candidates
+#[derive(Clone, Copy)]
+pub enum Filter {
+    Eq(u8),
+    Changed,
+    Unchanged,
+}
+
+impl Search {
+    // Every address holding `value`; a search needs a value to start from
+    pub fn start(vm: &VM, value: u8) -> Self {
+        Search {
+            candidates: (0..vm.memory.len())
+                .filter(|&addr| vm.memory[addr] == value)
+                .collect(),
+            previous: vm.memory.clone(),
+        }
+    }
+
+    pub fn refine(&mut self, vm: &VM, filter: Filter) {
+        let (now, before) = (&vm.memory, &self.previous);
+        self.candidates.retain(|&addr| match filter {
+            Filter::Eq(value) => now.get(addr) == Some(&value),
+            Filter::Changed => now.get(addr) != before.get(addr),
+            Filter::Unchanged => now.get(addr) == before.get(addr),
+        });
+        self.previous = vm.memory.clone();
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
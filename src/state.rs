@@ -0,0 +1,270 @@
+// Serde support for the complete VM state, behind the `serde` feature.
+//
+// The VM goes through `VmState`, which carries a layout version so snapshots
+// written by older releases can be recognised and migrated. Byte buffers use
+// serde_bytes so binary formats store them as one blob instead of a sequence
+// of integers. Addresses (PC, I and the return addresses on the stack) are
+// written as big-endian byte pairs, the order opcodes are stored in, so their
+// layout is fixed by this file rather than left to the format; the other
+// fields are whatever the format makes of a plain integer.
+
+use alloc::borrow::Cow;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use serde::de::{Error as _, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::{LowMemory, MAX_MEMORY_SIZE, Quirks};
+use crate::instruction::Family;
+use crate::rng::Rng;
+use crate::vm::{FB_HEIGHT, MEMORY_SIZE, STACK_SIZE, VM};
+
+// bump whenever VmState changes shape
+pub const STATE_VERSION: u32 = 10;
+
+#[derive(Serialize, Deserialize)]
+struct VmState<'a> {
+    version: u32,
+    v: [u8; 16],
+    // big-endian, as are the return addresses
+    pc: [u8; 2],
+    i: [u8; 2],
+    #[serde(borrow, with = "serde_bytes")]
+    memory: Cow<'a, [u8]>,
+    #[serde(deserialize_with = "bounded_stack")]
+    stack: Vec<[u8; 2]>,
+    // one u64 per row, bit 63 is column 0
+    framebuffer: [u64; FB_HEIGHT as usize],
+    draw_flag: bool,
+    last_draw_collisions: u32,
+    keyboard: [bool; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    quirks: Quirks,
+    start_address: u16,
+    cpu_hz: f64,
+    rng: RngState,
+    cycles: u64,
+    frames: u64,
+    waiting_for_key: bool,
+    #[serde(borrow, with = "serde_bytes")]
+    rom: Cow<'a, [u8]>,
+    // (address, value), see VM::freeze
+    freezes: Cow<'a, [(u16, u8)]>,
+}
+
+// An injected generator is a function pointer and can't be stored; it comes
+// back as a seeded generator and the frontend can inject its own again with
+// `VM::set_rng`.
+#[derive(Serialize, Deserialize)]
+enum RngState {
+    External,
+    Seeded { seed: u64, state: u64 },
+    Fixed(u8),
+}
+
+// Why a snapshot was rejected. Deserializing reports it through the format's
+// own error type, so it arrives as that error's message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateError {
+    UnsupportedVersion { found: u32 },
+    MemorySize { size: usize },
+    // deeper than STACK_SIZE, or a return address outside memory
+    CorruptStack,
+    RomTooLarge { size: usize },
+    PcOutOfBounds { pc: u16 },
+    // a seeded generator in the all-zero state it never leaves or reaches
+    RngState,
+    ClockRate { hz: f64 },
+    FreezeOutOfBounds { addr: u16 },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::UnsupportedVersion { found } => write!(
+                f,
+                "unsupported VM state version {} (expected {})",
+                found, STATE_VERSION
+            ),
+            StateError::MemorySize { size } => write!(f, "invalid memory size {}", size),
+            StateError::CorruptStack => write!(
+                f,
+                "corrupt stack: more than {} return addresses or one outside memory",
+                STACK_SIZE
+            ),
+            StateError::RomTooLarge { size } => {
+                write!(f, "ROM image of {} bytes does not fit in memory", size)
+            }
+            StateError::PcOutOfBounds { pc } => {
+                write!(f, "program counter {:04X} is outside memory", pc)
+            }
+            StateError::RngState => write!(f, "random generator state is zero"),
+            StateError::ClockRate { hz } => {
+                write!(f, "clock rate {} Hz is not a positive number", hz)
+            }
+            StateError::FreezeOutOfBounds { addr } => {
+                write!(f, "frozen address {:04X} is outside memory", addr)
+            }
+        }
+    }
+}
+
+impl error::Error for StateError {}
+
+// The stack as a sequence that fails as soon as it grows past STACK_SIZE,
+// so a malformed snapshot can't make it allocate room for millions
+fn bounded_stack<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<[u8; 2]>, D::Error> {
+    struct StackVisitor;
+
+    impl<'de> Visitor<'de> for StackVisitor {
+        type Value = Vec<[u8; 2]>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "at most {} return addresses", STACK_SIZE)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<[u8; 2]>, A::Error> {
+            let mut stack = Vec::with_capacity(STACK_SIZE);
+            while let Some(ret) = seq.next_element()? {
+                if stack.len() == STACK_SIZE {
+                    return Err(A::Error::custom(StateError::CorruptStack));
+                }
+                stack.push(ret);
+            }
+            Ok(stack)
+        }
+    }
+
+    deserializer.deserialize_seq(StackVisitor)
+}
+
+impl Serialize for VM {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        VmState {
+            version: STATE_VERSION,
+            v: self.v,
+            pc: self.pc.to_be_bytes(),
+            i: self.i.to_be_bytes(),
+            memory: Cow::Borrowed(&self.memory),
+            stack: self.stack().iter().map(|ret| ret.to_be_bytes()).collect(),
+            framebuffer: self.framebuffer,
+            draw_flag: self.draw_flag,
+            last_draw_collisions: self.last_draw_collisions,
+            keyboard: self.keyboard,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            quirks: self.quirks,
+            start_address: self.start_address,
+            cpu_hz: self.cpu_hz,
+            rng: match self.rng {
+                Rng::External(_) => RngState::External,
+                Rng::Seeded { seed, state } => RngState::Seeded { seed, state },
+                Rng::Fixed(byte) => RngState::Fixed(byte),
+            },
+            cycles: self.cycles,
+            frames: self.frames,
+            waiting_for_key: self.waiting_for_key,
+            rom: Cow::Borrowed(&self.rom),
+            freezes: Cow::Borrowed(&self.freezes),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for VM {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = VmState::deserialize(deserializer)?;
+        let invalid = |e: StateError| D::Error::custom(e);
+        if state.version != STATE_VERSION {
+            return Err(invalid(StateError::UnsupportedVersion {
+                found: state.version,
+            }));
+        }
+
+        // the same invariants VmConfig::validate and load_rom enforce, and
+        // none of the registers pointing outside memory. I isn't checked: it
+        // can legitimately run past the end with FX1E, and every access
+        // through it is bounds-checked anyway.
+        let memory = state.memory.into_owned();
+        if !(MEMORY_SIZE..=MAX_MEMORY_SIZE).contains(&memory.len()) {
+            return Err(invalid(StateError::MemorySize { size: memory.len() }));
+        }
+        let mut stack = [0; STACK_SIZE];
+        for (slot, &ret) in stack.iter_mut().zip(&state.stack) {
+            *slot = u16::from_be_bytes(ret);
+        }
+        if stack[..state.stack.len()]
+            .iter()
+            .any(|&ret| ret as usize >= memory.len())
+        {
+            return Err(invalid(StateError::CorruptStack));
+        }
+        let pc = u16::from_be_bytes(state.pc);
+        if state.start_address as usize + state.rom.len() > memory.len() {
+            return Err(invalid(StateError::RomTooLarge {
+                size: state.rom.len(),
+            }));
+        }
+        if pc as usize + 1 >= memory.len() {
+            return Err(invalid(StateError::PcOutOfBounds { pc }));
+        }
+        if let RngState::Seeded { state: 0, .. } = state.rng {
+            return Err(invalid(StateError::RngState));
+        }
+        if !(state.cpu_hz.is_finite() && state.cpu_hz > 0.0) {
+            return Err(invalid(StateError::ClockRate { hz: state.cpu_hz }));
+        }
+        if let Some(&(addr, _)) = state
+            .freezes
+            .iter()
+            .find(|&&(addr, _)| addr as usize >= memory.len())
+        {
+            return Err(invalid(StateError::FreezeOutOfBounds { addr }));
+        }
+
+        Ok(VM {
+            v: state.v,
+            pc,
+            i: u16::from_be_bytes(state.i),
+            memory,
+            stack,
+            sp: state.stack.len(),
+            framebuffer: state.framebuffer,
+            draw_flag: state.draw_flag,
+            last_draw_collisions: state.last_draw_collisions,
+            keyboard: state.keyboard,
+            keys_seen: state.keyboard,
+            delay_timer: state.delay_timer,
+            sound_timer: state.sound_timer,
+            quirks: state.quirks,
+            start_address: state.start_address,
+            cpu_hz: state.cpu_hz,
+            rng: match state.rng {
+                RngState::External => Rng::seeded(0),
+                RngState::Seeded { seed, state } => Rng::Seeded { seed, state },
+                RngState::Fixed(byte) => Rng::Fixed(byte),
+            },
+            cycles: state.cycles,
+            frames: state.frames,
+            rom: state.rom.into_owned(),
+            observer: None,
+            strict_sys: false,
+            strict_jumps: false,
+            halt_on_spin: false,
+            low_memory: LowMemory::Off,
+            pc_counts: None,
+            executed: None,
+            strict_smc: false,
+            family_counts: [0; Family::ALL.len()],
+            watchpoints: None,
+            watch_hit: None,
+            waiting_for_key: state.waiting_for_key,
+            freezes: state.freezes.into_owned(),
+            collision_log: VecDeque::new(),
+            collision_log_len: 0,
+        })
+    }
+}
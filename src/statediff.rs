@@ -0,0 +1,139 @@
+use chip8_emu_rs::{FB_HEIGHT, FB_WIDTH, VM};
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::savestate::{self, Header};
+use crate::sha1::hex;
+
+// --diff-state A B: what differs between two save states. Registers and
+// timers go side by side with a '*' on the rows that differ, then the stack,
+// the memory addresses that differ grouped into ranges, and the screen with
+// '#' for every pixel lit in one state and not the other. Returns whether the
+// states differ.
+pub fn diff_files(a: &Path, b: &Path) -> Result<bool, String> {
+    let (header_a, vm_a) = savestate::read(a)?;
+    let (header_b, vm_b) = savestate::read(b)?;
+    println!("A: {}\nB: {}\n", a.display(), b.display());
+    println!("{:<9}{:<20}B", "", "A");
+    let mut differs = header_rows(&header_a, &header_b);
+    differs |= register_rows(&vm_a, &vm_b);
+    differs |= stack_rows(&vm_a, &vm_b);
+    differs |= memory_diff(&vm_a, &vm_b);
+    differs |= screen_diff(&vm_a, &vm_b);
+    if !differs {
+        println!("The states are identical.");
+    }
+    Ok(differs)
+}
+
+// one side-by-side row, marked when the two sides differ
+fn row<T: PartialEq + Display>(name: &str, a: T, b: T) -> bool {
+    let mark = if a == b { ' ' } else { '*' };
+    println!("{} {:<7}{:<20}{}", mark, name, a.to_string(), b);
+    a != b
+}
+
+fn header_rows(a: &Header, b: &Header) -> bool {
+    let mut differs = row("ROM", &a.rom_name, &b.rom_name);
+    differs |= row("SHA-1", &hex(&a.rom_sha1)[..8], &hex(&b.rom_sha1)[..8]);
+    differs
+}
+
+fn register_rows(a: &VM, b: &VM) -> bool {
+    let mut differs = false;
+    for (idx, (va, vb)) in a.v.iter().zip(b.v).enumerate() {
+        differs |= row(
+            &format!("V{:X}", idx),
+            format!("{:02X}", va),
+            format!("{:02X}", vb),
+        );
+    }
+    let hex16 = |value: u16| format!("{:04X}", value);
+    let hex8 = |value: u8| format!("{:02X}", value);
+    differs |= row("I", hex16(a.i), hex16(b.i));
+    differs |= row("PC", hex16(a.pc), hex16(b.pc));
+    differs |= row("DT", hex8(a.delay_timer), hex8(b.delay_timer));
+    differs |= row("ST", hex8(a.sound_timer), hex8(b.sound_timer));
+    differs |= row("SP", a.stack().len(), b.stack().len());
+    differs |= row("cycles", a.cycles(), b.cycles());
+    differs |= row("frames", a.frames(), b.frames());
+    let quirks = |vm: &VM| crate::preset_name(&vm.quirks).unwrap_or("custom");
+    // two custom sets of quirks can differ under the same name
+    differs |= row("quirks", quirks(a), quirks(b)) | (a.quirks != b.quirks);
+    differs
+}
+
+fn stack_rows(a: &VM, b: &VM) -> bool {
+    let mut differs = false;
+    let depth = a.stack().len().max(b.stack().len());
+    let entry = |vm: &VM, idx: usize| {
+        vm.stack()
+            .get(idx)
+            .map_or("-".to_string(), |addr| format!("{:04X}", addr))
+    };
+    for idx in 0..depth {
+        differs |= row(&format!("S{}", idx), entry(a, idx), entry(b, idx));
+    }
+    differs
+}
+
+fn memory_diff(a: &VM, b: &VM) -> bool {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut count = 0;
+    for (addr, (x, y)) in a.memory.iter().zip(&b.memory).enumerate() {
+        if x == y {
+            continue;
+        }
+        count += 1;
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == addr => *end = addr,
+            _ => ranges.push((addr, addr)),
+        }
+    }
+    println!();
+    if a.memory.len() != b.memory.len() {
+        println!(
+            "Memory sizes differ: {} and {} bytes; comparing the first {}.",
+            a.memory.len(),
+            b.memory.len(),
+            a.memory.len().min(b.memory.len())
+        );
+    }
+    if count == 0 {
+        println!("Memory: no bytes differ.");
+    } else {
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    format!("{:04X}", start)
+                } else {
+                    format!("{:04X}-{:04X}", start, end)
+                }
+            })
+            .collect();
+        println!("Memory: {} byte(s) differ at {}", count, ranges.join(", "));
+    }
+    count > 0 || a.memory.len() != b.memory.len()
+}
+
+fn screen_diff(a: &VM, b: &VM) -> bool {
+    let mut grid = String::new();
+    let mut count = 0;
+    for y in 0..FB_HEIGHT {
+        for x in 0..FB_WIDTH {
+            let differs = a.pixel(x, y) != b.pixel(x, y);
+            count += differs as u32;
+            grid.push(if differs { '#' } else { '.' });
+        }
+        grid.push('\n');
+    }
+    println!();
+    if count == 0 {
+        println!("Screen: no pixels differ.");
+        return false;
+    }
+    println!("Screen: {} pixel(s) differ:", count);
+    print!("{}", grid);
+    true
+}
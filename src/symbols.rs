@@ -0,0 +1,217 @@
+// Labels for a ROM's addresses, read from a symbol file so the disassembler,
+// debugger and profiler can show `CALL draw_score` instead of `CALL 0x312`.
+// One label per line:
+//
+//     draw_score = 0x312
+//
+// Octo and the tools around it write the same pairs in other shapes, so the
+// name and the address may come in either order, separated by '=', ':' or
+// spaces, as `draw_score 0x312`, `0x312 draw_score` or `:const draw_score
+// 786`. Addresses are numbers as the assembler reads them. Blank lines and
+// lines starting with '#' or ';' are skipped.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use crate::asm::parse_number;
+use crate::disasm;
+use crate::instruction::Instruction;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for SymbolError {}
+
+// Both directions of the table. An address may have several names, the
+// first one read is the one shown.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+    addrs: BTreeMap<String, u16>,
+}
+
+// close matches offered for a misspelled label, at most
+const SUGGESTIONS: usize = 5;
+
+impl Symbols {
+    pub fn parse(text: &str) -> Result<Symbols, SymbolError> {
+        let mut symbols = Symbols::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let error = |message: String| SymbolError {
+                line: idx + 1,
+                message,
+            };
+            let words: Vec<&str> = line
+                .split(|c: char| c == '=' || c == ':' || c.is_whitespace())
+                .filter(|word| !word.is_empty() && *word != "const")
+                .collect();
+            let (name, addr) = match words.as_slice() {
+                [first, second] => match (parse_number(first), parse_number(second)) {
+                    (None, Some(addr)) => (*first, addr),
+                    (Some(addr), None) => (*second, addr),
+                    _ => {
+                        return Err(error(format!(
+                            "expected a name and an address, got '{}'",
+                            line
+                        )));
+                    }
+                },
+                _ => return Err(error(format!("expected NAME = ADDR, got '{}'", line))),
+            };
+            if !is_name(name) {
+                return Err(error(format!("invalid label '{}'", name)));
+            }
+            let addr = u16::try_from(addr)
+                .map_err(|_| error(format!("address {:#X} is past 0xFFFF", addr)))?;
+            if symbols.addrs.get(name).is_some_and(|&known| known != addr) {
+                return Err(error(format!("label '{}' is defined twice", name)));
+            }
+            symbols.insert(name, addr);
+        }
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, name: &str, addr: u16) {
+        self.addrs.insert(name.to_string(), addr);
+        self.names.entry(addr).or_insert_with(|| name.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    // the label at exactly `addr`
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    pub fn addr(&self, name: &str) -> Option<u16> {
+        self.addrs.get(name).copied()
+    }
+
+    // The closest label at or before `addr` and how far past it `addr` is,
+    // for the code between one label and the next
+    pub fn locate(&self, addr: u16) -> Option<(&str, u16)> {
+        self.names
+            .range(..=addr)
+            .next_back()
+            .map(|(&start, name)| (name.as_str(), addr - start))
+    }
+
+    // `draw_score` or `draw_score+0x4`, if there's a label at or before `addr`
+    pub fn describe(&self, addr: u16) -> Option<String> {
+        self.locate(addr).map(|(name, offset)| match offset {
+            0 => name.to_string(),
+            offset => format!("{}+{:#X}", name, offset),
+        })
+    }
+
+    // The labels `name` could be a typo of, closest first
+    pub fn close_matches(&self, name: &str) -> Vec<&str> {
+        let name = name.to_ascii_lowercase();
+        let mut matches: Vec<(usize, &str)> = self
+            .addrs
+            .keys()
+            .filter_map(|label| {
+                let lower = label.to_ascii_lowercase();
+                let distance = edit_distance(&name, &lower);
+                let close = distance <= (name.len() / 3).max(1)
+                    || lower.contains(&name)
+                    || name.contains(&lower);
+                close.then_some((distance, label.as_str()))
+            })
+            .collect();
+        matches.sort();
+        matches.truncate(SUGGESTIONS);
+        matches.into_iter().map(|(_, label)| label).collect()
+    }
+
+    // Like `disassemble`, with an address operand that has a label shown by
+    // name and the address moved to a comment: `CALL draw_score ; 0x312`
+    pub fn disassemble(&self, word: u16) -> String {
+        let text = disasm::disassemble(word);
+        let target = match Instruction::decode(word) {
+            Instruction::Jp(nnn)
+            | Instruction::Call(nnn)
+            | Instruction::LdI(nnn)
+            | Instruction::JpV0(nnn) => nnn,
+            _ => return text,
+        };
+        let operand = format!("0x{:03X}", target);
+        match (self.name(target), text.strip_suffix(operand.as_str())) {
+            (Some(name), Some(rest)) => format!("{}{} ; {}", rest, name, operand),
+            _ => text,
+        }
+    }
+
+    // `0x0200  2312  CALL draw_score ; 0x312`
+    pub fn disassemble_at(&self, addr: u16, word: u16) -> String {
+        format!("0x{:04X}  {:04X}  {}", addr, word, self.disassemble(word))
+    }
+
+    // `disasm::disassemble_rom` with labels: every labelled address gets a
+    // `name:` line of its own before it
+    pub fn disassemble_rom(&self, rom: &[u8], start: u16) -> Vec<String> {
+        let mut lines = Vec::with_capacity(rom.len().div_ceil(2));
+        let mut chunks = rom.chunks_exact(2);
+        let mut addr = start;
+        for word in chunks.by_ref() {
+            if let Some(name) = self.name(addr) {
+                lines.push(format!("{}:", name));
+            }
+            lines.push(self.disassemble_at(addr, u16::from_be_bytes([word[0], word[1]])));
+            addr = addr.wrapping_add(2);
+        }
+        if let [byte] = chunks.remainder() {
+            if let Some(name) = self.name(addr) {
+                lines.push(format!("{}:", name));
+            }
+            lines.push(format!("0x{:04X}  {:02X}    DB 0x{:02X}", addr, byte, byte));
+        }
+        lines
+    }
+}
+
+// letters, digits, '_' and '-', not starting with a digit
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// Levenshtein distance, two rows at a time
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = Vec::with_capacity(b.len() + 1);
+        current.push(i + 1);
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,73 @@
+// Named color schemes for --theme
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    // the emulator's original look: dark pixels on amber
+    Default,
+    Gameboy,
+    Amber,
+    C64,
+    Matrix,
+}
+
+pub const THEMES: [(&str, Theme); 5] = [
+    ("default", Theme::Default),
+    ("gameboy", Theme::Gameboy),
+    ("amber", Theme::Amber),
+    ("c64", Theme::C64),
+    ("matrix", Theme::Matrix),
+];
+
+pub type Rgb = [u8; 3];
+
+// Screen colors indexed by pixel value: 0 is the background, 1 the
+// foreground, and 2 and 3 the extra colors of XO-CHIP's second bitplane
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Palette {
+    pub fn bg(&self) -> Rgb {
+        self.0[0]
+    }
+
+    pub fn fg(&self) -> Rgb {
+        self.0[1]
+    }
+}
+
+impl Theme {
+    pub fn from_name(name: &str) -> Result<Theme, String> {
+        THEMES
+            .iter()
+            .find(|(theme_name, _)| theme_name.eq_ignore_ascii_case(name))
+            .map(|&(_, theme)| theme)
+            .ok_or_else(|| {
+                let names: Vec<&str> = THEMES.iter().map(|(name, _)| *name).collect();
+                format!("unknown theme '{}' (one of: {}).", name, names.join(", "))
+            })
+    }
+
+    pub fn palette(self) -> Palette {
+        Palette(match self {
+            Theme::Default => [[255, 176, 0], [60, 57, 60], [170, 110, 20], [120, 90, 50]],
+            Theme::Gameboy => [[155, 188, 15], [15, 56, 15], [139, 172, 15], [48, 98, 48]],
+            Theme::Amber => [[24, 14, 0], [255, 176, 0], [150, 90, 0], [255, 222, 140]],
+            Theme::C64 => [
+                [64, 49, 141],
+                [120, 105, 196],
+                [184, 105, 98],
+                [255, 255, 255],
+            ],
+            Theme::Matrix => [[0, 10, 0], [0, 255, 65], [0, 143, 17], [200, 255, 200]],
+        })
+    }
+}
+
+// RRGGBB, with or without a leading '#'
+pub fn parse_color(value: &str) -> Option<Rgb> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+}
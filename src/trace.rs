@@ -0,0 +1,238 @@
+use chip8_emu_rs::{Chip8Error, Instruction, StepInfo, VmObserver};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// --trace: one line per executed instruction,
+//
+//     <cycle> <pc> <opcode>  <mnemonic> ; <registers it changed>
+//
+// narrowed down by kind of instruction, PC range and a start address. With a
+// ring size only the last lines that passed are kept, and they're written
+// out if the VM faults.
+pub struct TraceOptions {
+    pub path: PathBuf,
+    // empty for every kind
+    pub kinds: Vec<Kind>,
+    pub range: Option<RangeInclusive<u16>>,
+    pub after: Option<u16>,
+    pub ring: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Draw,
+    Cls,
+    Call,
+    Ret,
+    Jump,
+    Skip,
+    Key,
+    Timer,
+    Rand,
+    Mem,
+    I,
+    Alu,
+}
+
+// --trace-filter names, in the order the help lists them
+const KINDS: [(&str, Kind); 12] = [
+    ("draw", Kind::Draw),
+    ("cls", Kind::Cls),
+    ("call", Kind::Call),
+    ("ret", Kind::Ret),
+    ("jump", Kind::Jump),
+    ("skip", Kind::Skip),
+    ("key", Kind::Key),
+    ("timer", Kind::Timer),
+    ("rand", Kind::Rand),
+    ("mem", Kind::Mem),
+    ("i", Kind::I),
+    ("alu", Kind::Alu),
+];
+
+impl Kind {
+    pub fn from_name(name: &str) -> Option<Kind> {
+        KINDS
+            .iter()
+            .find(|(kind_name, _)| kind_name.eq_ignore_ascii_case(name))
+            .map(|&(_, kind)| kind)
+    }
+
+    fn matches(self, instruction: Instruction) -> bool {
+        use Instruction::*;
+        match self {
+            Kind::Draw => matches!(instruction, Drw { .. }),
+            Kind::Cls => instruction == Cls,
+            Kind::Call => matches!(instruction, Call(_)),
+            Kind::Ret => instruction == Ret,
+            Kind::Jump => matches!(instruction, Jp(_) | JpV0(_)),
+            Kind::Skip => matches!(
+                instruction,
+                SeVxByte { .. } | SneVxByte { .. } | SeVxVy { .. } | SneVxVy { .. }
+            ),
+            Kind::Key => matches!(instruction, Skp { .. } | Sknp { .. } | LdVxK { .. }),
+            Kind::Timer => matches!(instruction, LdVxDt { .. } | LdDtVx { .. } | LdStVx { .. }),
+            Kind::Rand => matches!(instruction, Rnd { .. }),
+            Kind::Mem => matches!(instruction, LdBVx { .. } | LdIVx { .. } | LdVxI { .. }),
+            Kind::I => matches!(instruction, LdI(_) | AddIVx { .. } | LdFVx { .. }),
+            Kind::Alu => matches!(
+                instruction,
+                LdVxByte { .. }
+                    | AddVxByte { .. }
+                    | LdVxVy { .. }
+                    | Or { .. }
+                    | And { .. }
+                    | Xor { .. }
+                    | AddVxVy { .. }
+                    | Sub { .. }
+                    | Shr { .. }
+                    | Subn { .. }
+                    | Shl { .. }
+            ),
+        }
+    }
+}
+
+pub fn kind_names() -> String {
+    let names: Vec<&str> = KINDS.iter().map(|&(name, _)| name).collect();
+    names.join(", ")
+}
+
+pub struct Trace {
+    out: BufWriter<File>,
+    path: PathBuf,
+    kinds: Vec<Kind>,
+    range: Option<RangeInclusive<u16>>,
+    // cleared once the PC gets there
+    after: Option<u16>,
+    ring: Option<(VecDeque<StepInfo>, usize)>,
+    // the first write that failed; tracing stops there
+    error: Option<io::Error>,
+}
+
+impl Trace {
+    pub fn create(options: &TraceOptions) -> io::Result<Trace> {
+        Ok(Trace {
+            // big enough that the file sees a write every few thousand lines
+            out: BufWriter::with_capacity(1 << 16, File::create(&options.path)?),
+            path: options.path.clone(),
+            kinds: options.kinds.clone(),
+            range: options.range.clone(),
+            after: options.after,
+            ring: options
+                .ring
+                .map(|size| (VecDeque::with_capacity(size), size)),
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn record(&mut self, info: &StepInfo) {
+        let pc = info.step.pc;
+        if let Some(addr) = self.after {
+            if pc != addr {
+                return;
+            }
+            self.after = None;
+        }
+        if self
+            .range
+            .as_ref()
+            .is_some_and(|range| !range.contains(&pc))
+            || !(self.kinds.is_empty()
+                || self
+                    .kinds
+                    .iter()
+                    .any(|kind| kind.matches(info.step.instruction)))
+        {
+            return;
+        }
+        match &mut self.ring {
+            Some((lines, size)) => {
+                if lines.len() == *size {
+                    lines.pop_front();
+                }
+                lines.push_back(*info);
+            }
+            None => {
+                if self.error.is_none()
+                    && let Err(e) = write_line(&mut self.out, info)
+                {
+                    self.error = Some(e);
+                }
+            }
+        }
+    }
+
+    // Flushes the file, writing the ring out first if the VM faulted with
+    // `fault`. Returns how many ring lines were written.
+    pub fn finish(&mut self, fault: Option<&Chip8Error>) -> io::Result<usize> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let mut written = 0;
+        if let (Some((lines, _)), Some(fault)) = (&self.ring, fault) {
+            for info in lines {
+                write_line(&mut self.out, info)?;
+            }
+            writeln!(self.out, "# {}", fault)?;
+            written = lines.len();
+        }
+        self.out.flush()?;
+        Ok(written)
+    }
+}
+
+pub fn write_line(out: &mut impl Write, info: &StepInfo) -> io::Result<()> {
+    let step = &info.step;
+    write!(
+        out,
+        "{} {:04X} {:04X}  {}",
+        info.cycle, step.pc, step.opcode, step.instruction
+    )?;
+    use Instruction::*;
+    match step.instruction {
+        LdVxByte { x, .. }
+        | AddVxByte { x, .. }
+        | LdVxVy { x, .. }
+        | Or { x, .. }
+        | And { x, .. }
+        | Xor { x, .. }
+        | Rnd { x, .. }
+        | LdVxDt { x } => write!(out, " ; V{:X}={:02X}", x, info.v[x as usize])?,
+        LdVxK { .. } if step.waiting_for_key => write!(out, " ; waiting")?,
+        LdVxK { x } => write!(out, " ; V{:X}={:02X}", x, info.v[x as usize])?,
+        AddVxVy { x, .. } | Sub { x, .. } | Shr { x, .. } | Subn { x, .. } | Shl { x, .. } => {
+            write!(
+                out,
+                " ; V{:X}={:02X} VF={:02X}",
+                x, info.v[x as usize], info.v[0xF]
+            )?
+        }
+        Drw { .. } => write!(out, " ; VF={:02X}", info.v[0xF])?,
+        LdI(_) | AddIVx { .. } | LdFVx { .. } | LdBVx { .. } | LdIVx { .. } | LdVxI { .. } => {
+            write!(out, " ; I={:03X}", info.i)?
+        }
+        _ if step.skipped => write!(out, " ; skip")?,
+        _ => {}
+    }
+    writeln!(out)
+}
+
+// The observer half of a trace, sharing it with main so it can be finished
+// after the VM stops
+pub struct Tracer(pub Rc<RefCell<Trace>>);
+
+impl VmObserver for Tracer {
+    fn on_step(&mut self, info: &StepInfo) {
+        self.0.borrow_mut().record(info);
+    }
+}
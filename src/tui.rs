@@ -0,0 +1,258 @@
+use chip8_emu_rs::{AudioSink, Control, DisplaySink, FB_HEIGHT, FB_WIDTH, InputSource, VM};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crate::playlist::Playlist;
+use crate::theme::{Palette, Rgb};
+
+// Terminals only report key presses, so a key counts as held until this long
+// after its last press. Auto-repeat refreshes a held key, but only once the
+// repeat delay has passed, so holding a key can briefly read as released.
+const KEY_HOLD: Duration = Duration::from_millis(200);
+
+// `.` steps one frame per press, but auto-repeat is capped to this pace so
+// holding it scrubs at 10 frames a second like the SDL frontend
+const FRAME_STEP_REPEAT: Duration = Duration::from_millis(100);
+
+// how long a show_message notice replaces the status line
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+// same layout as the SDL frontend: the left side of a QWERTY keyboard
+const KEYMAP: [(char, usize); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+// Draws the screen with half blocks, two pixel rows per line of text, so it
+// fits in 64x16 cells. Beeps with the terminal bell.
+pub struct TuiFrontend {
+    out: Stdout,
+    playlist: Playlist,
+    palette: Palette,
+    // last press of each CHIP-8 key, see KEY_HOLD
+    pressed_at: [Option<Instant>; 16],
+    // the last `.` that stepped a frame, see FRAME_STEP_REPEAT
+    stepped_at: Option<Instant>,
+    // the last Backspace, held like a CHIP-8 key for KEY_HOLD
+    rewind_at: Option<Instant>,
+    // redraw even if the framebuffer didn't change (first frame, resize)
+    dirty: bool,
+    was_beeping: bool,
+    // printed once the terminal is restored, where it stays readable
+    error: Option<String>,
+    // the latest show_message notice and when it goes away
+    message: Option<(String, Instant)>,
+}
+
+impl TuiFrontend {
+    pub fn new(playlist: Playlist, palette: Palette) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+        Ok(TuiFrontend {
+            out,
+            playlist,
+            palette,
+            pressed_at: [None; 16],
+            stepped_at: None,
+            rewind_at: None,
+            dirty: true,
+            was_beeping: false,
+            error: None,
+            message: None,
+        })
+    }
+
+    fn draw(&mut self, vm: &VM) -> io::Result<()> {
+        let (bg, fg) = (rgb(self.palette.bg()), rgb(self.palette.fg()));
+        for row in 0..FB_HEIGHT / 2 {
+            queue!(self.out, MoveTo(0, row as u16))?;
+            // colors are only sent when they change, which keeps the output
+            // small enough for a slow SSH link
+            let mut colors = None;
+            for x in 0..FB_WIDTH {
+                let top = if vm.pixel(x, 2 * row) { fg } else { bg };
+                let bottom = if vm.pixel(x, 2 * row + 1) { fg } else { bg };
+                if colors != Some((top, bottom)) {
+                    queue!(
+                        self.out,
+                        SetForegroundColor(top),
+                        SetBackgroundColor(bottom)
+                    )?;
+                    colors = Some((top, bottom));
+                }
+                queue!(self.out, Print('▀'))?;
+            }
+        }
+        let status = match &self.message {
+            Some((text, _)) => text.clone(),
+            None => format!(
+                "{} - Esc quits, N next ROM, F8/F9 save/load, F10 cheats",
+                self.playlist.name()
+            ),
+        };
+        queue!(
+            self.out,
+            ResetColor,
+            MoveTo(0, (FB_HEIGHT / 2) as u16),
+            Clear(ClearType::CurrentLine),
+            Print(status),
+        )?;
+        self.out.flush()
+    }
+
+    // N: restart the VM with the next ROM of the playlist
+    fn next_rom(&mut self, vm: &mut VM) -> Result<(), String> {
+        self.playlist.advance();
+        vm.load_new_rom(self.playlist.rom())
+            .map_err(|e| format!("{}: {}", self.playlist.name(), e))?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn key_event(&mut self, key: KeyEvent, vm: &mut VM) -> Control {
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || ctrl_c {
+            return Control::Quit;
+        }
+        if key.code == KeyCode::Backspace {
+            self.rewind_at = match key.kind {
+                KeyEventKind::Release => None,
+                _ => Some(Instant::now()),
+            };
+        }
+        match key.code {
+            KeyCode::F(8) if key.kind == KeyEventKind::Press => return Control::SaveState,
+            KeyCode::F(9) if key.kind == KeyEventKind::Press => return Control::LoadState,
+            KeyCode::F(10) if key.kind == KeyEventKind::Press => return Control::NextCheat,
+            _ => {}
+        }
+        let KeyCode::Char(c) = key.code else {
+            return Control::Continue;
+        };
+        let c = c.to_ascii_lowercase();
+        if c == 'n' && key.kind == KeyEventKind::Press {
+            if let Err(msg) = self.next_rom(vm) {
+                self.error = Some(msg);
+                return Control::Quit;
+            }
+            return Control::Continue;
+        }
+        // one frame while the debugger is paused
+        if c == '.' && key.kind != KeyEventKind::Release {
+            let now = Instant::now();
+            if self
+                .stepped_at
+                .is_some_and(|at| now - at < FRAME_STEP_REPEAT)
+            {
+                return Control::Continue;
+            }
+            self.stepped_at = Some(now);
+            return Control::StepFrame;
+        }
+        if let Some(&(_, chip8_key)) = KEYMAP.iter().find(|(k, _)| *k == c) {
+            // only Windows terminals report releases
+            self.pressed_at[chip8_key] = match key.kind {
+                KeyEventKind::Release => None,
+                _ => Some(Instant::now()),
+            };
+        }
+        Control::Continue
+    }
+}
+
+fn rgb([r, g, b]: Rgb) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+impl Drop for TuiFrontend {
+    // gives the terminal back even when the run ends with an error or a panic
+    fn drop(&mut self) {
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+        if let Some(msg) = &self.error {
+            eprintln!("Error: {}", msg);
+        }
+    }
+}
+
+impl DisplaySink for TuiFrontend {
+    fn present(&mut self, vm: &VM) {
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|(_, until)| Instant::now() >= *until)
+        {
+            self.message = None;
+            self.dirty = true;
+        }
+        if vm.draw_flag || self.dirty {
+            self.dirty = false;
+            let _ = self.draw(vm);
+        }
+    }
+
+    fn show_message(&mut self, text: &str) {
+        self.message = Some((text.to_string(), Instant::now() + MESSAGE_TIME));
+        self.dirty = true;
+    }
+}
+
+impl AudioSink for TuiFrontend {
+    fn set_beep(&mut self, on: bool) {
+        // the bell can't be held, so it rings once at the start of each beep
+        if on && !self.was_beeping {
+            let _ = write!(self.out, "\x07");
+            let _ = self.out.flush();
+        }
+        self.was_beeping = on;
+    }
+}
+
+impl InputSource for TuiFrontend {
+    fn poll(&mut self, vm: &mut VM) -> Control {
+        let mut control = Control::Continue;
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            match event::read() {
+                Ok(Event::Key(key)) => match self.key_event(key, vm) {
+                    Control::Quit => return Control::Quit,
+                    Control::Continue => {}
+                    other => control = other,
+                },
+                Ok(Event::Resize(..)) => {
+                    let _ = execute!(self.out, Clear(ClearType::All));
+                    self.dirty = true;
+                }
+                _ => {}
+            }
+        }
+        let now = Instant::now();
+        for (key, pressed_at) in self.pressed_at.iter().enumerate() {
+            vm.set_key(key, pressed_at.is_some_and(|at| now - at < KEY_HOLD));
+        }
+        if self.rewind_at.is_some_and(|at| now - at < KEY_HOLD) {
+            return Control::Rewind;
+        }
+        control
+    }
+}
@@ -0,0 +1,1349 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{self, BufWriter, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::config::{LowMemory, Quirks, VmConfig};
+use crate::error::Chip8Error;
+use crate::instruction::{Family, Instruction};
+use crate::observer::{CodeWrite, FrameInfo, StepInfo, VmObserver};
+use crate::rng::Rng;
+use crate::watch::{Access, WatchHit, Watchpoint};
+
+// CHIP-8 framebuffer size
+pub const FB_WIDTH: u32 = 64;
+pub const FB_HEIGHT: u32 = 32;
+
+// default RAM size; VmConfig can raise it up to 64K
+pub const MEMORY_SIZE: usize = 4096;
+pub const ROM_START: usize = 0x200;
+// subroutine nesting levels, as on the COSMAC VIP
+pub const STACK_SIZE: usize = 16;
+
+pub const FONT_START: usize = 0x050;
+pub const FONT_BYTES: usize = 16 * 5;
+pub const FONT: [u8; FONT_BYTES] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// What a single `step` executed; faults are reported through the error instead.
+// Kept small and Copy since it's returned on every instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepResult {
+    // address the instruction was fetched from
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    // CLS or DRW touched the framebuffer
+    pub drew: bool,
+    // FX0A found no key down and will run again
+    pub waiting_for_key: bool,
+    // a skip instruction's condition held
+    pub skipped: bool,
+}
+
+// A DRW that turned pixels off and so set VF, as VM::collision_log keeps them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Collision {
+    // VM::cycles right after the DRW
+    pub cycle: u64,
+    pub pc: u16,
+    // where the sprite went, Vx and Vy wrapped onto the screen
+    pub x: u8,
+    pub y: u8,
+    // pixels it turned off
+    pub pixels: u32,
+}
+
+// What one `run_frame` did. A frame ends early when the program waits for a
+// key, halts or faults, since running the rest of it would change nothing.
+#[derive(Debug)]
+pub struct FrameOutcome {
+    // instructions actually executed, the faulting one included
+    pub instructions: u32,
+    // some instruction changed the framebuffer
+    pub drew: bool,
+    // FX0A found no key down
+    pub waiting_for_key: bool,
+    // the program jumped to itself, the usual way a CHIP-8 program ends
+    pub halted: bool,
+    // the watchpoint an instruction touched, which ended the frame; taken out
+    // of the VM, so the next frame runs in full
+    pub watch_hit: Option<WatchHit>,
+    pub fault: Option<Chip8Error>,
+}
+
+// What a running program can change, copied out by `VM::snapshot` and put
+// back by `VM::restore`. Configuration, the observer, watchpoints, frozen
+// addresses and the profiling counts stay as they are.
+#[derive(Clone)]
+pub struct Snapshot {
+    v: [u8; 16],
+    pc: u16,
+    i: u16,
+    memory: Vec<u8>,
+    stack: [u16; STACK_SIZE],
+    sp: usize,
+    framebuffer: [u64; FB_HEIGHT as usize],
+    last_draw_collisions: u32,
+    keyboard: [bool; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    rng: Rng,
+    cycles: u64,
+    frames: u64,
+    waiting_for_key: bool,
+}
+
+impl Snapshot {
+    // VM::cycles when it was taken
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+pub struct VM {
+    pub v: [u8; 16],
+    pub pc: u16,
+    pub i: u16,
+
+    pub memory: Vec<u8>,
+    // return addresses, the first `sp` of them in use
+    pub(crate) stack: [u16; STACK_SIZE],
+    pub(crate) sp: usize,
+    // one row per word, bit 63 is column 0
+    pub(crate) framebuffer: [u64; FB_HEIGHT as usize],
+    // set by CLS/DRW, cleared by whoever presents the framebuffer
+    pub draw_flag: bool,
+    // pixels the last DRW turned off; VF only says whether there were any
+    pub(crate) last_draw_collisions: u32,
+    // set through set_key and friends
+    pub(crate) keyboard: [bool; 16],
+    // the keys as the observer last heard of them
+    pub(crate) keys_seen: [bool; 16],
+
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+
+    // consulted on every step, so they can be flipped while running
+    pub quirks: Quirks,
+    pub(crate) start_address: u16,
+    pub(crate) cpu_hz: f64,
+    pub(crate) rng: Rng,
+    // instructions executed since the last reset
+    pub(crate) cycles: u64,
+    // 60Hz timer ticks since the last reset
+    pub(crate) frames: u64,
+    // image passed to the last load_rom, copied back in by reset
+    pub(crate) rom: Vec<u8>,
+    pub(crate) observer: Option<Box<dyn VmObserver>>,
+    // a debugging aid like the observer, so not part of serialized state
+    pub(crate) strict_sys: bool,
+    pub(crate) strict_jumps: bool,
+    pub(crate) low_memory: LowMemory,
+    // likewise for halt_on_spin
+    pub(crate) halt_on_spin: bool,
+    // executions per address, with VmConfig::track_hot_spots
+    pub(crate) pc_counts: Option<Vec<u64>>,
+    // one bit per address an instruction was fetched from since the last
+    // reset, with VmConfig::track_executed
+    pub(crate) executed: Option<Vec<u64>>,
+    // writes over executed code stop like a write watchpoint
+    pub(crate) strict_smc: bool,
+    // executions per `Family`, counted along with pc_counts
+    pub(crate) family_counts: [u64; Family::ALL.len()],
+    // None while there are none, so memory accesses only pay for the check
+    pub(crate) watchpoints: Option<Vec<Watchpoint>>,
+    pub(crate) watch_hit: Option<WatchHit>,
+    // the last FX0A found no key down, so the PC is still on it
+    pub(crate) waiting_for_key: bool,
+    // addresses pinned to a value by `freeze`, written back after every
+    // instruction
+    pub(crate) freezes: Vec<(u16, u8)>,
+    // the last collision_log_len collisions, with VmConfig::log_collisions
+    pub(crate) collision_log: VecDeque<Collision>,
+    pub(crate) collision_log_len: usize,
+}
+
+impl VM {
+    pub fn new(rng: fn() -> u8) -> Self {
+        VM::from_config(VmConfig::new(rng))
+    }
+
+    pub fn with_config(config: VmConfig) -> Result<Self, Chip8Error> {
+        config.validate()?;
+        Ok(VM::from_config(config))
+    }
+
+    fn from_config(config: VmConfig) -> Self {
+        VM {
+            v: [0; 16],
+            pc: config.start_address,
+            i: 0,
+            memory: vec![0; config.memory_size],
+            stack: [0; STACK_SIZE],
+            sp: 0,
+            framebuffer: [0; FB_HEIGHT as usize],
+            draw_flag: false,
+            last_draw_collisions: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            keyboard: [false; 16],
+            keys_seen: [false; 16],
+            quirks: config.quirks,
+            start_address: config.start_address,
+            cpu_hz: config.cpu_hz,
+            rng: config.rng,
+            cycles: 0,
+            frames: 0,
+            rom: Vec::new(),
+            observer: None,
+            strict_sys: config.strict_sys,
+            strict_jumps: config.strict_jumps,
+            low_memory: config.low_memory,
+            halt_on_spin: config.halt_on_spin,
+            pc_counts: config.track_hot_spots.then(|| vec![0; config.memory_size]),
+            executed: (config.track_executed || config.strict_smc)
+                .then(|| vec![0; config.memory_size.div_ceil(64)]),
+            strict_smc: config.strict_smc,
+            family_counts: [0; Family::ALL.len()],
+            watchpoints: None,
+            watch_hit: None,
+            waiting_for_key: false,
+            freezes: Vec::new(),
+            collision_log: VecDeque::new(),
+            collision_log_len: config.log_collisions,
+        }
+    }
+
+    // Presses or releases CHIP-8 key `key` (0x0-0xF); anything past 0xF is
+    // ignored, so frontends can pass on whatever they mapped
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if let Some(state) = self.keyboard.get_mut(key) {
+            *state = pressed;
+        }
+    }
+
+    pub fn key_down(&mut self, key: usize) {
+        self.set_key(key, true);
+    }
+
+    pub fn key_up(&mut self, key: usize) {
+        self.set_key(key, false);
+    }
+
+    // false past 0xF
+    pub fn is_key_down(&self, key: usize) -> bool {
+        self.keyboard.get(key).copied().unwrap_or(false)
+    }
+
+    // all 16 keys, key 0 first
+    pub fn keys(&self) -> [bool; 16] {
+        self.keyboard
+    }
+
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.keyboard = keys;
+    }
+
+    // swaps the CXNN source, e.g. to re-inject OS randomness after loading a
+    // serialized state
+    pub fn set_rng(&mut self, rng: fn() -> u8) {
+        self.rng = Rng::External(rng);
+    }
+
+    // instructions per second the VM is meant to run at
+    pub fn cpu_hz(&self) -> f64 {
+        self.cpu_hz
+    }
+
+    // replaces any observer installed before; kept across resets
+    pub fn set_observer(&mut self, observer: Box<dyn VmObserver>) {
+        self.observer = Some(observer);
+        self.keys_seen = self.keyboard;
+    }
+
+    // kept across resets like the observer
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints
+            .get_or_insert_with(Vec::new)
+            .push(watchpoint);
+    }
+
+    // drops every watchpoint overlapping start..=end, returning how many
+    pub fn remove_watchpoints(&mut self, start: u16, end: u16) -> usize {
+        let Some(watchpoints) = &mut self.watchpoints else {
+            return 0;
+        };
+        let before = watchpoints.len();
+        watchpoints.retain(|watchpoint| !watchpoint.overlaps(start, end));
+        let removed = before - watchpoints.len();
+        if watchpoints.is_empty() {
+            self.watchpoints = None;
+        }
+        removed
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        self.watchpoints.as_deref().unwrap_or_default()
+    }
+
+    // The first watchpoint hit since the last call, for callers of `step`;
+    // run_frame hands it over in FrameOutcome instead. Until it's taken,
+    // later hits are dropped.
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            v: self.v,
+            pc: self.pc,
+            i: self.i,
+            memory: self.memory.clone(),
+            stack: self.stack,
+            sp: self.sp,
+            framebuffer: self.framebuffer,
+            last_draw_collisions: self.last_draw_collisions,
+            keyboard: self.keyboard,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            rng: self.rng,
+            cycles: self.cycles,
+            frames: self.frames,
+            waiting_for_key: self.waiting_for_key,
+        }
+    }
+
+    // Back to the state `snapshot` was taken in. Running on from there
+    // repeats what ran after it, given the same keys, timer ticks and a
+    // generator that isn't `Rng::External`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.v = snapshot.v;
+        self.pc = snapshot.pc;
+        self.i = snapshot.i;
+        self.memory.clone_from(&snapshot.memory);
+        self.stack = snapshot.stack;
+        self.sp = snapshot.sp;
+        self.framebuffer = snapshot.framebuffer;
+        // the screen changed under the frontend
+        self.draw_flag = true;
+        self.last_draw_collisions = snapshot.last_draw_collisions;
+        self.keyboard = snapshot.keyboard;
+        self.delay_timer = snapshot.delay_timer;
+        self.set_sound_timer(snapshot.sound_timer);
+        self.rng = snapshot.rng;
+        self.cycles = snapshot.cycles;
+        self.frames = snapshot.frames;
+        self.waiting_for_key = snapshot.waiting_for_key;
+        self.watch_hit = None;
+        self.apply_freezes();
+    }
+
+    // Pins the byte at `addr` to `value`, for cheats: it's written now and
+    // again after every instruction until `unfreeze`. Freezing an address
+    // again replaces its value.
+    pub fn freeze(&mut self, addr: u16, value: u8) -> Result<(), Chip8Error> {
+        if addr as usize >= self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                addr: addr as usize,
+            });
+        }
+        self.freezes.retain(|&(frozen, _)| frozen != addr);
+        self.freezes.push((addr, value));
+        self.memory[addr as usize] = value;
+        Ok(())
+    }
+
+    // false if `addr` wasn't frozen
+    pub fn unfreeze(&mut self, addr: u16) -> bool {
+        let before = self.freezes.len();
+        self.freezes.retain(|&(frozen, _)| frozen != addr);
+        self.freezes.len() != before
+    }
+
+    // the frozen addresses with their values, in the order they were frozen
+    pub fn freezes(&self) -> &[(u16, u8)] {
+        &self.freezes
+    }
+
+    fn apply_freezes(&mut self) {
+        for &(addr, value) in &self.freezes {
+            self.memory[addr as usize] = value;
+        }
+    }
+
+    // Takes over `state`'s machine, e.g. one read from a save state, frozen
+    // addresses included. The observer, watchpoints, profiling counts,
+    // collision log, strict_sys, strict_jumps, halt_on_spin and low memory
+    // protection stay, and so do the keys held right now. So does an
+    // `Rng::External` generator: a deserialized VM only has a seeded
+    // stand-in for one.
+    pub fn replace_state(&mut self, state: VM) {
+        let old = mem::replace(self, state);
+        self.observer = old.observer;
+        self.strict_sys = old.strict_sys;
+        self.strict_jumps = old.strict_jumps;
+        self.halt_on_spin = old.halt_on_spin;
+        self.low_memory = old.low_memory;
+        self.pc_counts = old.pc_counts.map(|mut counts| {
+            counts.resize(self.memory.len(), 0);
+            counts
+        });
+        self.family_counts = old.family_counts;
+        self.executed = old.executed.map(|mut executed| {
+            executed.resize(self.memory.len().div_ceil(64), 0);
+            executed
+        });
+        self.strict_smc = old.strict_smc;
+        self.collision_log = old.collision_log;
+        self.collision_log_len = old.collision_log_len;
+        self.watchpoints = old.watchpoints;
+        self.watch_hit = None;
+        self.keyboard = old.keyboard;
+        self.keys_seen = old.keys_seen;
+        if let Rng::External(_) = old.rng {
+            self.rng = old.rng;
+        }
+        self.draw_flag = true;
+        if (old.sound_timer > 0) != (self.sound_timer > 0)
+            && let Some(observer) = &mut self.observer
+        {
+            observer.on_sound_changed(self.sound_timer > 0);
+        }
+    }
+
+    pub fn take_observer(&mut self) -> Option<Box<dyn VmObserver>> {
+        self.observer.take()
+    }
+
+    // instructions fetched since power-on or the last reset; input logs are
+    // keyed to it, so replays see the exact same timing
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // 60Hz frames since power-on or the last reset, counted as the timers
+    // tick
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // Blocked in FX0A: its last run found no key down, so the next step runs
+    // it again and nothing else happens until a key is pressed. Frontends
+    // can skip stepping further that frame.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    // The `n` most executed addresses since the last reset with their counts,
+    // most executed first, ties in address order. Empty unless the VM was
+    // built with VmConfig::track_hot_spots.
+    pub fn hot_spots(&self, n: usize) -> Vec<(u16, u64)> {
+        let Some(counts) = &self.pc_counts else {
+            return Vec::new();
+        };
+        let mut spots: Vec<(u16, u64)> = counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(addr, &count)| (addr as u16, count))
+            .collect();
+        spots.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots.truncate(n);
+        spots
+    }
+
+    // Executions per instruction family since the last reset, most executed
+    // first, leaving out families that never ran. Empty unless the VM was
+    // built with VmConfig::track_hot_spots.
+    pub fn family_counts(&self) -> Vec<(Family, u64)> {
+        let mut counts: Vec<(Family, u64)> = Family::ALL
+            .into_iter()
+            .zip(self.family_counts)
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        // stable, so ties keep the order of Family::ALL
+        counts.sort_by_key(|&(_, count)| core::cmp::Reverse(count));
+        counts
+    }
+
+    // Back to the power-on state with the font and the current ROM loaded again,
+    // undoing anything the program wrote to memory. Configuration is kept.
+    pub fn reset(&mut self) {
+        self.v = [0; 16];
+        self.pc = self.start_address;
+        self.i = 0;
+        self.memory.fill(0);
+        self.sp = 0;
+        self.framebuffer = [0; FB_HEIGHT as usize];
+        self.draw_flag = true;
+        self.last_draw_collisions = 0;
+        self.keyboard = [false; 16];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.rng.reseed();
+        self.cycles = 0;
+        self.frames = 0;
+        self.waiting_for_key = false;
+        if let Some(counts) = &mut self.pc_counts {
+            counts.fill(0);
+        }
+        self.family_counts = [0; Family::ALL.len()];
+        if let Some(executed) = &mut self.executed {
+            executed.fill(0);
+        }
+        self.collision_log.clear();
+
+        self.copy_in_rom();
+        // cheats outlast a reset
+        self.apply_freezes();
+        if let Some(observer) = &mut self.observer {
+            observer.on_rom_loaded(&self.rom);
+        }
+    }
+
+    // reset with a different program, for switching ROMs without a new VM
+    pub fn load_new_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.check_rom_size(rom)?;
+        self.rom = rom.to_vec();
+        // another game keeps its variables elsewhere
+        self.freezes.clear();
+        self.reset();
+        Ok(())
+    }
+
+    // Counts both timers down by `frames` 60Hz ticks, stopping at 0. A loop
+    // that fell behind passes every frame it missed, so the timers keep to
+    // wall-clock time.
+    pub fn step_timers(&mut self, frames: u8) {
+        self.frames += frames as u64;
+        self.delay_timer = self.delay_timer.saturating_sub(frames);
+        if self.sound_timer > 0 {
+            self.set_sound_timer(self.sound_timer.saturating_sub(frames));
+        }
+        if let Some(observer) = &mut self.observer {
+            observer.on_frame(self.frames);
+        }
+    }
+
+    // ST as LD ST, Vx sets it, telling the observer when the beep turns on
+    // or off; for debuggers writing the register
+    pub fn set_sound_timer(&mut self, value: u8) {
+        let was_on = self.sound_timer > 0;
+        self.sound_timer = value;
+        if was_on != (value > 0)
+            && let Some(observer) = &mut self.observer
+        {
+            observer.on_sound_changed(value > 0);
+        }
+    }
+
+    // `cycles` instructions back to back, without timers or input, for
+    // headless runs and benchmarks; stops at the first fault
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    // One 60Hz tick: up to `ipf` instructions, then the timers, unless the VM
+    // faulted. This is the loop every frontend needs, kept in one place. It
+    // ends early on FX0A waiting for a key, a jump to itself, a watchpoint
+    // and, with the display-wait quirk, a DXYN.
+    pub fn run_frame(&mut self, ipf: u32) -> FrameOutcome {
+        let mut outcome = FrameOutcome {
+            instructions: 0,
+            drew: false,
+            waiting_for_key: false,
+            halted: false,
+            watch_hit: None,
+            fault: None,
+        };
+        while outcome.instructions < ipf {
+            outcome.instructions += 1;
+            match self.step() {
+                Ok(step) => {
+                    outcome.drew |= step.drew;
+                    if let Some(hit) = self.watch_hit.take() {
+                        outcome.watch_hit = Some(hit);
+                        break;
+                    }
+                    if step.waiting_for_key {
+                        outcome.waiting_for_key = true;
+                        break;
+                    }
+                    if step.instruction == Instruction::Jp(step.pc) {
+                        outcome.halted = true;
+                        break;
+                    }
+                    if self.quirks.display_wait
+                        && matches!(step.instruction, Instruction::Drw { .. })
+                    {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    outcome.fault = Some(e);
+                    return outcome;
+                }
+            }
+        }
+        self.step_timers(1);
+        outcome
+    }
+
+    // One instruction; a fault is passed to the observer before it's returned
+    pub fn step(&mut self) -> Result<StepResult, Chip8Error> {
+        let result = self.execute();
+        if let Err(fault) = &result
+            && let Some(observer) = &mut self.observer
+        {
+            observer.on_fault(fault);
+        }
+        result
+    }
+
+    fn execute(&mut self) -> Result<StepResult, Chip8Error> {
+        // frontends may set a key several times between instructions, only
+        // what the instruction gets to see is passed on
+        if let Some(observer) = &mut self.observer
+            && self.keys_seen != self.keyboard
+        {
+            for key in 0..16 {
+                if self.keys_seen[key] != self.keyboard[key] {
+                    observer.on_key_changed(key as u8, self.keyboard[key]);
+                }
+            }
+            self.keys_seen = self.keyboard;
+        }
+        let pc = self.pc;
+        // both opcode bytes must be in memory, so the last address can't hold one
+        if pc as usize + 1 >= self.memory.len() {
+            return Err(Chip8Error::PcOutOfBounds { pc });
+        }
+        let opcode: u16 =
+            (self.memory[pc as usize] as u16) << 8 | self.memory[pc as usize + 1] as u16;
+        let instruction = Instruction::decode(opcode);
+        let mut result = StepResult {
+            pc,
+            opcode,
+            instruction,
+            drew: false,
+            waiting_for_key: false,
+            skipped: false,
+        };
+        self.pc = self.pc.wrapping_add(2);
+        self.cycles += 1;
+        if let Some(counts) = &mut self.pc_counts {
+            counts[pc as usize] += 1;
+            self.family_counts[instruction.family() as usize] += 1;
+        }
+        if let Some(executed) = &mut self.executed {
+            executed[pc as usize / 64] |= 1 << (pc % 64);
+        }
+        match instruction {
+            Instruction::Cls => {
+                // CLEAR SCREEN, at once: a frontend may fade the old pixels
+                // out, but collisions only ever see this framebuffer. XO-CHIP
+                // clears only the planes FN01 selected; with the one plane
+                // this VM draws on, that is always the whole screen.
+                self.framebuffer = [0; FB_HEIGHT as usize];
+                self.draw_flag = true;
+                result.drew = true;
+            }
+
+            Instruction::Ret => {
+                // RET
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc });
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+                if let Some(observer) = &mut self.observer {
+                    observer.on_ret(self.pc);
+                }
+            }
+
+            // 0000 is what cleared memory decodes as, not a call to address 0,
+            // so running off the end of a program into it isn't reported
+            Instruction::Sys(0) => {}
+
+            Instruction::Sys(nnn) => {
+                // SYS nnn: machine code routines can't run here
+                if self.strict_sys {
+                    return Err(Chip8Error::InvalidOpcode { opcode, pc });
+                }
+                if let Some(observer) = &mut self.observer {
+                    observer.on_sys(nnn, pc);
+                }
+            }
+
+            Instruction::Jp(nnn) => {
+                // JUMP nnn
+                self.pc = nnn;
+                if nnn == pc && self.halt_on_spin {
+                    return Err(Chip8Error::Halted { pc });
+                }
+            }
+
+            Instruction::Call(nnn) => {
+                // CALL nnn
+                if self.sp == STACK_SIZE {
+                    return Err(Chip8Error::StackOverflow { pc });
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+                if let Some(observer) = &mut self.observer {
+                    observer.on_call(nnn);
+                }
+            }
+
+            Instruction::SeVxByte { x, nn } => {
+                // SE Vx, byte
+                if self.v[x as usize] == nn {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::SneVxByte { x, nn } => {
+                // SNE Vx, byte
+                if self.v[x as usize] != nn {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::SeVxVy { x, y } => {
+                // SE Vx, Vy
+                if self.v[x as usize] == self.v[y as usize] {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::LdVxByte { x, nn } => {
+                // LOAD Vx, nn
+                self.v[x as usize] = nn;
+            }
+
+            Instruction::AddVxByte { x, nn } => {
+                // ADD Vx, nn
+                let x = x as usize;
+                self.v[x] = self.v[x].wrapping_add(nn);
+            }
+
+            Instruction::LdVxVy { x, y } => {
+                // LD Vx, Vy
+                self.v[x as usize] = self.v[y as usize];
+            }
+
+            Instruction::Or { x, y } => {
+                // OR Vx, Vy
+                self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset_on_logic {
+                    self.v[0xF] = 0;
+                }
+            }
+
+            Instruction::And { x, y } => {
+                // AND Vx, Vy
+                self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset_on_logic {
+                    self.v[0xF] = 0;
+                }
+            }
+
+            Instruction::Xor { x, y } => {
+                // XOR Vx, Vy
+                self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset_on_logic {
+                    self.v[0xF] = 0;
+                }
+            }
+
+            Instruction::AddVxVy { x, y } => {
+                // ADD Vx, Vy
+                let (x, y) = (x as usize, y as usize);
+                let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+                self.v[x] = sum;
+                self.v[0xF] = if carry { 1 } else { 0 };
+            }
+
+            Instruction::Sub { x, y } => {
+                // SUB Vx, Vy
+                let (x, y) = (x as usize, y as usize);
+                let (diff, borrow) = self.v[x].overflowing_sub(self.v[y]);
+                self.v[x] = diff;
+                self.v[0xF] = if borrow { 0 } else { 1 };
+            }
+
+            Instruction::Shr { x, y } => {
+                // SHR Vx {, Vy}
+                let (x, y) = (x as usize, y as usize);
+                if self.quirks.shift_uses_vy {
+                    self.v[x] = self.v[y];
+                }
+                self.v[0xF] = self.v[x] & 0x01;
+                self.v[x] >>= 1;
+            }
+
+            Instruction::Subn { x, y } => {
+                // SUBN Vx, Vy
+                let (x, y) = (x as usize, y as usize);
+                let (diff, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                self.v[x] = diff;
+                self.v[0xF] = if borrow { 0 } else { 1 };
+            }
+
+            Instruction::Shl { x, y } => {
+                // SHL Vx {, Vy}
+                let (x, y) = (x as usize, y as usize);
+                if self.quirks.shift_uses_vy {
+                    self.v[x] = self.v[y];
+                }
+                self.v[0xF] = (self.v[x] & 0x80) >> 7;
+                self.v[x] <<= 1;
+            }
+
+            Instruction::SneVxVy { x, y } => {
+                // SNE Vx, Vy
+                if self.v[x as usize] != self.v[y as usize] {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::LdI(nnn) => {
+                // LOAD i, nnn
+                self.i = nnn;
+            }
+
+            Instruction::JpV0(nnn) => {
+                // JUMP V0, nnn (or Vx, xnn with the jump quirk)
+                // nnn + V0 can reach 0x10FE, wrap it back into memory
+                let reg = if self.quirks.jump_uses_vx {
+                    (nnn >> 8) as usize
+                } else {
+                    0
+                };
+                let target = nnn as usize + self.v[reg] as usize;
+                if target >= self.memory.len() && self.strict_jumps {
+                    return Err(Chip8Error::JumpOutOfBounds { target, pc });
+                }
+                self.pc = (target % self.memory.len()) as u16;
+            }
+
+            Instruction::Rnd { x, nn } => {
+                // RND Vx, byte
+                let rnd_byte: u8 = self.rng.next_byte();
+                self.v[x as usize] = rnd_byte & nn;
+            }
+
+            Instruction::Drw { x, y, n } => {
+                // DRAW Vx, Vy, n
+                let n = n as usize;
+                let mut vx = self.v[x as usize] as usize;
+                let mut vy = self.v[y as usize] as usize;
+                let clip = self.quirks.clip_sprites;
+                if clip {
+                    // only the start position wraps, the sprite itself is cut off
+                    vx %= FB_WIDTH as usize;
+                    vy %= FB_HEIGHT as usize;
+                }
+                let range = self.i_range(n)?;
+                let sprite = range.start;
+                if self.watchpoints.is_some() {
+                    self.watch_read(pc, opcode, sprite, n);
+                }
+                // copied out, so the loop below indexes nothing unchecked
+                let mut bytes = [0u8; 15];
+                bytes[..n].copy_from_slice(&self.memory[range]);
+                self.v[0xF] = 0;
+                self.last_draw_collisions = 0;
+                self.draw_flag = true;
+                result.drew = true;
+                for (row, &byte) in bytes[..n].iter().enumerate() {
+                    if clip && vy + row >= FB_HEIGHT as usize {
+                        break;
+                    }
+                    // the sprite byte lined up with column 0, then moved to Vx:
+                    // shifted out past the right edge when clipping, rotated
+                    // around to the left edge otherwise
+                    let sprite_bits = (byte as u64) << 56;
+                    let sprite_bits = if clip {
+                        sprite_bits >> vx
+                    } else {
+                        sprite_bits.rotate_right((vx % FB_WIDTH as usize) as u32)
+                    };
+                    let fb_row = &mut self.framebuffer[(vy + row) % FB_HEIGHT as usize];
+                    // the whole sprite row in one AND and one XOR, aligned or
+                    // not; lit pixels under the sprite get turned off, which
+                    // sets VF
+                    let collisions = (*fb_row & sprite_bits).count_ones();
+                    if collisions != 0 {
+                        self.v[0xF] = 1;
+                        self.last_draw_collisions += collisions;
+                    }
+                    *fb_row ^= sprite_bits;
+                }
+                let (x, y) = (
+                    (vx % FB_WIDTH as usize) as u8,
+                    (vy % FB_HEIGHT as usize) as u8,
+                );
+                if self.last_draw_collisions != 0 && self.collision_log_len != 0 {
+                    if self.collision_log.len() == self.collision_log_len {
+                        self.collision_log.pop_front();
+                    }
+                    self.collision_log.push_back(Collision {
+                        cycle: self.cycles,
+                        pc,
+                        x,
+                        y,
+                        pixels: self.last_draw_collisions,
+                    });
+                }
+                if let Some(observer) = &mut self.observer {
+                    observer.on_draw(&FrameInfo {
+                        pc,
+                        x,
+                        y,
+                        height: n as u8,
+                        sprite: sprite as u16,
+                        collisions: self.last_draw_collisions,
+                    });
+                }
+            }
+
+            Instruction::Skp { x } => {
+                // SKP Vx, only the low nibble naming a key, as on the VIP
+                let key = (self.v[x as usize] & 0xF) as usize;
+                if self.keyboard[key] {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::Sknp { x } => {
+                // SKNP Vx
+                let key = (self.v[x as usize] & 0xF) as usize;
+                if !self.keyboard[key] {
+                    self.pc = self.pc.wrapping_add(2);
+                    result.skipped = true;
+                }
+            }
+
+            Instruction::LdVxDt { x } => {
+                // Vx = get_delay()
+                self.v[x as usize] = self.delay_timer;
+            }
+
+            Instruction::LdVxK { x } => {
+                // Vx = get_key()
+                if let Some(key) = (0..16).find(|&key| self.keyboard[key]) {
+                    self.v[x as usize] = key as u8;
+                    self.waiting_for_key = false;
+                    if let Some(observer) = &mut self.observer {
+                        observer.on_key_taken(x, key as u8);
+                    }
+                } else {
+                    self.pc = self.pc.wrapping_sub(2);
+                    self.waiting_for_key = true;
+                    result.waiting_for_key = true;
+                    if let Some(observer) = &mut self.observer {
+                        observer.on_key_wait(x);
+                    }
+                }
+            }
+
+            Instruction::LdDtVx { x } => {
+                // delay_timer(Vx)
+                self.delay_timer = self.v[x as usize];
+            }
+
+            Instruction::LdStVx { x } => {
+                // sound_timer(Vx)
+                self.set_sound_timer(self.v[x as usize]);
+            }
+
+            Instruction::AddIVx { x } => {
+                // ADD I, Vx
+                self.i = self.i.wrapping_add(self.v[x as usize] as u16);
+            }
+
+            Instruction::LdFVx { x } => {
+                // I = sprite_addr[Vx]
+                let digit = self.v[x as usize] as u16;
+                self.i = FONT_START as u16 + (digit * 5);
+            }
+
+            Instruction::LdBVx { x } => {
+                // set_BCD(Vx) *(I+0) = BCD(3); *(I+1) = BCD(2); *(I+2) = BCD(1);
+                let vx = self.v[x as usize];
+                let range = self.i_range(3)?;
+                let bcd = [vx / 100, (vx % 100) / 10, vx % 10];
+                if self.low_memory != LowMemory::Off {
+                    self.check_low_write(pc, range.start)?;
+                }
+                if self.watchpoints.is_some() {
+                    self.watch_write(pc, opcode, range.start, &bcd);
+                }
+                if self.executed.is_some() {
+                    self.check_code_write(pc, opcode, range.start, &bcd);
+                }
+                self.memory[range].copy_from_slice(&bcd);
+            }
+
+            Instruction::LdIVx { x } => {
+                // LD [I], V0..Vx
+                let x = x as usize;
+                let range = self.i_range(x + 1)?;
+                if self.low_memory != LowMemory::Off {
+                    self.check_low_write(pc, range.start)?;
+                }
+                if self.watchpoints.is_some() {
+                    let v = self.v;
+                    self.watch_write(pc, opcode, range.start, &v[..=x]);
+                }
+                if self.executed.is_some() {
+                    let v = self.v;
+                    self.check_code_write(pc, opcode, range.start, &v[..=x]);
+                }
+                self.memory[range].copy_from_slice(&self.v[..=x]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+
+            Instruction::LdVxI { x } => {
+                // LD V0..Vx, [I]
+                let x = x as usize;
+                let range = self.i_range(x + 1)?;
+                if self.watchpoints.is_some() {
+                    self.watch_read(pc, opcode, range.start, x + 1);
+                }
+                self.v[..=x].copy_from_slice(&self.memory[range]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+
+            Instruction::Unknown(_) => {
+                // Unknown opcode
+                if let Some(observer) = &mut self.observer {
+                    observer.on_unknown_opcode(opcode, pc);
+                }
+            }
+        }
+        self.apply_freezes();
+        if let Some(observer) = &mut self.observer {
+            observer.on_step(&StepInfo {
+                cycle: self.cycles,
+                step: result,
+                v: self.v,
+                i: self.i,
+            });
+        }
+        Ok(result)
+    }
+
+    pub fn load_font(&mut self) {
+        self.memory[FONT_START..FONT_START + FONT_BYTES].copy_from_slice(&FONT);
+    }
+
+    // Copies `rom` in at the start address, along with the font, and points
+    // PC at it. Memory ends up as `reset` would leave it.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.check_rom_size(rom)?;
+        self.rom = rom.to_vec();
+        self.copy_in_rom();
+        self.pc = self.start_address;
+        Ok(())
+    }
+
+    // the font, then the ROM image, for load_rom and reset alike
+    fn copy_in_rom(&mut self) {
+        self.load_font();
+        let start = self.start_address as usize;
+        self.memory[start..start + self.rom.len()].copy_from_slice(&self.rom);
+    }
+
+    fn check_rom_size(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        // the first fetch would read the zeroed memory past it
+        if rom.len() < 2 {
+            return Err(Chip8Error::RomTooSmall { size: rom.len() });
+        }
+        let start = self.start_address as usize;
+        if start + rom.len() > self.memory.len() {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                max: self.memory.len() - start,
+            });
+        }
+        Ok(())
+    }
+
+    // records a read of memory[start..start + len] if it's watched and no
+    // earlier hit is waiting to be taken
+    fn watch_read(&mut self, pc: u16, opcode: u16, start: usize, len: usize) {
+        let Some(watchpoints) = &self.watchpoints else {
+            return;
+        };
+        if self.watch_hit.is_some() {
+            return;
+        }
+        let hit = (start..start + len).find(|&addr| {
+            watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.watches(addr as u16, Access::Read))
+        });
+        if let Some(addr) = hit {
+            self.watch_hit = Some(WatchHit {
+                pc,
+                opcode,
+                addr: addr as u16,
+                access: Access::Read,
+                old: self.memory[addr],
+                new: self.memory[addr],
+            });
+        }
+    }
+
+    // same for `new` about to be written at `start`
+    fn watch_write(&mut self, pc: u16, opcode: u16, start: usize, new: &[u8]) {
+        let Some(watchpoints) = &self.watchpoints else {
+            return;
+        };
+        if self.watch_hit.is_some() {
+            return;
+        }
+        let hit = (start..start + new.len()).find(|&addr| {
+            watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.watches(addr as u16, Access::Write))
+        });
+        if let Some(addr) = hit {
+            self.watch_hit = Some(WatchHit {
+                pc,
+                opcode,
+                addr: addr as u16,
+                access: Access::Write,
+                old: self.memory[addr],
+                new: new[addr - start],
+            });
+        }
+    }
+
+    // FX33 or FX55 at `pc` writing from `start` with low memory protected:
+    // tells the observer, or faults before anything is written
+    fn check_low_write(&mut self, pc: u16, start: usize) -> Result<(), Chip8Error> {
+        if start >= ROM_START {
+            return Ok(());
+        }
+        if self.low_memory == LowMemory::Trap {
+            return Err(Chip8Error::ProtectedWrite { addr: start, pc });
+        }
+        if let Some(observer) = &mut self.observer {
+            observer.on_low_memory_write(start as u16, pc);
+        }
+        Ok(())
+    }
+
+    // Tells the observer about each byte of `new`, about to be written at
+    // `start`, that changes an instruction already executed, and stops like
+    // a write watchpoint with strict_smc
+    fn check_code_write(&mut self, pc: u16, opcode: u16, start: usize, new: &[u8]) {
+        for (addr, &byte) in (start..).zip(new) {
+            let old = self.memory[addr];
+            if old == byte {
+                continue;
+            }
+            // the byte is the first or the second half of the instruction
+            let Some(at) = [addr, addr.wrapping_sub(1)]
+                .into_iter()
+                .find(|&at| self.was_executed(at as u16) && at + 1 < self.memory.len())
+            else {
+                continue;
+            };
+            let before = u16::from_be_bytes([self.memory[at], self.memory[at + 1]]);
+            let mut after = before.to_be_bytes();
+            after[addr - at] = byte;
+            if let Some(observer) = &mut self.observer {
+                observer.on_code_write(&CodeWrite {
+                    pc,
+                    opcode,
+                    addr: addr as u16,
+                    old,
+                    new: byte,
+                    instruction: at as u16,
+                    before,
+                    after: u16::from_be_bytes(after),
+                });
+            }
+            if self.strict_smc && self.watch_hit.is_none() {
+                self.watch_hit = Some(WatchHit {
+                    pc,
+                    opcode,
+                    addr: addr as u16,
+                    access: Access::Write,
+                    old,
+                    new: byte,
+                });
+            }
+        }
+    }
+
+    // whether an instruction was fetched from `addr` since the last reset;
+    // always false without VmConfig::track_executed
+    pub fn was_executed(&self, addr: u16) -> bool {
+        self.executed.as_ref().is_some_and(|executed| {
+            executed
+                .get(addr as usize / 64)
+                .is_some_and(|word| word & (1 << (addr % 64)) != 0)
+        })
+    }
+
+    // The one place memory accesses are bounds checked: the `len` bytes from
+    // `start`, or MemoryOutOfBounds with the first address past the end.
+    // Nothing wraps around to address 0, an access running off the end of
+    // memory faults before any of it happens.
+    fn mem_range(&self, start: usize, len: usize) -> Result<Range<usize>, Chip8Error> {
+        if start + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                addr: start.max(self.memory.len()),
+            });
+        }
+        Ok(start..start + len)
+    }
+
+    // the `len` bytes DXYN, FX33, FX55 and FX65 access at I
+    fn i_range(&self, len: usize) -> Result<Range<usize>, Chip8Error> {
+        self.mem_range(self.i as usize, len)
+    }
+
+    // the byte at `addr`, checked like the VM's own accesses
+    pub fn read_mem(&self, addr: u16) -> Result<u8, Chip8Error> {
+        let range = self.mem_range(addr as usize, 1)?;
+        Ok(self.memory[range.start])
+    }
+
+    // image passed to the last load_rom
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    // memory the last loaded ROM was copied to
+    pub fn rom_range(&self) -> Range<usize> {
+        let start = self.start_address as usize;
+        start..start + self.rom.len()
+    }
+
+    // return addresses of the calls in progress, innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    // Writes a text hexdump of the whole RAM, preceded by the register state,
+    // for post-mortem analysis
+    #[cfg(feature = "std")]
+    pub fn dump_memory(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(
+            out,
+            "PC={:04X} I={:04X} SP={} DT={:02X} ST={:02X}",
+            self.pc, self.i, self.sp, self.delay_timer, self.sound_timer
+        )?;
+        for (idx, v) in self.v.iter().enumerate() {
+            write!(out, "V{:X}={:02X} ", idx, v)?;
+        }
+        writeln!(out)?;
+        write!(out, "STACK")?;
+        for addr in self.stack() {
+            write!(out, " {:04X}", addr)?;
+        }
+        writeln!(out)?;
+        writeln!(out)?;
+
+        for (row, bytes) in self.memory.chunks(16).enumerate() {
+            write!(out, "{:04X}:", row * 16)?;
+            for byte in bytes {
+                write!(out, " {:02X}", byte)?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+
+    pub fn last_draw_collisions(&self) -> u32 {
+        self.last_draw_collisions
+    }
+
+    // The latest DRWs that set VF, oldest first: as many as
+    // VmConfig::log_collisions asked for, and none without it. Cleared by
+    // reset; restoring a snapshot leaves it alone.
+    pub fn collision_log(&self) -> impl ExactSizeIterator<Item = Collision> + '_ {
+        self.collision_log.iter().copied()
+    }
+
+    // false outside the screen
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        x < FB_WIDTH && y < FB_HEIGHT && self.framebuffer[y as usize] & (1 << (63 - x)) != 0
+    }
+
+    // (x, y) of every lit pixel, row by row from the top left
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.framebuffer.iter().enumerate().flat_map(|(y, &row)| {
+            let mut bits = row;
+            core::iter::from_fn(move || {
+                // leftmost remaining pixel, then clear it
+                let x = bits.leading_zeros();
+                if x == 64 {
+                    return None;
+                }
+                bits &= !(1 << (63 - x));
+                Some((x, y as u32))
+            })
+        })
+    }
+
+    // Screen fingerprint for golden tests: 64-bit FNV-1a over the packed
+    // framebuffer, 8 bytes per row from the top, each byte holding 8 pixels
+    // with the leftmost in the most significant bit (lit = 1). Only depends on
+    // the pixels, so it's the same on every platform and across releases.
+    pub fn framebuffer_hash(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+        self.framebuffer
+            .iter()
+            .flat_map(|row| row.to_be_bytes())
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    }
+}
@@ -0,0 +1,60 @@
+use core::fmt;
+
+// A memory range to stop on when an instruction reads or writes it. Only
+// FX33, FX55, FX65 and DXYN touch memory, so those are the ones checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    // inclusive
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    pub(crate) fn watches(&self, addr: u16, access: Access) -> bool {
+        (self.start..=self.end).contains(&addr)
+            && match access {
+                Access::Read => self.read,
+                Access::Write => self.write,
+            }
+    }
+
+    pub fn overlaps(&self, start: u16, end: u16) -> bool {
+        self.start <= end && start <= self.end
+    }
+}
+
+// 0x0300-0x0302 rw
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#06X}", self.start)?;
+        if self.end != self.start {
+            write!(f, "-{:#06X}", self.end)?;
+        }
+        let mode = match (self.read, self.write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        write!(f, " {}", mode)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// The first watched byte an instruction touched. For reads `old` and `new`
+// are both the value read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub opcode: u16,
+    pub addr: u16,
+    pub access: Access,
+    pub old: u8,
+    pub new: u8,
+}
@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_BYTES: u32 = 44;
+
+// 16-bit mono PCM writer. The RIFF and data chunk sizes are unknown until the
+// recording stops, so the header is written with zero sizes and patched by
+// `finish`.
+pub struct WavWriter {
+    out: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?; // patched in finish()
+        out.write_all(b"WAVE")?;
+
+        out.write_all(b"fmt ")?;
+        out.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&1u16.to_le_bytes())?; // mono
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * 2).to_le_bytes())?; // byte rate
+        out.write_all(&2u16.to_le_bytes())?; // block align
+        out.write_all(&16u16.to_le_bytes())?; // bits per sample
+
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?; // patched in finish()
+        Ok(WavWriter { out, data_bytes: 0 })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.out.write_all(&pcm.to_le_bytes())?;
+        }
+        self.data_bytes = self.data_bytes.saturating_add(samples.len() as u32 * 2);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out
+            .write_all(&(HEADER_BYTES - 8 + self.data_bytes).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_bytes.to_le_bytes())?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,69 @@
+// The assembler against the disassembler, and where its errors point.
+
+use chip8_emu_rs::{AsmError, assemble, disassemble, disassemble_rom};
+
+// xorshift64, for the inputs only
+struct Inputs(u64);
+
+impl Inputs {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn every_disassembled_word_assembles_back() {
+    for word in 0..=u16::MAX {
+        let text = disassemble(word);
+        let bytes = assemble(&text).unwrap_or_else(|e| panic!("{:04X} '{}': {}", word, text, e));
+        assert_eq!(bytes, word.to_be_bytes(), "{:04X} '{}'", word, text);
+    }
+}
+
+#[test]
+fn disassembled_roms_assemble_back() {
+    let mut inputs = Inputs(0x0123_4567_89AB_CDEF);
+    for _ in 0..200 {
+        // odd lengths too, which end in a lone byte
+        let rom: Vec<u8> = (0..1 + inputs.next() % 300)
+            .map(|_| inputs.next() as u8)
+            .collect();
+        // `0x0200  A22A  LD I, 0x22A` without the address and the word
+        let source: Vec<String> = disassemble_rom(&rom, 0x200)
+            .iter()
+            .map(|line| line.splitn(3, "  ").nth(2).unwrap().trim().to_string())
+            .collect();
+        assert_eq!(assemble(&source.join("\n")).unwrap(), rom);
+    }
+}
+
+fn error(source: &str) -> AsmError {
+    assemble(source).expect_err("source should not assemble")
+}
+
+#[test]
+fn errors_name_the_line() {
+    let duplicate = error("start:\n    CLS\nstart:\n    JP start\n");
+    assert_eq!(duplicate.line, 3);
+    assert!(duplicate.message.contains("duplicate label 'start'"));
+
+    let too_big = error("CLS\nLD V0, 0x100\n");
+    assert_eq!(too_big.line, 2);
+    assert!(too_big.message.contains("out of range"));
+
+    let far = error("CLS\nCLS\nJP 0x1000\n");
+    assert_eq!(far.line, 3);
+    assert!(far.message.contains("out of range"));
+
+    let nibble = error("DRW V0, V1, 16\n");
+    assert_eq!(nibble.line, 1);
+    assert!(nibble.message.contains("out of range"));
+
+    let missing = error("CLS\n\nJP nowhere\n");
+    assert_eq!(missing.line, 3);
+    assert!(missing.message.contains("undefined label 'nowhere'"));
+    assert_eq!(missing.to_string(), format!("line 3: {}", missing.message));
+}
@@ -0,0 +1,87 @@
+// The buzzer's samples for a ROM that sets the sound timer to 1 over and
+// over, rendered a frame at a time the way the SDL frontend's callback does.
+
+use chip8_emu_rs::{Tone, VM, VmObserver, assemble};
+use std::cell::Cell;
+use std::rc::Rc;
+
+const SAMPLE_RATE: u32 = 44100;
+const VOLUME: f32 = 0.25;
+const RAMP_MS: f32 = 5.0;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+// one-frame beeps with a frame of silence between them
+const BLIPS: &str = "
+        LD V0, 1
+        LD V1, 2
+    loop:
+        LD ST, V0
+        LD DT, V1
+    wait:
+        LD V2, DT
+        SE V2, 0
+        JP wait
+        JP loop
+";
+
+// whether the sound timer ran at all since the flag was last cleared, since a
+// timer of 1 has run out again by the end of the frame that set it
+struct Sounded(Rc<Cell<bool>>);
+
+impl VmObserver for Sounded {
+    fn on_sound_changed(&mut self, on: bool) {
+        if on {
+            self.0.set(true);
+        }
+    }
+}
+
+fn render(frames: usize) -> Vec<f32> {
+    let mut vm = VM::new(|| 0);
+    vm.load_rom(&assemble(BLIPS).unwrap()).unwrap();
+    let sounded = Rc::new(Cell::new(false));
+    vm.set_observer(Box::new(Sounded(Rc::clone(&sounded))));
+
+    let mut tone = Tone::new(SAMPLE_RATE, 440.0, VOLUME, RAMP_MS);
+    let mut samples = vec![0.0; frames * SAMPLES_PER_FRAME];
+    for frame in samples.chunks_mut(SAMPLES_PER_FRAME) {
+        sounded.set(false);
+        assert!(vm.run_frame(10).fault.is_none());
+        tone.fill(sounded.get() || vm.sound_timer > 0, frame);
+    }
+    samples
+}
+
+#[test]
+fn short_beeps_are_heard() {
+    let samples = render(60);
+    // from silence, the first frame beeping already
+    let onsets = [0.0]
+        .iter()
+        .chain(&samples)
+        .zip(&samples)
+        .filter(|&(&before, &sample)| before == 0.0 && sample != 0.0)
+        .count();
+    assert_eq!(onsets, 30);
+    // a one-frame beep is longer than the attack, so it gets to full volume
+    assert!(samples.iter().any(|&sample| sample.abs() == VOLUME));
+}
+
+#[test]
+fn repeated_short_beeps_never_clip_or_click() {
+    let samples = render(60);
+    assert!(samples.iter().all(|&sample| sample.abs() <= VOLUME));
+
+    // the square wave flips sign every half period, but its amplitude only
+    // ever moves by one envelope step per sample
+    let step = VOLUME / (SAMPLE_RATE as f32 * RAMP_MS / 1000.0);
+    for (idx, pair) in samples.windows(2).enumerate() {
+        let change = (pair[1].abs() - pair[0].abs()).abs();
+        assert!(
+            change <= step + 1e-6,
+            "amplitude jumps by {} at sample {}",
+            change,
+            idx + 1
+        );
+    }
+}
@@ -0,0 +1,24 @@
+// What VmConfig::validate lets through to a VM.
+
+use chip8_emu_rs::{Chip8Error, FONT, FONT_START, VmConfig};
+
+#[test]
+fn start_address_over_the_font_is_rejected() {
+    for addr in [0x000, 0x050, 0x09F] {
+        let result = VmConfig::new(|| 0).start_address(addr).build();
+        assert!(
+            matches!(result, Err(Chip8Error::InvalidConfig(ref message)) if message.contains("font")),
+            "{:04X} accepted",
+            addr
+        );
+    }
+}
+
+#[test]
+fn start_address_just_past_the_font_keeps_it_intact() {
+    let mut vm = VmConfig::new(|| 0).start_address(0x0A0).build().unwrap();
+    vm.load_rom(&[0x12, 0xA0]).unwrap();
+    assert_eq!(&vm.memory[FONT_START..FONT_START + FONT.len()], &FONT);
+    assert_eq!(&vm.memory[0x0A0..0x0A2], &[0x12, 0xA0]);
+    assert_eq!(vm.pc, 0x0A0);
+}
@@ -0,0 +1,82 @@
+// Golden frame hashes: every `tests/golden/NAME.asm` is assembled and run
+// for FRAMES frames with no keys pressed and the generator seeded with 0, as
+// --deterministic does, and the screen's hash after each frame must match
+// `tests/golden/NAME.frames`, in the format --record-frames writes. A new
+// ROM, or a change that's meant to alter what one shows, gets its golden
+// file (re)written by running this test with UPDATE_GOLDEN=1.
+
+use chip8_emu_rs::{VmConfig, assemble, instructions_per_frame};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FRAMES: usize = 120;
+const HEADER: &str = "# chip8-emu-rs frame hashes: one per frame, FNV-1a of the screen\n";
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+// the screen's hash after each of FRAMES frames of the ROM in `source`
+fn run(source: &str) -> Vec<u64> {
+    let mut vm = VmConfig::new(|| 0).rng_seed(0).build().unwrap();
+    vm.load_rom(&assemble(source).unwrap()).unwrap();
+    let ipf = instructions_per_frame(&vm);
+    (0..FRAMES)
+        .map(|_| {
+            let outcome = vm.run_frame(ipf);
+            assert!(outcome.fault.is_none(), "fault: {:?}", outcome.fault);
+            vm.framebuffer_hash()
+        })
+        .collect()
+}
+
+fn read(path: &Path) -> Vec<u64> {
+    fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("{}: {} (UPDATE_GOLDEN=1 writes it)", path.display(), e))
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| u64::from_str_radix(line, 16).unwrap())
+        .collect()
+}
+
+fn write(path: &Path, hashes: &[u64]) {
+    let mut text = HEADER.to_string();
+    for hash in hashes {
+        text.push_str(&format!("{:016x}\n", hash));
+    }
+    fs::write(path, text).unwrap();
+}
+
+#[test]
+fn roms_show_their_golden_frames() {
+    let mut sources: Vec<PathBuf> = fs::read_dir(golden_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asm"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    for source in sources {
+        let shown = run(&fs::read_to_string(&source).unwrap());
+        let golden = source.with_extension("frames");
+        if update {
+            write(&golden, &shown);
+            continue;
+        }
+        let expected = read(&golden);
+        assert_eq!(expected.len(), FRAMES, "{}", golden.display());
+        if let Some(frame) = expected.iter().zip(&shown).position(|(a, b)| a != b) {
+            panic!(
+                "{}: frame {} differs: screen hash {:016x}, expected {:016x}",
+                source.display(),
+                frame + 1,
+                shown[frame],
+                expected[frame]
+            );
+        }
+    }
+}
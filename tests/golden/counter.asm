@@ -0,0 +1,23 @@
+; counts up in V3, showing the count in decimal each time DT runs out
+        LD V5, 6
+    loop:
+        LD DT, V5
+    wait:
+        LD V0, DT
+        SE V0, 0
+        JP wait
+        ADD V3, 7
+        LD I, 0x300
+        LD B, V3
+        LD V2, [I]
+        CLS
+        LD V6, 20
+        LD F, V0
+        DRW V6, V6, 5
+        ADD V6, 6
+        LD F, V1
+        DRW V6, V5, 5
+        ADD V6, 6
+        LD F, V2
+        DRW V6, V6, 5
+        JP loop
@@ -0,0 +1,19 @@
+; random blocks falling down the screen, erasing where they overlap
+        LD I, block
+    loop:
+        RND V0, 0x3F
+        RND V1, 0x1F
+        DRW V0, V1, 3
+        ADD V2, VF
+        RND V3, 0x03
+        SE V3, 0
+        JP loop
+        LD V4, 1
+        LD DT, V4
+    wait:
+        LD V4, DT
+        SE V4, 0
+        JP wait
+        JP loop
+    block:
+        DB 0xE0, 0xA0, 0xE0
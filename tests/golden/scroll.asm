@@ -0,0 +1,20 @@
+; the 16 font digits marching right one column per frame, wrapping around
+    loop:
+        CLS
+        LD V1, 0
+        LD V2, V0
+    digit:
+        LD F, V1
+        DRW V2, V3, 5
+        ADD V2, 5
+        ADD V1, 1
+        SE V1, 16
+        JP digit
+        ADD V0, 1
+        LD V4, 1
+        LD DT, V4
+    wait:
+        LD V4, DT
+        SE V4, 0
+        JP wait
+        JP loop
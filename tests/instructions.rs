@@ -0,0 +1,245 @@
+// Single instructions and the quirks and settings that change them.
+
+use chip8_emu_rs::{Breakpoints, Chip8Error, Profile, Quirk, Quirks, ROM_START, VM, VmConfig};
+
+// `words` loaded at ROM_START into a VM built from `config`
+fn vm_with(config: VmConfig, words: &[u16]) -> VM {
+    let rom: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+    let mut vm = config.build().expect("valid config");
+    vm.load_font();
+    vm.load_rom(&rom).expect("ROM fits");
+    vm
+}
+
+fn vm(words: &[u16]) -> VM {
+    vm_with(VmConfig::new(|| 0), words)
+}
+
+#[test]
+fn bnnn_past_the_end_of_memory_wraps() {
+    // V0 = FF, JP V0, FFF: 0x10FE
+    let mut vm = vm(&[0x60FF, 0xBFFF]);
+    vm.run_cycles(2).unwrap();
+    assert_eq!(vm.pc, 0x0FE);
+}
+
+#[test]
+fn bnnn_past_the_end_of_memory_faults_with_strict_jumps() {
+    let mut vm = vm_with(VmConfig::new(|| 0).strict_jumps(true), &[0x60FF, 0xBFFF]);
+    vm.step().unwrap();
+    let pc = ROM_START as u16 + 2;
+    assert!(matches!(
+        vm.step(),
+        Err(Chip8Error::JumpOutOfBounds { target: 0x10FE, pc: at }) if at == pc
+    ));
+
+    // jumps that stay in memory are unaffected
+    let mut vm = vm_with(VmConfig::new(|| 0).strict_jumps(true), &[0x6010, 0xB300]);
+    vm.run_cycles(2).unwrap();
+    assert_eq!(vm.pc, 0x310);
+}
+
+#[test]
+fn skp_and_sknp_use_the_low_nibble_of_vx() {
+    // V0 = 0x25 names key 5; once panicked indexing key 0x25
+    let mut skp = vm(&[0x6025, 0xE09E, 0x6100, 0x6201]);
+    skp.key_down(5);
+    skp.run_cycles(3).unwrap();
+    assert_eq!(skp.v[2], 1);
+
+    let mut sknp = vm(&[0x60FF, 0xE0A1, 0x6101, 0x6201]);
+    sknp.run_cycles(3).unwrap();
+    assert_eq!(sknp.v[1], 0);
+    assert_eq!(sknp.v[2], 1);
+}
+
+#[test]
+fn fetching_from_the_last_address_faults() {
+    // only one byte of the opcode is in memory
+    let mut half = vm(&[0x1FFF]);
+    half.step().unwrap();
+    assert!(matches!(
+        half.step(),
+        Err(Chip8Error::PcOutOfBounds { pc: 0xFFF })
+    ));
+
+    // the last whole word still runs
+    let mut whole = vm(&[0x1FFE]);
+    whole.memory[0xFFE..].copy_from_slice(&[0x60, 0x07]);
+    whole.run_cycles(2).unwrap();
+    assert_eq!(whole.v[0], 7);
+    assert!(matches!(
+        whole.step(),
+        Err(Chip8Error::PcOutOfBounds { pc: 0x1000 })
+    ));
+
+    // and the same at the top of a 64K address space
+    let mut wide = vm_with(VmConfig::new(|| 0).memory_size(0x10000), &[0x6000]);
+    wide.pc = 0xFFFF;
+    assert!(matches!(
+        wide.step(),
+        Err(Chip8Error::PcOutOfBounds { pc: 0xFFFF })
+    ));
+}
+
+#[test]
+fn fx55_and_fx65_leave_i_past_the_registers_only_with_the_quirk() {
+    // LD I, 0x300; LD V0..V5 with 1..6; LD [I], V5; LD I, 0x300; LD V5, [I]
+    let words = [
+        0xA300, 0x6001, 0x6102, 0x6203, 0x6304, 0x6405, 0x6506, 0xF555,
+    ];
+    for (quirk, i) in [(true, 0x306), (false, 0x300)] {
+        let config = VmConfig::new(|| 0).quirk(Quirk::LoadStore, quirk);
+        let mut store = vm_with(config, &words);
+        store.run_cycles(words.len() as u64).unwrap();
+        assert_eq!(&store.memory[0x300..0x307], &[1, 2, 3, 4, 5, 6, 0]);
+        assert_eq!(store.i, i, "FX55 with the quirk {}", quirk);
+
+        let config = VmConfig::new(|| 0).quirk(Quirk::LoadStore, quirk);
+        let mut load = vm_with(config, &[0xA300, 0xF565]);
+        load.memory[0x300..0x307].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7]);
+        load.run_cycles(2).unwrap();
+        assert_eq!(&load.v[..7], &[1, 2, 3, 4, 5, 6, 0]);
+        assert_eq!(load.i, i, "FX65 with the quirk {}", quirk);
+    }
+}
+
+#[test]
+fn fx0a_is_waiting_until_a_key_is_down() {
+    // LD V3, K; LD V4, 1
+    let mut vm = vm(&[0xF30A, 0x6401]);
+    assert!(!vm.is_waiting_for_key());
+    vm.delay_timer = 5;
+
+    // the frame ends at the wait, and the timers still run
+    let outcome = vm.run_frame(10);
+    assert!(outcome.waiting_for_key);
+    assert_eq!(outcome.instructions, 1);
+    assert!(vm.is_waiting_for_key());
+    assert_eq!(vm.pc, ROM_START as u16);
+    assert_eq!(vm.delay_timer, 4);
+
+    // a snapshot taken mid-wait is still waiting when restored
+    let waiting = vm.snapshot();
+
+    vm.key_down(0xB);
+    vm.step().unwrap();
+    assert!(!vm.is_waiting_for_key());
+    assert_eq!(vm.v[3], 0xB);
+    assert_eq!(vm.pc, ROM_START as u16 + 2);
+
+    vm.restore(&waiting);
+    assert!(vm.is_waiting_for_key());
+    vm.reset();
+    assert!(!vm.is_waiting_for_key());
+}
+
+#[test]
+fn logic_ops_reset_vf_only_with_the_quirk() {
+    // LD V0, 0x0C; LD V1, 0x0A; LD VF, 1; then OR, AND or XOR V0, V1
+    for (op, result) in [(0x8011, 0x0E), (0x8012, 0x08), (0x8013, 0x06)] {
+        for (quirk, vf) in [(true, 0), (false, 1)] {
+            let config = VmConfig::new(|| 0).quirk(Quirk::VfReset, quirk);
+            let mut vm = vm_with(config, &[0x600C, 0x610A, 0x6F01, op]);
+            vm.run_cycles(4).unwrap();
+            assert_eq!(vm.v[0], result, "{:04X}", op);
+            assert_eq!(vm.v[0xF], vf, "{:04X} with the quirk {}", op, quirk);
+        }
+    }
+
+    // with VF as the target the reset comes last
+    let config = VmConfig::new(|| 0).quirk(Quirk::VfReset, true);
+    let mut vm = vm_with(config, &[0x6F0C, 0x610A, 0x8F11]);
+    vm.run_cycles(3).unwrap();
+    assert_eq!(vm.v[0xF], 0);
+}
+
+#[test]
+fn only_the_cosmac_vip_resets_vf_on_logic_ops() {
+    for (profile, on) in [
+        (Profile::CosmacVip, true),
+        (Profile::Chip48, false),
+        (Profile::SchipModern, false),
+        (Profile::XoChip, false),
+    ] {
+        assert_eq!(Quirks::for_profile(profile).get(Quirk::VfReset), on);
+    }
+}
+
+#[test]
+fn roms_under_two_bytes_are_refused() {
+    let mut vm = VM::new(|| 0);
+    for rom in [&[][..], &[0x12]] {
+        assert!(matches!(
+            vm.load_rom(rom),
+            Err(Chip8Error::RomTooSmall { size }) if size == rom.len()
+        ));
+        assert!(matches!(
+            vm.load_new_rom(rom),
+            Err(Chip8Error::RomTooSmall { size }) if size == rom.len()
+        ));
+    }
+    vm.load_rom(&[0x12, 0x00]).unwrap();
+}
+
+#[test]
+fn opcode_0000_only_advances_the_pc() {
+    // even with strict_sys, which faults on any other SYS
+    let mut cleared = vm_with(VmConfig::new(|| 0).strict_sys(true), &[0x0000, 0x0000]);
+    let before = (cleared.v, cleared.i, cleared.stack().to_vec());
+    let screen = cleared.framebuffer_hash();
+    cleared.run_cycles(2).unwrap();
+    assert_eq!(cleared.pc, ROM_START as u16 + 4);
+    assert_eq!((cleared.v, cleared.i, cleared.stack().to_vec()), before);
+    assert_eq!(cleared.framebuffer_hash(), screen);
+
+    let mut sys = vm_with(VmConfig::new(|| 0).strict_sys(true), &[0x0123]);
+    assert!(matches!(
+        sys.step(),
+        Err(Chip8Error::InvalidOpcode { opcode: 0x0123, .. })
+    ));
+}
+
+#[test]
+fn fx1e_moves_i_past_the_end_without_faulting() {
+    // LD I, 0xFFF; LD V0, 0x10; ADD I, V0
+    let mut past = vm(&[0xAFFF, 0x6010, 0xF01E]);
+    past.run_cycles(3).unwrap();
+    assert_eq!(past.i, 0x100F);
+
+    // it wraps at 16 bits, back into memory, where I can be used again:
+    // LD V0, 0x20; ADD I, V0; LD V0, [I]
+    let mut wrap = vm(&[0x6020, 0xF01E, 0xF065]);
+    wrap.i = 0xFFF0;
+    wrap.memory[0x10] = 0x42;
+    wrap.run_cycles(3).unwrap();
+    assert_eq!((wrap.i, wrap.v[0]), (0x0010, 0x42));
+}
+
+#[test]
+fn display_wait_ends_the_frame_after_a_draw() {
+    // CLS; DRW V0, V0, 5; ADD V1, 1; JP 0x202
+    let words = [0x00E0, 0xD005, 0x7101, 0x1202];
+    let waiting = VmConfig::new(|| 0).quirk(Quirk::DisplayWait, true);
+
+    let mut paced = vm_with(waiting.clone(), &words);
+    paced.delay_timer = 5;
+    let outcome = paced.run_frame(30);
+    // CLS doesn't end it, the draw does, and the timers still tick
+    assert_eq!((outcome.instructions, outcome.drew), (2, true));
+    assert_eq!(paced.delay_timer, 4);
+    let outcome = paced.run_frame(30);
+    assert_eq!((outcome.instructions, paced.v[1]), (3, 1));
+
+    // breakpoints elsewhere don't change that
+    let mut stopped = vm_with(waiting, &words);
+    let breakpoints: Breakpoints = [0x300].into_iter().collect();
+    let (outcome, hit) = breakpoints.run_frame(&mut stopped, 30);
+    assert_eq!((outcome.instructions, hit), (2, None));
+
+    let mut free = vm_with(VmConfig::new(|| 0), &words);
+    assert_eq!(free.run_frame(30).instructions, 30);
+
+    assert!(Quirks::for_profile(Profile::CosmacVip).display_wait);
+    assert!(!Quirks::for_profile(Profile::SchipModern).display_wait);
+}
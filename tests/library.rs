@@ -0,0 +1,124 @@
+// End-to-end runs of small ROMs through the library API alone, with no
+// frontend and randomness injected by the test.
+
+use chip8_emu_rs::{FB_HEIGHT, FB_WIDTH, FONT, FONT_START, ROM_START, VM, VmConfig, assemble};
+
+fn vm(source: &str) -> VM {
+    let mut vm = VM::new(|| 0x5A);
+    vm.load_font();
+    vm.load_rom(&assemble(source).expect("test program assembles"))
+        .expect("test ROM fits");
+    vm
+}
+
+// runs frames until the program jumps to itself
+fn run_to_halt(vm: &mut VM) {
+    for _ in 0..60 {
+        let outcome = vm.run_frame(100);
+        assert!(outcome.fault.is_none(), "fault: {:?}", outcome.fault);
+        if outcome.halted {
+            return;
+        }
+    }
+    panic!("program didn't halt within 60 frames");
+}
+
+#[test]
+fn draws_a_font_digit() {
+    let mut vm = vm("
+        LD V0, 7
+        LD F, V0
+        LD V1, 10
+        LD V2, 4
+        DRW V1, V2, 5
+    end:
+        JP end
+    ");
+    run_to_halt(&mut vm);
+
+    let glyph = &FONT[7 * 5..8 * 5];
+    for y in 0..FB_HEIGHT {
+        for x in 0..FB_WIDTH {
+            let lit = (4..9).contains(&y)
+                && (10..18).contains(&x)
+                && glyph[(y - 4) as usize] & (0x80 >> (x - 10)) != 0;
+            assert_eq!(vm.pixel(x, y), lit, "pixel ({}, {})", x, y);
+        }
+    }
+    assert_eq!(vm.v[0xF], 0);
+    assert_eq!(vm.i, FONT_START as u16 + 7 * 5);
+}
+
+#[test]
+fn drawing_twice_erases_and_sets_vf() {
+    let mut vm = vm("
+        LD F, V0
+        DRW V0, V0, 5
+        DRW V0, V0, 5
+    end:
+        JP end
+    ");
+    run_to_halt(&mut vm);
+    assert_eq!(vm.lit_pixels().count(), 0);
+    assert_eq!(vm.v[0xF], 1);
+}
+
+#[test]
+fn subroutines_and_bcd() {
+    let mut vm = vm("
+        LD V5, 137
+        CALL digits
+    end:
+        JP end
+    digits:
+        LD I, 0x300
+        LD B, V5
+        LD V2, [I]
+        RET
+    ");
+    run_to_halt(&mut vm);
+    assert_eq!(&vm.memory[0x300..0x303], &[1, 3, 7]);
+    assert_eq!(vm.v[..3], [1, 3, 7]);
+    assert!(vm.stack().is_empty());
+}
+
+#[test]
+fn randomness_comes_from_the_injected_source() {
+    let mut vm = VmConfig::new(|| 0x5A).build().expect("default config");
+    vm.load_rom(&assemble("RND V3, 0x0F").unwrap()).unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.v[3], 0x0A);
+
+    // a seeded generator repeats itself
+    let run = || {
+        let mut vm = VmConfig::new(|| 0).rng_seed(42).build().unwrap();
+        vm.load_rom(&assemble("RND V0, 0xFF\nRND V1, 0xFF").unwrap())
+            .unwrap();
+        vm.run_cycles(2).unwrap();
+        [vm.v[0], vm.v[1]]
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn keys_and_timers() {
+    let mut vm = vm("
+        LD V0, 30
+        LD DT, V0
+    wait:
+        LD V1, K
+        LD V2, DT
+    end:
+        JP end
+    ");
+    vm.run_frame(10);
+    assert!(vm.is_waiting_for_key());
+    assert_eq!(vm.delay_timer, 29);
+
+    vm.key_down(0xB);
+    assert!(vm.is_key_down(0xB));
+    run_to_halt(&mut vm);
+    assert_eq!(vm.v[1], 0xB);
+    assert_eq!(vm.v[2], 29);
+    assert_eq!(vm.pc as usize, ROM_START + 8);
+}
@@ -0,0 +1,50 @@
+// Memory accesses at the top of the address space.
+
+use chip8_emu_rs::{Chip8Error, VM, VmConfig};
+
+fn vm(rom: &[u8]) -> VM {
+    let mut vm = VM::new(|| 0);
+    vm.load_rom(rom).unwrap();
+    vm
+}
+
+fn out_of_bounds_at(result: Result<impl std::fmt::Debug, Chip8Error>) -> usize {
+    match result {
+        Err(Chip8Error::MemoryOutOfBounds { addr }) => addr,
+        other => panic!("expected MemoryOutOfBounds, got {:?}", other),
+    }
+}
+
+#[test]
+fn read_mem_reaches_the_last_byte_and_no_further() {
+    let mut vm = vm(&[0x00, 0xE0]);
+    vm.memory[0xFFF] = 0x42;
+    assert_eq!(vm.read_mem(0xFFF).unwrap(), 0x42);
+    assert_eq!(out_of_bounds_at(vm.read_mem(0x1000)), 0x1000);
+    assert_eq!(out_of_bounds_at(vm.read_mem(0xFFFF)), 0xFFFF);
+
+    let mut wide = VmConfig::new(|| 0).memory_size(0x10000).build().unwrap();
+    wide.memory[0xFFFF] = 0x24;
+    assert_eq!(wide.read_mem(0xFFFF).unwrap(), 0x24);
+}
+
+#[test]
+fn accesses_running_off_the_end_fault_before_touching_memory() {
+    // LD I, 0xFFE; LD V0, 123; LD B, V0
+    let mut bcd = vm(&[0xAF, 0xFE, 0x60, 0x7B, 0xF0, 0x33]);
+    bcd.run_cycles(2).unwrap();
+    assert_eq!(out_of_bounds_at(bcd.step()), 0x1000);
+    assert_eq!(&bcd.memory[0xFFE..], &[0, 0]);
+
+    // LD I, 0xFFF; LD V0, [I] reads just the last byte
+    let mut load = vm(&[0xAF, 0xFF, 0xF0, 0x65]);
+    load.memory[0xFFF] = 9;
+    load.run_cycles(2).unwrap();
+    assert_eq!(load.v[0], 9);
+
+    // LD I, 0xFFF; LD V1, [I] wants one more
+    let mut load = vm(&[0xAF, 0xFF, 0xF1, 0x65]);
+    load.step().unwrap();
+    assert_eq!(out_of_bounds_at(load.step()), 0x1000);
+    assert_eq!(load.v[..2], [0, 0]);
+}
@@ -0,0 +1,88 @@
+// What a VmObserver hears from a known ROM.
+
+use chip8_emu_rs::{FrameInfo, VM, VmObserver, assemble};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// draws the digits 0-9 across the top row through a subroutine, then halts
+const DIGITS: &str = "
+        LD V0, 0
+        LD V1, 0
+    loop:
+        CALL digit
+        ADD V0, 1
+        ADD V1, 5
+        SE V0, 10
+        JP loop
+    done:
+        JP done
+    digit:
+        LD F, V0
+        DRW V1, V2, 5
+        RET
+";
+
+#[derive(Default)]
+struct Seen {
+    draws: Vec<FrameInfo>,
+    calls: u32,
+    rets: u32,
+}
+
+struct Recorder(Rc<RefCell<Seen>>);
+
+impl VmObserver for Recorder {
+    fn on_draw(&mut self, frame: &FrameInfo) {
+        self.0.borrow_mut().draws.push(*frame);
+    }
+    fn on_call(&mut self, _addr: u16) {
+        self.0.borrow_mut().calls += 1;
+    }
+    fn on_ret(&mut self, _addr: u16) {
+        self.0.borrow_mut().rets += 1;
+    }
+}
+
+fn run_to_halt(vm: &mut VM) {
+    for _ in 0..10 {
+        let outcome = vm.run_frame(100);
+        assert!(outcome.fault.is_none(), "fault: {:?}", outcome.fault);
+        if outcome.halted {
+            return;
+        }
+    }
+    panic!("program didn't halt within 10 frames");
+}
+
+fn watched() -> (VM, Rc<RefCell<Seen>>) {
+    let mut vm = VM::new(|| 0);
+    vm.load_rom(&assemble(DIGITS).unwrap()).unwrap();
+    let seen = Rc::new(RefCell::new(Seen::default()));
+    vm.set_observer(Box::new(Recorder(Rc::clone(&seen))));
+    (vm, seen)
+}
+
+#[test]
+fn every_draw_call_and_return_is_reported() {
+    let (mut vm, seen) = watched();
+    run_to_halt(&mut vm);
+
+    let seen = seen.borrow();
+    assert_eq!(seen.draws.len(), 10);
+    assert_eq!((seen.calls, seen.rets), (10, 10));
+    for (digit, draw) in seen.draws.iter().enumerate() {
+        assert_eq!((draw.x, draw.y, draw.height), (digit as u8 * 5, 0, 5));
+        assert_eq!(draw.sprite, 0x50 + digit as u16 * 5);
+        // side by side, so nothing overlaps
+        assert_eq!(draw.collisions, 0);
+    }
+}
+
+#[test]
+fn the_observer_keeps_counting_after_a_reset() {
+    let (mut vm, seen) = watched();
+    run_to_halt(&mut vm);
+    vm.reset();
+    run_to_halt(&mut vm);
+    assert_eq!(seen.borrow().draws.len(), 20);
+}
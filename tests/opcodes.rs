@@ -0,0 +1,82 @@
+// `OPCODES`, which --list-opcodes prints, against the decoder and the
+// disassembler, over every 16-bit word.
+
+use chip8_emu_rs::{Instruction, OPCODES, lookup};
+
+// "LD Vx, NN" as the mnemonic and its operands; "SHR Vx {, Vy}" lists Vy and
+// "SYS NNN (ignored)" loses its note
+fn table_form(mnemonic: &str) -> (String, Vec<String>) {
+    let text = mnemonic.split(" (").next().unwrap().replace(['{', '}'], "");
+    split(&text)
+}
+
+fn split(text: &str) -> (String, Vec<String>) {
+    let (name, operands) = text.split_once(' ').unwrap_or((text, ""));
+    let operands = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .map(String::from)
+        .collect();
+    (name.to_string(), operands)
+}
+
+// whether a disassembled operand fits the table's placeholder for it
+fn fits(placeholder: &str, operand: &str) -> bool {
+    match placeholder {
+        "Vx" | "Vy" => operand.len() == 2 && operand.starts_with('V'),
+        "N" => operand.parse::<u8>().is_ok(),
+        "NN" | "NNN" => operand.starts_with("0x"),
+        _ => placeholder == operand,
+    }
+}
+
+#[test]
+fn table_covers_exactly_the_decoded_instructions() {
+    for word in 0..=u16::MAX {
+        let decoded = Instruction::decode(word);
+        let entry = lookup(word);
+        assert_eq!(
+            entry.is_some(),
+            !matches!(decoded, Instruction::Unknown(_)),
+            "{:04X} decodes to {:?}",
+            word,
+            decoded
+        );
+    }
+}
+
+#[test]
+fn table_mnemonics_match_the_disassembler() {
+    for word in 0..=u16::MAX {
+        let Some(entry) = lookup(word) else {
+            continue;
+        };
+        let (name, placeholders) = table_form(entry.mnemonic);
+        let (shown, operands) = split(&Instruction::decode(word).to_string());
+        assert_eq!(name, shown, "{:04X} in {}", word, entry.pattern);
+        assert_eq!(placeholders.len(), operands.len(), "{:04X}", word);
+        for (placeholder, operand) in placeholders.iter().zip(&operands) {
+            assert!(
+                fits(placeholder, operand),
+                "{:04X}: {} shown for {} in '{}'",
+                word,
+                operand,
+                placeholder,
+                entry.mnemonic
+            );
+        }
+    }
+}
+
+#[test]
+fn every_entry_is_reachable() {
+    for entry in OPCODES {
+        assert!(
+            (0..=u16::MAX)
+                .any(|word| lookup(word).is_some_and(|found| found.pattern == entry.pattern)),
+            "{} is shadowed by an earlier entry",
+            entry.pattern
+        );
+    }
+}
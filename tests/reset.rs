@@ -0,0 +1,126 @@
+// VM::reset against a freshly built VM, with a ROM that patches its own code.
+
+use chip8_emu_rs::{Profile, VM, VmConfig, assemble};
+
+// counts to 20 in V6, writing the count into the immediate of `patched` with
+// FX55 each time round, and draws a random column of digits on the way
+const SELF_MODIFYING: &str = "
+    start:
+        ADD V6, 1
+        LD V0, 0x65
+        LD V1, V6
+        LD I, patched
+        LD [I], V1
+    patched:
+        LD V5, 0
+        LD V7, V5
+        LD V8, 0x0F
+        AND V7, V8
+        LD F, V7
+        RND V2, 0x3F
+        DRW V2, V5, 5
+        SE V5, 20
+        JP start
+    done:
+        JP done
+";
+
+fn config() -> VmConfig {
+    VmConfig::new(|| 0).rng_seed(7).profile(Profile::CosmacVip)
+}
+
+fn boot() -> VM {
+    let mut vm = config().build().unwrap();
+    vm.load_rom(&assemble(SELF_MODIFYING).unwrap()).unwrap();
+    vm
+}
+
+fn run_to_halt(vm: &mut VM) {
+    for _ in 0..100 {
+        let outcome = vm.run_frame(15);
+        assert!(outcome.fault.is_none(), "fault: {:?}", outcome.fault);
+        if outcome.halted {
+            return;
+        }
+    }
+    panic!("program didn't halt within 100 frames");
+}
+
+// everything a program can observe or change
+#[derive(Debug, PartialEq)]
+struct State {
+    memory: Vec<u8>,
+    v: [u8; 16],
+    pc: u16,
+    i: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    cycles: u64,
+    frames: u64,
+    screen: u64,
+    keys: [bool; 16],
+    waiting_for_key: bool,
+}
+
+fn state(vm: &VM) -> State {
+    State {
+        memory: vm.memory.clone(),
+        v: vm.v,
+        pc: vm.pc,
+        i: vm.i,
+        stack: vm.stack().to_vec(),
+        delay_timer: vm.delay_timer,
+        sound_timer: vm.sound_timer,
+        cycles: vm.cycles(),
+        frames: vm.frames(),
+        screen: vm.framebuffer_hash(),
+        keys: vm.keys(),
+        waiting_for_key: vm.is_waiting_for_key(),
+    }
+}
+
+#[test]
+fn reset_matches_a_fresh_boot() {
+    let mut vm = boot();
+    vm.key_down(3);
+    vm.delay_timer = 9;
+    run_to_halt(&mut vm);
+    assert_ne!(state(&vm), state(&boot()));
+
+    vm.reset();
+    assert_eq!(state(&vm), state(&boot()));
+}
+
+#[test]
+fn self_modifying_rom_runs_identically_after_reset() {
+    let mut vm = boot();
+    run_to_halt(&mut vm);
+    let first = state(&vm);
+    // the program did rewrite itself, and drew
+    assert!(first.memory.windows(2).any(|word| word == [0x65, 20]));
+    assert_ne!(first.screen, boot().framebuffer_hash());
+
+    vm.reset();
+    run_to_halt(&mut vm);
+    assert_eq!(state(&vm), first);
+}
+
+#[test]
+fn load_new_rom_keeps_the_configuration() {
+    let mut vm = VmConfig::new(|| 0)
+        .profile(Profile::SchipModern)
+        .start_address(0x300)
+        .build()
+        .unwrap();
+    vm.load_rom(&[0x12, 0x34]).unwrap();
+    let quirks = vm.quirks;
+
+    vm.load_new_rom(&[0x60, 0x01, 0x13, 0x02]).unwrap();
+    assert_eq!(vm.quirks, quirks);
+    assert_eq!(vm.pc, 0x300);
+    assert_eq!(vm.rom(), &[0x60, 0x01, 0x13, 0x02]);
+    assert_eq!(&vm.memory[0x300..0x304], vm.rom());
+    vm.run_frame(10);
+    assert_eq!(vm.v[0], 1);
+}
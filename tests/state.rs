@@ -0,0 +1,287 @@
+// Serialized VM state: its encoding, round trips through postcard, bincode
+// and JSON, and what loading refuses and why.
+
+use chip8_emu_rs::{
+    Chip8Error, STATE_VERSION, StateError, VM, VmConfig, assemble, instructions_per_frame,
+};
+
+// picked so it's easy to find in the bytes: the RNG follows it
+const CPU_HZ: f64 = 1234.5;
+
+fn vm() -> VM {
+    let mut vm = VmConfig::new(|| 0)
+        .rng_seed(5)
+        .cpu_hz(CPU_HZ)
+        .build()
+        .unwrap();
+    vm.load_rom(&[0x12, 0x00]).unwrap();
+    vm
+}
+
+fn save(vm: &VM) -> Vec<u8> {
+    postcard::to_stdvec(vm).unwrap()
+}
+
+fn load(bytes: &[u8]) -> Result<VM, postcard::Error> {
+    postcard::from_bytes(bytes)
+}
+
+// postcard drops a custom error's message, so this only sees that the state
+// was refused; the tests check the StateError's message on its own
+fn refused(bytes: &[u8]) {
+    assert!(
+        matches!(load(bytes), Err(postcard::Error::SerdeDeCustom)),
+        "state was accepted"
+    );
+}
+
+#[test]
+fn any_i_loads_and_faults_only_when_used() {
+    // FX1E can leave I anywhere, so a state can hold any I at all
+    for i in [0x1000, 0x1001, 0xFFFF] {
+        let mut vm = vm();
+        vm.i = i;
+        let mut loaded = load(&save(&vm)).unwrap();
+        assert_eq!(loaded.i, i);
+
+        // LD V0, [I]
+        loaded.pc = 0x300;
+        loaded.memory[0x300..0x302].copy_from_slice(&[0xF0, 0x65]);
+        assert!(matches!(
+            loaded.step(),
+            Err(Chip8Error::MemoryOutOfBounds { addr }) if addr == i as usize
+        ));
+    }
+}
+
+#[test]
+fn a_zero_rng_state_is_refused() {
+    let mut bytes = save(&vm());
+    // cpu_hz, then RngState::Seeded as variant 1, the seed 5 and the state
+    // as a varint, which becomes a single 0
+    let hz = CPU_HZ.to_le_bytes();
+    let at = bytes
+        .windows(hz.len())
+        .position(|window| window == hz)
+        .unwrap()
+        + hz.len();
+    assert_eq!(&bytes[at..at + 2], &[1, 5]);
+    let state = at + 2;
+    let len = bytes[state..].iter().position(|&b| b < 0x80).unwrap() + 1;
+    bytes.splice(state..state + len, [0]);
+
+    refused(&bytes);
+    assert_eq!(
+        StateError::RngState.to_string(),
+        "random generator state is zero"
+    );
+}
+
+// a VM with something in every part of the state: registers, a call on the
+// stack, memory written by the program, pixels, both timers, a key held, a
+// frozen byte and the generator moved on from its seed
+fn busy() -> VM {
+    let mut vm = VmConfig::new(|| 0)
+        .rng_seed(99)
+        .cpu_hz(CPU_HZ)
+        .build()
+        .unwrap();
+    // CALL 0x206; JP 0x204; RND V0, 0xFF; LD F, V0; DRW V0, V0, 5;
+    // LD I, 0x300; LD B, V0; LD ST, V0; LD DT, V0; JP 0x214
+    vm.load_rom(&[
+        0x22, 0x06, 0x12, 0x04, 0x12, 0x04, 0xC0, 0xFF, 0xF0, 0x29, 0xD0, 0x05, 0xA3, 0x00, 0xF0,
+        0x33, 0xF0, 0x18, 0xF0, 0x15, 0x12, 0x14,
+    ])
+    .unwrap();
+    vm.freeze(0x310, 0x77).unwrap();
+    vm.key_down(0xC);
+    vm.run_cycles(12).unwrap();
+    vm.step_timers(3);
+    vm
+}
+
+#[test]
+fn a_saved_vm_loads_back_the_same() {
+    let vm = busy();
+    let bytes = save(&vm);
+    let mut loaded = load(&bytes).unwrap();
+    assert_eq!(save(&loaded), bytes);
+
+    assert_eq!((loaded.pc, loaded.i, loaded.v), (vm.pc, vm.i, vm.v));
+    assert_eq!(loaded.stack(), vm.stack());
+    assert_eq!(loaded.memory, vm.memory);
+    assert_eq!(loaded.framebuffer_hash(), vm.framebuffer_hash());
+    assert_eq!(
+        (loaded.delay_timer, loaded.sound_timer),
+        (vm.delay_timer, vm.sound_timer)
+    );
+    assert_eq!(loaded.keys(), vm.keys());
+    assert_eq!(
+        (loaded.cycles(), loaded.frames()),
+        (vm.cycles(), vm.frames())
+    );
+    assert_eq!(loaded.rom(), vm.rom());
+    assert_eq!(loaded.freezes(), vm.freezes());
+
+    // and carries on the same, random numbers included
+    let mut vm = vm;
+    for vm in [&mut vm, &mut loaded] {
+        vm.pc = 0x206;
+        vm.run_cycles(3).unwrap();
+    }
+    assert_eq!(loaded.v, vm.v);
+    assert_eq!(loaded.framebuffer_hash(), vm.framebuffer_hash());
+}
+
+// draws random font digits at random places, with the sound and delay
+// timers running and a key deciding whether to clear the screen first
+const RANDOM_DRAWS: &str = "
+        LD V5, 30
+    loop:
+        RND V0, 0x0F
+        RND V1, 0x3F
+        RND V2, 0x1F
+        SKNP V5
+        CLS
+        LD F, V0
+        DRW V1, V2, 5
+        LD ST, V0
+        LD V3, DT
+        SE V3, 0
+        JP loop
+        LD DT, V5
+        CALL count
+        JP loop
+    count:
+        ADD V4, 1
+        LD I, 0x300
+        LD B, V4
+        RET
+";
+
+// RANDOM_DRAWS a few frames in, its generator well past the seed
+fn running() -> VM {
+    let mut vm = VmConfig::new(|| 0).rng_seed(0xC0FFEE).build().unwrap();
+    vm.load_rom(&assemble(RANDOM_DRAWS).unwrap()).unwrap();
+    vm.set_key(0xE, true);
+    for _ in 0..7 {
+        vm.run_frame(instructions_per_frame(&vm));
+    }
+    vm
+}
+
+// `restored` runs on exactly as `original` does, frame by frame: registers,
+// the numbers RND gives, timers and the screen
+fn runs_on_the_same(mut original: VM, mut restored: VM) {
+    let ipf = instructions_per_frame(&original);
+    assert_eq!(instructions_per_frame(&restored), ipf);
+    for frame in 0..300 {
+        if frame == 100 {
+            original.set_key(0xE, false);
+            restored.set_key(0xE, false);
+        }
+        let outcome = original.run_frame(ipf);
+        assert!(outcome.fault.is_none(), "{:?}", outcome.fault);
+        restored.run_frame(ipf);
+        let state = |vm: &VM| {
+            (
+                vm.v,
+                vm.pc,
+                vm.i,
+                vm.stack().to_vec(),
+                (vm.delay_timer, vm.sound_timer),
+                vm.framebuffer_hash(),
+            )
+        };
+        assert_eq!(state(&restored), state(&original), "frame {}", frame);
+    }
+    assert_eq!(restored.memory, original.memory);
+}
+
+#[test]
+fn a_postcard_round_trip_runs_on_the_same() {
+    let vm = running();
+    let restored = load(&save(&vm)).unwrap();
+    runs_on_the_same(vm, restored);
+}
+
+#[test]
+fn a_bincode_round_trip_runs_on_the_same() {
+    let vm = running();
+    let restored = bincode::deserialize(&bincode::serialize(&vm).unwrap()).unwrap();
+    runs_on_the_same(vm, restored);
+}
+
+#[test]
+fn a_json_round_trip_runs_on_the_same() {
+    let vm = running();
+    let restored = serde_json::from_str(&serde_json::to_string(&vm).unwrap()).unwrap();
+    runs_on_the_same(vm, restored);
+}
+
+// CALL 0x204; JP 0x202; RET
+const SMALL_ROM: [u8; 6] = [0x22, 0x04, 0x12, 0x02, 0x00, 0xEE];
+
+// a VM one CALL into SMALL_ROM, with nothing else in memory
+fn small() -> VM {
+    let mut vm = VmConfig::new(|| 0).cpu_hz(500.0).build().unwrap();
+    vm.load_rom(&SMALL_ROM).unwrap();
+    vm.memory.fill(0);
+    vm.memory[0x200..0x206].copy_from_slice(&SMALL_ROM);
+    vm.step().unwrap();
+    vm.i = 0x1234;
+    vm.delay_timer = 60;
+    vm
+}
+
+// `small()` in version 10 of the state, written out by hand field by field.
+// A change to the encoding has to bump STATE_VERSION and rewrite this.
+fn small_fixture() -> Vec<u8> {
+    let mut bytes = vec![10];
+    // V0-VF
+    bytes.extend([0; 16]);
+    // PC and I, big-endian
+    bytes.extend([0x02, 0x04, 0x12, 0x34]);
+    // memory: its length as a varint, then every byte
+    bytes.extend([0x80, 0x20]);
+    bytes.extend([0; 0x200]);
+    bytes.extend([0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]);
+    bytes.extend([0; 0x1000 - 0x206]);
+    // the stack: one return address, big-endian
+    bytes.extend([1, 0x02, 0x02]);
+    // 32 blank rows, then draw_flag and last_draw_collisions
+    bytes.extend([0; 32]);
+    bytes.extend([0, 0]);
+    // 16 keys up, DT = 60, ST = 0
+    bytes.extend([0; 16]);
+    bytes.extend([0x3C, 0]);
+    // six quirks off, then the start address 0x200 as a varint
+    bytes.extend([0; 6]);
+    bytes.extend([0x80, 0x04]);
+    // 500.0 Hz as a little-endian f64
+    bytes.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x7F, 0x40]);
+    // an injected generator, 1 cycle, 0 frames, not waiting for a key
+    bytes.extend([0, 1, 0, 0]);
+    // the ROM, with its length
+    bytes.extend([6, 0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]);
+    // no frozen bytes
+    bytes.push(0);
+    bytes
+}
+
+#[test]
+fn the_encoding_matches_the_fixture_for_this_version() {
+    assert_eq!(STATE_VERSION, 10, "a new STATE_VERSION needs a new fixture");
+    let fixture = small_fixture();
+    assert_eq!(
+        save(&small()),
+        fixture,
+        "VM state encoding changed without a version bump"
+    );
+
+    let loaded = load(&fixture).unwrap();
+    assert_eq!((loaded.pc, loaded.i), (0x204, 0x1234));
+    assert_eq!(loaded.stack(), [0x202]);
+    assert_eq!(loaded.delay_timer, 60);
+    assert_eq!(loaded.rom(), SMALL_ROM);
+}
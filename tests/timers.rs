@@ -0,0 +1,58 @@
+// The delay and sound timers over several frames at once.
+
+use chip8_emu_rs::{VM, VmObserver};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Sound(Rc<RefCell<Vec<bool>>>);
+
+impl VmObserver for Sound {
+    fn on_sound_changed(&mut self, on: bool) {
+        self.0.borrow_mut().push(on);
+    }
+}
+
+#[test]
+fn more_frames_than_left_stop_at_zero() {
+    let mut vm = VM::new(|| 0);
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    vm.set_observer(Box::new(Sound(Rc::clone(&changes))));
+    // through the program, so the observer hears it start
+    vm.load_rom(&[0x60, 0x02, 0xF0, 0x18]).unwrap();
+    vm.run_cycles(2).unwrap();
+    assert_eq!(vm.sound_timer, 2);
+    vm.delay_timer = 2;
+
+    vm.step_timers(3);
+    assert_eq!((vm.delay_timer, vm.sound_timer), (0, 0));
+    assert_eq!(vm.frames(), 3);
+    assert_eq!(*changes.borrow(), [true, false]);
+
+    // and stay there
+    vm.step_timers(255);
+    assert_eq!((vm.delay_timer, vm.sound_timer), (0, 0));
+    assert_eq!(vm.frames(), 258);
+    assert_eq!(changes.borrow().len(), 2);
+}
+
+#[test]
+fn several_frames_count_down_as_far_as_one_at_a_time() {
+    let mut batched = VM::new(|| 0);
+    let mut single = VM::new(|| 0);
+    for vm in [&mut batched, &mut single] {
+        vm.delay_timer = 200;
+        vm.sound_timer = 90;
+    }
+    batched.step_timers(100);
+    for _ in 0..100 {
+        single.step_timers(1);
+    }
+    assert_eq!((batched.delay_timer, batched.sound_timer), (100, 0));
+    assert_eq!(
+        (single.delay_timer, single.sound_timer, single.frames()),
+        (batched.delay_timer, batched.sound_timer, batched.frames())
+    );
+
+    batched.step_timers(0);
+    assert_eq!((batched.delay_timer, batched.frames()), (100, 100));
+}
@@ -0,0 +1,66 @@
+// Watchpoints as run_frame reports them.
+
+use chip8_emu_rs::{Access, Breakpoints, VM, Watchpoint, assemble};
+
+// stores V0 at 0x300 once, then counts in V1 forever
+const STORE_ONCE: &str = "
+        LD V0, 0x2A
+        LD I, 0x300
+        LD [I], V0
+    loop:
+        ADD V1, 1
+        JP loop
+";
+
+fn vm() -> VM {
+    let mut vm = VM::new(|| 0);
+    vm.load_rom(&assemble(STORE_ONCE).unwrap()).unwrap();
+    vm.add_watchpoint(Watchpoint {
+        start: 0x300,
+        end: 0x300,
+        read: false,
+        write: true,
+    });
+    vm
+}
+
+#[test]
+fn a_watch_hit_ends_one_frame_only() {
+    let mut vm = vm();
+    let first = vm.run_frame(20);
+    assert_eq!(first.instructions, 3);
+    let hit = first.watch_hit.expect("FX55 hits the watchpoint");
+    assert_eq!(
+        (hit.pc, hit.addr, hit.access),
+        (0x204, 0x300, Access::Write)
+    );
+    assert_eq!((hit.old, hit.new), (0, 0x2A));
+    // handed over, not left pending
+    assert_eq!(vm.take_watch_hit(), None);
+
+    // once reported, the hit no longer cuts frames short
+    for _ in 0..3 {
+        let outcome = vm.run_frame(20);
+        assert_eq!(outcome.instructions, 20);
+        assert!(outcome.watch_hit.is_none());
+    }
+}
+
+#[test]
+fn breakpoint_frames_hand_over_the_hit_too() {
+    let mut vm = vm();
+    let breakpoints = Breakpoints::new();
+    let (first, _) = breakpoints.run_frame(&mut vm, 20);
+    assert_eq!(first.watch_hit.map(|hit| hit.pc), Some(0x204));
+    let (second, _) = breakpoints.run_frame(&mut vm, 20);
+    assert_eq!(second.instructions, 20);
+    assert!(second.watch_hit.is_none());
+}
+
+#[test]
+fn step_leaves_the_hit_to_be_taken() {
+    let mut vm = vm();
+    vm.run_cycles(4).unwrap();
+    assert_eq!(vm.take_watch_hit().map(|hit| hit.pc), Some(0x204));
+    assert_eq!(vm.take_watch_hit(), None);
+}
@@ -0,0 +1,109 @@
+// Browser frontend: loads a ROM from the file picker and runs the emulator at
+// 60 frames a second whatever the display's refresh rate, drawing to the
+// canvas and gating a square wave on the sound timer.
+import init, { Emulator } from "./pkg/chip8_emu_rs_web.js";
+
+// same layout as the SDL frontend: the left block of a QWERTY keyboard
+const KEYMAP = {
+  Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xc,
+  KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xd,
+  KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xe,
+  KeyZ: 0xa, KeyX: 0x0, KeyC: 0xb, KeyV: 0xf,
+};
+const BG = [255, 176, 0];
+const FG = [60, 57, 60];
+const FRAME_MS = 1000 / 60;
+// frames to catch up on at most, after the tab was hidden or the page stalled
+const MAX_FRAMES = 4;
+
+const canvas = document.getElementById("screen");
+const ctx = canvas.getContext("2d");
+const status = document.getElementById("status");
+
+await init();
+const emu = new Emulator((Math.random() * 0x100000000) >>> 0);
+const image = ctx.createImageData(Emulator.width(), Emulator.height());
+let running = false;
+// time not yet run, and the timestamp of the last animation frame
+let acc = 0;
+let last = null;
+
+// created on the first ROM load, since browsers only allow audio after a
+// user gesture
+let audio = null;
+let gain = null;
+
+function startAudio() {
+  if (audio) return;
+  audio = new AudioContext();
+  const osc = audio.createOscillator();
+  osc.type = "square";
+  osc.frequency.value = 440;
+  gain = audio.createGain();
+  gain.gain.value = 0;
+  osc.connect(gain).connect(audio.destination);
+  osc.start();
+}
+
+function draw() {
+  const pixels = emu.framebuffer();
+  for (let i = 0; i < pixels.length; i++) {
+    const [r, g, b] = pixels[i] ? FG : BG;
+    image.data.set([r, g, b, 255], i * 4);
+  }
+  ctx.putImageData(image, 0, 0);
+}
+
+// requestAnimationFrame fires at the display's rate, 60Hz or not, so each
+// callback runs as many emulator frames as fit in the time since the last
+function frame(now) {
+  if (!running) return;
+  if (last !== null) acc += now - last;
+  last = now;
+  const due = Math.floor(acc / FRAME_MS);
+  const frames = Math.min(due, MAX_FRAMES);
+  acc = due > MAX_FRAMES ? 0 : acc - frames * FRAME_MS;
+  try {
+    let drew = false;
+    for (let i = 0; i < frames; i++) {
+      if (emu.run_frame()) drew = true;
+    }
+    if (drew) draw();
+  } catch (e) {
+    running = false;
+    gain.gain.value = 0;
+    status.textContent = `Error: ${e.message}`;
+    return;
+  }
+  gain.gain.value = emu.sound_active() ? 0.1 : 0;
+  requestAnimationFrame(frame);
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) return;
+  startAudio();
+  try {
+    emu.load_rom(new Uint8Array(await file.arrayBuffer()));
+  } catch (e) {
+    status.textContent = `Error: ${e.message}`;
+    return;
+  }
+  status.textContent = file.name;
+  draw();
+  if (!running) {
+    running = true;
+    acc = 0;
+    last = null;
+    requestAnimationFrame(frame);
+  }
+});
+
+for (const [type, down] of [["keydown", true], ["keyup", false]]) {
+  window.addEventListener(type, (event) => {
+    const key = KEYMAP[event.code];
+    if (key === undefined) return;
+    emu.set_key(key, down);
+    event.preventDefault();
+  });
+}
@@ -0,0 +1,63 @@
+// JS bindings over the emulator core for the browser frontend in main.js.
+//
+// The page drives timing: it calls run_frame 60 times a second, spread over
+// its requestAnimationFrame callbacks, and each call runs one 60Hz frame
+// worth of instructions and ticks the timers.
+
+use chip8_emu_rs::{FB_HEIGHT, FB_WIDTH, VM, VmConfig, instructions_per_frame};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    vm: VM,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    // `seed` feeds the CXNN generator; the page passes a random one
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Result<Emulator, JsError> {
+        let mut vm = VmConfig::new(|| 0).rng_seed(seed as u64).build()?;
+        vm.load_font();
+        Ok(Emulator { vm })
+    }
+
+    // Loads the ROM at the start address and resets the machine
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.vm.load_new_rom(rom)?;
+        Ok(())
+    }
+
+    // Runs one frame, returning whether the screen changed
+    pub fn run_frame(&mut self) -> Result<bool, JsError> {
+        let frame = self.vm.run_frame(instructions_per_frame(&self.vm));
+        match frame.fault {
+            Some(e) => Err(e.into()),
+            None => Ok(frame.drew),
+        }
+    }
+
+    // FB_WIDTH * FB_HEIGHT bytes, row by row, 1 for a lit pixel
+    pub fn framebuffer(&self) -> Vec<u8> {
+        (0..FB_WIDTH * FB_HEIGHT)
+            .map(|idx| self.vm.pixel(idx % FB_WIDTH, idx / FB_WIDTH) as u8)
+            .collect()
+    }
+
+    pub fn width() -> u32 {
+        FB_WIDTH
+    }
+
+    pub fn height() -> u32 {
+        FB_HEIGHT
+    }
+
+    // Keys outside 0-F are ignored
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        self.vm.set_key(key as usize, down);
+    }
+
+    pub fn sound_active(&self) -> bool {
+        self.vm.sound_timer > 0
+    }
+}